@@ -0,0 +1,146 @@
+// ============================================================================
+// Reference frames: where the frame chosen in SimParams sits and which way
+// it faces, worked out from the bodies about to be drawn, and the body
+// trails, extended in that frame after every physics step. Mirrors
+// frames.rs and cpu_physics::push_trails.
+// ============================================================================
+
+struct CelestialBody {
+    position: vec4<f32>,   // xyz = position, w = mass
+    velocity: vec4<f32>,   // xyz = velocity, w = radius
+    color: vec4<f32>,      // rgba
+    data: vec4<f32>,       // x = is_star, y = orbital_speed, z = fixed, w = physical radius
+    position_low: vec4<f32>, // xyz = df64 low part of the position
+    velocity_low: vec4<f32>, // xyz = df64 low part of the velocity
+};
+
+// Matches GridVertex in Rust
+struct TrailVertex {
+    position_pad: vec4<f32>, // xyz = pos, w = pad
+    color: vec4<f32>,
+};
+
+struct SimParams {
+    dt: f32,
+    gravitational_constant: f32,
+    num_particles: u32,
+    num_bodies: u32,
+    separation_radius: f32,
+    alignment_radius: f32,
+    cohesion_radius: f32,
+    separation_weight: f32,
+    alignment_weight: f32,
+    cohesion_weight: f32,
+    max_speed: f32,
+    max_force: f32,
+    target_x: f32,
+    target_y: f32,
+    target_z: f32,
+    target_active: f32,
+    softening: f32,
+    damping: f32,
+    swarm_gravity_weight: f32,
+    time: f32,
+    trail_length: u32,
+    despawn_radius: f32,
+    integrator: u32,
+    body_precision: u32,
+    one: f32,
+    // 0 = scenario, 1 = centred on body a, 2 = barycentre,
+    // 3 = rotating with the pair a, b
+    frame_kind: u32,
+    frame_a: u32,
+    frame_b: u32,
+};
+
+// A simulation point p sits at rows * (p - origin) in the frame
+struct Frame {
+    origin: vec4<f32>,
+    origin_low: vec4<f32>,
+    x_axis: vec4<f32>,
+    y_axis: vec4<f32>,
+    z_axis: vec4<f32>,
+};
+
+@group(0) @binding(0) var<storage, read> bodies: array<CelestialBody>;
+@group(0) @binding(1) var<uniform> params: SimParams;
+@group(0) @binding(2) var<storage, read_write> frame: Frame;
+@group(0) @binding(3) var<storage, read_write> trails: array<TrailVertex>;
+
+@compute @workgroup_size(1)
+fn cs_frame() {
+    var f: Frame;
+    f.origin = vec4<f32>(0.0);
+    f.origin_low = vec4<f32>(0.0);
+    f.x_axis = vec4<f32>(1.0, 0.0, 0.0, 0.0);
+    f.y_axis = vec4<f32>(0.0, 1.0, 0.0, 0.0);
+    f.z_axis = vec4<f32>(0.0, 0.0, 1.0, 0.0);
+    let a = params.frame_a;
+    let b = params.frame_b;
+    let n = params.num_bodies;
+
+    switch params.frame_kind {
+        case 1u: {
+            if (a < n) {
+                f.origin = vec4<f32>(bodies[a].position.xyz, 0.0);
+                f.origin_low = vec4<f32>(bodies[a].position_low.xyz, 0.0);
+            }
+        }
+        case 2u: {
+            var weighted = vec3<f32>(0.0);
+            var total = 0.0;
+            for (var i = 0u; i < n; i = i + 1u) {
+                weighted += bodies[i].position.xyz * bodies[i].position.w;
+                total += bodies[i].position.w;
+            }
+            if (total > 0.0) {
+                f.origin = vec4<f32>(weighted / total, 0.0);
+            }
+        }
+        case 3u: {
+            if (a < n && b < n) {
+                let body_a = bodies[a];
+                let body_b = bodies[b];
+                let total = body_a.position.w + body_b.position.w;
+                let wa = select(0.5, body_a.position.w / total, total > 0.0);
+                let wb = select(0.5, body_b.position.w / total, total > 0.0);
+                f.origin = vec4<f32>(body_a.position.xyz * wa + body_b.position.xyz * wb, 0.0);
+
+                // x from a to b, y along the pair's angular momentum on
+                // the +y side, so the frame stands upright
+                let r = body_b.position.xyz - body_a.position.xyz;
+                let h = cross(r, body_b.velocity.xyz - body_a.velocity.xyz);
+                let normal = select(h, -h, h.y < 0.0);
+                if (dot(r, r) > 0.0 && dot(normal, normal) > 0.0) {
+                    let x = normalize(r);
+                    let y = normalize(normal);
+                    f.x_axis = vec4<f32>(x, 0.0);
+                    f.y_axis = vec4<f32>(y, 0.0);
+                    f.z_axis = vec4<f32>(cross(x, y), 0.0);
+                }
+            }
+        }
+        default: {}
+    }
+    frame = f;
+}
+
+@compute @workgroup_size(32)
+fn cs_trails(@builtin(global_invocation_id) global_id: vec3<u32>) {
+    let index = global_id.x;
+    if (index >= params.num_bodies) { return; }
+    let body = bodies[index];
+
+    // Shift all points for this body by one index to create a "moving history"
+    let start_idx = index * params.trail_length;
+    let end_idx = start_idx + params.trail_length - 1u;
+    for (var i = start_idx; i < end_idx; i = i + 1u) {
+        trails[i] = trails[i + 1u];
+    }
+
+    // Write the body's position in the frame at the end of the trail
+    let d = (body.position.xyz - frame.origin.xyz) + (body.position_low.xyz - frame.origin_low.xyz);
+    let p = vec3<f32>(dot(frame.x_axis.xyz, d), dot(frame.y_axis.xyz, d), dot(frame.z_axis.xyz, d));
+    trails[end_idx].position_pad = vec4<f32>(p, 0.0);
+    trails[end_idx].color = body.color * 0.5; // Dimmer trail
+}
@@ -14,13 +14,9 @@ struct CelestialBody {
     position: vec4<f32>,   // xyz = position, w = mass
     velocity: vec4<f32>,   // xyz = velocity, w = radius
     color: vec4<f32>,      // rgba
-    data: vec4<f32>,       // x = is_star, y = orbital_speed, z = unused, w = unused
-};
-
-// Matches GridVertex in Rust
-struct TrailVertex {
-    position_pad: vec4<f32>, // xyz = pos, w = pad
-    color: vec4<f32>,
+    data: vec4<f32>,       // x = is_star, y = orbital_speed, z = fixed, w = physical radius
+    position_low: vec4<f32>, // xyz = df64 low part of the position
+    velocity_low: vec4<f32>, // xyz = df64 low part of the velocity
 };
 
 struct SimParams {
@@ -47,20 +43,44 @@ struct SimParams {
     damping: f32,
     swarm_gravity_weight: f32,
     time: f32,
+    // Points per body trail
+    trail_length: u32,
+    // Particles beyond this distance from the origin are removed
+    despawn_radius: f32,
+    // 0 = symplectic Euler, 1 = leapfrog (drift-kick-drift)
+    integrator: u32,
+    // Bodies: 0 = f32, 1 = df64 positions and velocities
+    body_precision: u32,
+    // Always 1.0, see df64 below
+    one: f32,
+    // Reference frame, see frame.wgsl
+    frame_kind: u32,
+    frame_a: u32,
+    frame_b: u32,
 };
 
+// Length of the opening drift: zero for Euler, half a step for leapfrog
+fn half_drift(dt: f32, integrator: u32) -> f32 {
+    return select(0.0, 0.5 * dt, integrator == 1u);
+}
+
 @group(0) @binding(0) var<storage, read> particles_in: array<Particle>;
 @group(0) @binding(1) var<storage, read_write> particles_out: array<Particle>;
 @group(0) @binding(2) var<storage, read> bodies: array<CelestialBody>;
 @group(0) @binding(3) var<uniform> params: SimParams;
 
-// Compute gravitational acceleration from all celestial bodies
-fn compute_gravity(pos: vec3<f32>) -> vec3<f32> {
+// Compute gravitational acceleration from all celestial bodies.
+// `rewind` moves the (already updated) bodies back to the mid-step
+// positions the leapfrog kick is evaluated at.
+fn compute_gravity(pos: vec3<f32>, rewind: f32) -> vec3<f32> {
     var accel = vec3<f32>(0.0, 0.0, 0.0);
     let num_bodies = params.num_bodies;
 
     for (var i = 0u; i < num_bodies; i = i + 1u) {
-        let body_pos = bodies[i].position.xyz;
+        var body_pos = bodies[i].position.xyz;
+        if (bodies[i].data.z < 0.5) {
+            body_pos -= bodies[i].velocity.xyz * rewind;
+        }
         let body_mass = bodies[i].position.w;
 
         let diff = body_pos - pos;
@@ -182,25 +202,25 @@ fn cs_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
         return;
     }
 
-    let pos = particle.position.xyz;
     let vel = particle.velocity.xyz;
     let mass = particle.position.w;
     let is_swarm = particle.velocity.w > 0.5;
     let dt = params.dt;
+    let half = half_drift(dt, params.integrator);
+    let pos = particle.position.xyz + vel * half;
 
     // Compute gravitational acceleration
-    var accel = compute_gravity(pos);
+    var accel = compute_gravity(pos, half);
 
     // Add swarm forces for swarm particles
     if (is_swarm) {
         let swarm_force = compute_swarm(index, pos, vel);
-        accel += swarm_force / max(mass, 0.01);
 
         // Reduce gravity influence for swarm particles (they have thrusters!)
-        accel = compute_gravity(pos) * params.swarm_gravity_weight + swarm_force / max(mass, 0.01);
+        accel = accel * params.swarm_gravity_weight + swarm_force / max(mass, 0.01);
     }
 
-    // Symplectic Euler integration (better energy conservation)
+    // Kick (symplectic Euler, or the middle of a leapfrog step)
     var new_vel = vel + accel * dt;
 
     // Apply speed limit for swarm particles
@@ -211,7 +231,7 @@ fn cs_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     // Apply damping
     new_vel *= params.damping;
 
-    let new_pos = pos + new_vel * dt;
+    let new_pos = pos + new_vel * (dt - half);
 
     // Check collision with celestial bodies
     var alive = particle.data.w;
@@ -225,7 +245,7 @@ fn cs_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     }
 
     // Despawn if too far from origin
-    if (length(new_pos) > 100.0) {
+    if (length(new_pos) > params.despawn_radius) {
         alive = 0.0;
     }
 
@@ -251,6 +271,96 @@ fn cs_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
     particles_out[index] = particle;
 }
 
+// ============================================================================
+// df64: double-single arithmetic. A value is the unevaluated sum hi + lo of
+// two f32s with |lo| <= ulp(hi) / 2, giving about 48 bits of mantissa.
+// Vectors of them are operated on lane by lane; scalars are splatted across
+// all three lanes. After Dekker (1971) and Thall (2006).
+//
+// The error terms are algebraically zero, and shader compilers free to
+// reassociate floats fold them away: `(a + b) - a` becomes `b`. Multiplying
+// by `orbit_params.one`, a 1.0 they cannot see, keeps them. Products are
+// split by hand because `fma` is not fused on every backend.
+// ============================================================================
+
+struct Df64 {
+    hi: vec3<f32>,
+    lo: vec3<f32>,
+};
+
+fn df64_splat(v: f32) -> Df64 {
+    return Df64(vec3<f32>(v), vec3<f32>(0.0));
+}
+
+// a + b exactly, for any a and b
+fn two_sum(a: vec3<f32>, b: vec3<f32>) -> Df64 {
+    let s = (a + b) * orbit_params.one;
+    let v = s - a;
+    return Df64(s, (a - (s - v)) + (b - v));
+}
+
+// a + b exactly, for |a| >= |b|
+fn quick_two_sum(a: vec3<f32>, b: vec3<f32>) -> Df64 {
+    let s = (a + b) * orbit_params.one;
+    return Df64(s, b - (s - a));
+}
+
+// a as two halves of 12 bits each, whose products are exact
+fn split(a: vec3<f32>) -> Df64 {
+    let t = (4097.0 * a) * orbit_params.one;
+    let hi = t - (t - a);
+    return Df64(hi, a - hi);
+}
+
+// a * b exactly
+fn two_prod(a: vec3<f32>, b: vec3<f32>) -> Df64 {
+    let p = (a * b) * orbit_params.one;
+    let x = split(a);
+    let y = split(b);
+    return Df64(p, ((x.hi * y.hi - p) + x.hi * y.lo + x.lo * y.hi) + x.lo * y.lo);
+}
+
+fn df64_add(a: Df64, b: Df64) -> Df64 {
+    let s = two_sum(a.hi, b.hi);
+    let t = two_sum(a.lo, b.lo);
+    let u = quick_two_sum(s.hi, s.lo + t.hi);
+    return quick_two_sum(u.hi, u.lo + t.lo);
+}
+
+fn df64_sub(a: Df64, b: Df64) -> Df64 {
+    return df64_add(a, Df64(-b.hi, -b.lo));
+}
+
+fn df64_mul(a: Df64, b: Df64) -> Df64 {
+    let p = two_prod(a.hi, b.hi);
+    return quick_two_sum(p.hi, p.lo + (a.hi * b.lo + a.lo * b.hi));
+}
+
+// Long division: each step adds the f32 quotient of what remains
+fn df64_div(a: Df64, b: Df64) -> Df64 {
+    let q1 = a.hi / b.hi;
+    let r1 = df64_sub(a, df64_mul(b, Df64(q1, vec3<f32>(0.0))));
+    let q2 = r1.hi / b.hi;
+    let r2 = df64_sub(r1, df64_mul(b, Df64(q2, vec3<f32>(0.0))));
+    let q = quick_two_sum(q1, q2);
+    return df64_add(q, Df64(r2.hi / b.hi, vec3<f32>(0.0)));
+}
+
+// Every lane holds the sum of a's three lanes
+fn df64_sum_lanes(a: Df64) -> Df64 {
+    let x = Df64(a.hi.xxx, a.lo.xxx);
+    let y = Df64(a.hi.yyy, a.lo.yyy);
+    let z = Df64(a.hi.zzz, a.lo.zzz);
+    return df64_add(df64_add(x, y), z);
+}
+
+// One Newton step on the f32 square root; a must be positive
+fn df64_sqrt(a: Df64) -> Df64 {
+    let x = sqrt(a.hi);
+    let r = df64_sub(a, two_prod(x, x));
+    return quick_two_sum(x, r.hi / (2.0 * x));
+}
+
 // ============================================================================
 // Celestial body orbital compute shader
 // ============================================================================
@@ -258,7 +368,44 @@ fn cs_main(@builtin(global_invocation_id) global_id: vec3<u32>) {
 @group(0) @binding(0) var<storage, read> bodies_in: array<CelestialBody>;
 @group(0) @binding(1) var<storage, read_write> bodies_out_buf: array<CelestialBody>;
 @group(0) @binding(2) var<uniform> orbit_params: SimParams;
-@group(0) @binding(3) var<storage, read_write> trails: array<TrailVertex>;
+
+// The physics of cs_orbit in df64, for body `index` drifting by `half`
+// either side of the kick. Only the high parts are seen by rendering.
+fn orbit_df64(index: u32, body_in: CelestialBody, half: f32, G: f32) -> CelestialBody {
+    var body = body_in;
+    let dt = df64_splat(orbit_params.dt);
+    let half_dt = df64_splat(half);
+    let softening = vec3<f32>(orbit_params.softening);
+    let soft_sq = two_prod(softening, softening);
+
+    let vel = Df64(body.velocity.xyz, body.velocity_low.xyz);
+    let pos = df64_add(Df64(body.position.xyz, body.position_low.xyz), df64_mul(vel, half_dt));
+
+    var accel = df64_splat(0.0);
+    for (var i = 0u; i < orbit_params.num_bodies; i = i + 1u) {
+        if (i == index) { continue; }
+        let other = bodies_in[i];
+        var other_pos = Df64(other.position.xyz, other.position_low.xyz);
+        if (other.data.z < 0.5) {
+            let other_vel = Df64(other.velocity.xyz, other.velocity_low.xyz);
+            other_pos = df64_add(other_pos, df64_mul(other_vel, half_dt));
+        }
+        let diff = df64_sub(other_pos, pos);
+        let dist_sq = df64_add(df64_sum_lanes(df64_mul(diff, diff)), soft_sq);
+        let dist3 = df64_mul(df64_sqrt(dist_sq), dist_sq);
+        let strength = df64_div(two_prod(vec3<f32>(G), vec3<f32>(other.position.w)), dist3);
+        accel = df64_add(accel, df64_mul(diff, strength));
+    }
+
+    let new_vel = df64_add(vel, df64_mul(accel, dt));
+    let new_pos = df64_add(pos, df64_mul(new_vel, df64_sub(dt, half_dt)));
+
+    body.position = vec4<f32>(new_pos.hi, body.position.w);
+    body.position_low = vec4<f32>(new_pos.lo, 0.0);
+    body.velocity = vec4<f32>(new_vel.hi, body.velocity.w);
+    body.velocity_low = vec4<f32>(new_vel.lo, 0.0);
+    return body;
+}
 
 @compute @workgroup_size(32)
 fn cs_orbit(@builtin(global_invocation_id) global_id: vec3<u32>) {
@@ -266,56 +413,51 @@ fn cs_orbit(@builtin(global_invocation_id) global_id: vec3<u32>) {
     if (index >= orbit_params.num_bodies) { return; }
 
     var body = bodies_in[index];
-    let TRAIL_LENGTH = 512u;
-
-    // 1. UPDATE PHYSICS
 
-    // Stars don't move
-    if (body.data.x > 0.5) {
+    // Fixed bodies (by default the central star) don't move
+    if (body.data.z > 0.5) {
         bodies_out_buf[index] = body;
         return;
     }
 
-    let pos = body.position.xyz;
     let vel = body.velocity.xyz;
     let mass = body.position.w;
     let dt = orbit_params.dt;
+    let half = half_drift(dt, orbit_params.integrator);
+    let pos = body.position.xyz + vel * half;
 
     // HARDCODED G: Locks planetary orbits so user tuning of swarm gravity
     // doesn't cause planets to fly off into deep space.
     let G = 39.4784176;
 
-    // Compute gravity from other bodies
-    var accel = vec3<f32>(0.0);
-    for (var i = 0u; i < orbit_params.num_bodies; i = i + 1u) {
-        if (i == index) { continue; }
-        let other_pos = bodies_in[i].position.xyz;
-        let other_mass = bodies_in[i].position.w;
-        let diff = other_pos - pos;
-        let dist_sq = dot(diff, diff) + orbit_params.softening * orbit_params.softening;
-        let dist = sqrt(dist_sq);
-        let inv_dist3 = 1.0 / (dist * dist_sq);
-        accel += diff * (G * other_mass * inv_dist3);
-    }
-
-    // Integration
-    let new_vel = vel + accel * dt;
-    let new_pos = pos + new_vel * dt;
-
-    body.position = vec4<f32>(new_pos, mass);
-    body.velocity = vec4<f32>(new_vel, body.velocity.w);
-
-    bodies_out_buf[index] = body;
+    if (orbit_params.body_precision == 1u) {
+        body = orbit_df64(index, body, half, G);
+    } else {
+        // Compute gravity from other bodies
+        var accel = vec3<f32>(0.0);
+        for (var i = 0u; i < orbit_params.num_bodies; i = i + 1u) {
+            if (i == index) { continue; }
+            var other_pos = bodies_in[i].position.xyz;
+            if (bodies_in[i].data.z < 0.5) {
+                other_pos += bodies_in[i].velocity.xyz * half;
+            }
+            let other_mass = bodies_in[i].position.w;
+            let diff = other_pos - pos;
+            let dist_sq = dot(diff, diff) + orbit_params.softening * orbit_params.softening;
+            let dist = sqrt(dist_sq);
+            let inv_dist3 = 1.0 / (dist * dist_sq);
+            accel += diff * (G * other_mass * inv_dist3);
+        }
 
-    // 2. UPDATE TRAILS
-    // Shift all points for this body by one index to create a "moving history"
-    let start_idx = index * TRAIL_LENGTH;
+        // Integration: kick, then the rest of the drift
+        let new_vel = vel + accel * dt;
+        let new_pos = pos + new_vel * (dt - half);
 
-    for (var i = 0u; i < TRAIL_LENGTH - 1u; i = i + 1u) {
-        trails[start_idx + i] = trails[start_idx + i + 1u];
+        body.position = vec4<f32>(new_pos, mass);
+        body.velocity = vec4<f32>(new_vel, body.velocity.w);
+        body.position_low = vec4<f32>(0.0);
+        body.velocity_low = vec4<f32>(0.0);
     }
 
-    // Write new position at the end of the trail
-    trails[start_idx + TRAIL_LENGTH - 1u].position_pad = vec4<f32>(new_pos, 0.0);
-    trails[start_idx + TRAIL_LENGTH - 1u].color = body.color * 0.5; // Dimmer trail
-}
\ No newline at end of file
+    bodies_out_buf[index] = body;
+}
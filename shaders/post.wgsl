@@ -0,0 +1,151 @@
+// ============================================================================
+// Post-processing: bloom and tone mapping.
+// The scene is rendered into an Rgba16Float target. Bright areas are
+// downsampled through a chain of half-size targets (13-tap filter), blurred
+// back up with a 3x3 tent filter, added to the scene and tone mapped into
+// the swapchain. Tiled captures average every tile into one full-frame
+// bloom source first, so the glow spans tiles.
+// ============================================================================
+
+struct PostParams {
+    exposure: f32,
+    bloom_intensity: f32,
+    bloom_threshold: f32,
+    bloom_knee: f32,       // soft threshold width
+    bloom_radius: f32,     // upsample filter radius in texels
+    tonemap: u32,          // 0 = ACES, 1 = Reinhard, 2 = linear (clamp)
+    reduction: u32,        // scene pixels per bloom source pixel, each way
+    _pad0: u32,
+    bloom_rect: vec4<f32>, // part of the bloom texture under the scene: uv offset, uv size
+    tile_origin: vec2<f32>, // the scene's top-left pixel in a tiled capture
+    _pad1: vec2<f32>,
+};
+
+@group(0) @binding(0) var source: texture_2d<f32>;
+@group(0) @binding(1) var source_sampler: sampler;
+@group(0) @binding(2) var<uniform> post: PostParams;
+// Composite pass only
+@group(0) @binding(3) var bloom: texture_2d<f32>;
+
+struct FullscreenVsOut {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_fullscreen(@builtin(vertex_index) vertex_index: u32) -> FullscreenVsOut {
+    var out: FullscreenVsOut;
+    let uv = vec2<f32>(
+        f32((vertex_index << 1u) & 2u),
+        f32(vertex_index & 2u)
+    );
+    out.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+fn tap(uv: vec2<f32>, texel: vec2<f32>, x: f32, y: f32) -> vec3<f32> {
+    return textureSampleLevel(source, source_sampler, uv + texel * vec2<f32>(x, y), 0.0).rgb;
+}
+
+// 13-tap downsample (Jimenez, "Next Generation Post Processing in Call of Duty")
+fn downsample13(uv: vec2<f32>) -> vec3<f32> {
+    let texel = 1.0 / vec2<f32>(textureDimensions(source));
+    let a = tap(uv, texel, -2.0,  2.0);
+    let b = tap(uv, texel,  0.0,  2.0);
+    let c = tap(uv, texel,  2.0,  2.0);
+    let d = tap(uv, texel, -2.0,  0.0);
+    let e = tap(uv, texel,  0.0,  0.0);
+    let f = tap(uv, texel,  2.0,  0.0);
+    let g = tap(uv, texel, -2.0, -2.0);
+    let h = tap(uv, texel,  0.0, -2.0);
+    let i = tap(uv, texel,  2.0, -2.0);
+    let j = tap(uv, texel, -1.0,  1.0);
+    let k = tap(uv, texel,  1.0,  1.0);
+    let l = tap(uv, texel, -1.0, -1.0);
+    let m = tap(uv, texel,  1.0, -1.0);
+    return e * 0.125
+        + (a + c + g + i) * 0.03125
+        + (b + d + f + h) * 0.0625
+        + (j + k + l + m) * 0.125;
+}
+
+// Soft-knee threshold: keep what is brighter than the threshold
+fn bright_pass(color: vec3<f32>) -> vec3<f32> {
+    let brightness = max(color.r, max(color.g, color.b));
+    let knee = max(post.bloom_knee, 1e-4);
+    var soft = clamp(brightness - post.bloom_threshold + knee, 0.0, 2.0 * knee);
+    soft = soft * soft / (4.0 * knee);
+    let contribution = max(soft, brightness - post.bloom_threshold) / max(brightness, 1e-4);
+    return color * contribution;
+}
+
+@fragment
+fn fs_prefilter(in: FullscreenVsOut) -> @location(0) vec4<f32> {
+    // Clamp first so a single inf/NaN pixel can't flood the chain
+    let color = min(downsample13(in.uv), vec3<f32>(60000.0));
+    return vec4<f32>(bright_pass(color), 1.0);
+}
+
+@fragment
+fn fs_downsample(in: FullscreenVsOut) -> @location(0) vec4<f32> {
+    return vec4<f32>(downsample13(in.uv), 1.0);
+}
+
+// 3x3 tent upsample, blended additively onto the next larger level
+@fragment
+fn fs_upsample(in: FullscreenVsOut) -> @location(0) vec4<f32> {
+    let texel = post.bloom_radius / vec2<f32>(textureDimensions(source));
+    let uv = in.uv;
+    var sum = tap(uv, texel, 0.0, 0.0) * 4.0;
+    sum += (tap(uv, texel, -1.0, 0.0) + tap(uv, texel, 1.0, 0.0)
+          + tap(uv, texel, 0.0, -1.0) + tap(uv, texel, 0.0, 1.0)) * 2.0;
+    sum += tap(uv, texel, -1.0, -1.0) + tap(uv, texel, 1.0, -1.0)
+         + tap(uv, texel, -1.0, 1.0) + tap(uv, texel, 1.0, 1.0);
+    return vec4<f32>(sum / 16.0, 1.0);
+}
+
+// Box-average a capture tile into the full-frame bloom source, whose
+// pixels each cover `reduction` scene pixels each way
+@fragment
+fn fs_reduce(in: FullscreenVsOut) -> @location(0) vec4<f32> {
+    let n = i32(post.reduction);
+    let first = vec2<i32>(floor(in.position.xy)) * n - vec2<i32>(post.tile_origin);
+    let last = vec2<i32>(textureDimensions(source)) - 1;
+    var sum = vec3<f32>(0.0);
+    for (var y = 0; y < n; y++) {
+        for (var x = 0; x < n; x++) {
+            let texel = clamp(first + vec2<i32>(x, y), vec2<i32>(0), last);
+            sum += min(textureLoad(source, texel, 0).rgb, vec3<f32>(60000.0));
+        }
+    }
+    return vec4<f32>(sum / f32(n * n), 1.0);
+}
+
+// Narkowicz's fit of the ACES filmic curve
+fn aces(x: vec3<f32>) -> vec3<f32> {
+    return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// Luminance-based Reinhard, which keeps hues instead of desaturating per channel
+fn reinhard(x: vec3<f32>) -> vec3<f32> {
+    let luma = dot(x, vec3<f32>(0.2126, 0.7152, 0.0722));
+    return x / (1.0 + luma);
+}
+
+@fragment
+fn fs_composite(in: FullscreenVsOut) -> @location(0) vec4<f32> {
+    let scene = textureSampleLevel(source, source_sampler, in.uv, 0.0).rgb;
+    let glow_uv = post.bloom_rect.xy + in.uv * post.bloom_rect.zw;
+    let glow = textureSampleLevel(bloom, source_sampler, glow_uv, 0.0).rgb;
+    let hdr = (scene + glow * post.bloom_intensity) * post.exposure;
+
+    var ldr: vec3<f32>;
+    switch post.tonemap {
+        case 0u: { ldr = aces(hdr); }
+        case 1u: { ldr = reinhard(hdr); }
+        default: { ldr = clamp(hdr, vec3<f32>(0.0), vec3<f32>(1.0)); }
+    }
+    // The swapchain is sRGB, so the hardware applies the transfer curve
+    return vec4<f32>(ldr, 1.0);
+}
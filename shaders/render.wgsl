@@ -4,14 +4,137 @@
 // ============================================================================
 
 struct Camera {
-    view_proj: mat4x4<f32>,
-    view: mat4x4<f32>,
+    view_proj: mat4x4<f32>,  // view and view_proj turn but do not move:
+    view: mat4x4<f32>,       // they take positions relative to the eye
     proj: mat4x4<f32>,
-    eye_pos: vec4<f32>,
-    screen_size: vec4<f32>,  // xy = screen size, z = time, w = unused
+    eye_pos: vec4<f32>,      // eye = eye_pos + eye_low, split from a double
+    eye_low: vec4<f32>,
+    screen_size: vec4<f32>,  // xy = screen size, z = time, w = true scale (0/1)
+    background: vec4<f32>,   // x = starfield brightness
+    scene: vec4<f32>,        // x = body count, y = trail width (px), z = trail length, w = pixel scale
+    selection: vec4<f32>,    // x = 0 none, 1 body, 2 particle; y = index
+};
+
+// Reference frame the camera sits in, written by cs_frame. A simulation
+// point p sits at rows * (p - origin) in it.
+struct Frame {
+    origin: vec4<f32>,
+    origin_low: vec4<f32>,
+    x_axis: vec4<f32>,
+    y_axis: vec4<f32>,
+    z_axis: vec4<f32>,
 };
 
 @group(0) @binding(0) var<uniform> camera: Camera;
+@group(0) @binding(1) var<uniform> frame: Frame;
+
+// Scene geometry and lighting stay in simulation coordinates, and the
+// camera in the reference frame: the frame's rotation is folded into the
+// view, and the eye is carried back into simulation axes
+fn frame_rotation() -> mat4x4<f32> {
+    return transpose(mat4x4<f32>(frame.x_axis, frame.y_axis, frame.z_axis, vec4<f32>(0.0, 0.0, 0.0, 1.0)));
+}
+
+fn view() -> mat4x4<f32> {
+    return camera.view * frame_rotation();
+}
+
+fn view_proj() -> mat4x4<f32> {
+    return camera.view_proj * frame_rotation();
+}
+
+// Frame vector `v` in simulation axes
+fn from_frame_axes(v: vec3<f32>) -> vec3<f32> {
+    return frame.x_axis.xyz * v.x + frame.y_axis.xyz * v.y + frame.z_axis.xyz * v.z;
+}
+
+// World position `p` relative to the eye. The high part cancels exactly
+// near the eye, so nearby geometry keeps full precision far from the origin.
+fn to_eye(p: vec3<f32>) -> vec3<f32> {
+    let from_origin = (p - frame.origin.xyz) - frame.origin_low.xyz;
+    return (from_origin - from_frame_axes(camera.eye_pos.xyz)) - from_frame_axes(camera.eye_low.xyz);
+}
+
+// `to_eye` for a position already in the reference frame
+fn frame_to_eye(p: vec3<f32>) -> vec3<f32> {
+    return (p - camera.eye_pos.xyz) - camera.eye_low.xyz;
+}
+
+// An orthographic projection leaves w at 1, a perspective one sets it from depth
+fn is_orthographic() -> bool {
+    return camera.proj[3][3] > 0.5;
+}
+
+// World size of one pixel at view depth `depth`
+fn pixel_size(depth: f32) -> f32 {
+    let scale = select(depth, 1.0, is_orthographic());
+    return 2.0 * scale / (camera.proj[1][1] * camera.screen_size.y);
+}
+
+// Direction of the eye ray through the point at `relative` to the eye: all
+// rays are parallel to the view axis in an orthographic view
+fn view_ray(relative: vec3<f32>) -> vec3<f32> {
+    if (is_orthographic()) {
+        let v = view();
+        return -vec3<f32>(v[0][2], v[1][2], v[2][2]);
+    }
+    return normalize(relative);
+}
+
+// ============================================================================
+// Background starfield, drawn first at infinity. Directions have w = 0 so
+// only the camera rotation applies; no depth test or write. An orthographic
+// view has no point at infinity, so the stars drop out there.
+// ============================================================================
+
+struct StarInstance {
+    @location(0) direction: vec4<f32>,  // xyz = direction, w = radius (pixels)
+    @location(1) color: vec4<f32>,
+};
+
+struct StarVsOut {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_star(
+    @builtin(vertex_index) vertex_index: u32,
+    instance: StarInstance,
+) -> StarVsOut {
+    var out: StarVsOut;
+    var clip = view_proj() * vec4<f32>(instance.direction.xyz, 0.0);
+    if (clip.w <= 0.0 || camera.background.x <= 0.0) {
+        out.position = vec4<f32>(0.0, 0.0, -999.0, 1.0);
+        return out;
+    }
+
+    var quad_positions = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>( 1.0, -1.0),
+        vec2<f32>(-1.0,  1.0),
+        vec2<f32>(-1.0,  1.0),
+        vec2<f32>( 1.0, -1.0),
+        vec2<f32>( 1.0,  1.0),
+    );
+    let quad_pos = quad_positions[vertex_index % 6u];
+
+    // Fixed pixel size regardless of zoom
+    let offset = quad_pos * instance.direction.w * camera.scene.w * 2.0 / camera.screen_size.xy;
+    clip = vec4<f32>(clip.xy + offset * clip.w, 0.0, clip.w);
+
+    out.position = clip;
+    out.color = instance.color.rgb * camera.background.x;
+    out.uv = quad_pos;
+    return out;
+}
+
+@fragment
+fn fs_star(in: StarVsOut) -> @location(0) vec4<f32> {
+    let falloff = exp(-4.0 * dot(in.uv, in.uv));
+    return vec4<f32>(in.color * falloff, 1.0);
+}
 
 // ============================================================================
 // Billboard particle rendering (for spacecraft/satellites)
@@ -59,15 +182,20 @@ fn vs_particle(
     let quad_pos = quad_positions[vertex_index % 6u];
 
     // Extract camera right and up from view matrix
-    let cam_right = vec3<f32>(camera.view[0][0], camera.view[1][0], camera.view[2][0]);
-    let cam_up = vec3<f32>(camera.view[0][1], camera.view[1][1], camera.view[2][1]);
+    let cam_right = from_frame_axes(vec3<f32>(camera.view[0][0], camera.view[1][0], camera.view[2][0]));
+    let cam_up = from_frame_axes(vec3<f32>(camera.view[0][1], camera.view[1][1], camera.view[2][1]));
 
     let size = instance.data.x * 2.0;
     let center = instance.position.xyz;
-    let world_pos = center + (cam_right * quad_pos.x + cam_up * quad_pos.y) * size;
+    let corner = (cam_right * quad_pos.x + cam_up * quad_pos.y) * size;
+    let world_pos = center + corner;
 
-    out.position = camera.view_proj * vec4<f32>(world_pos, 1.0);
-    out.color = instance.color;
+    // Dimmed where a planet or moon eclipses the stars
+    let light = lighting(center, vec3<f32>(0.0), vec3<f32>(0.0), NO_BODY).incident;
+    let lit = min(max(light.r, max(light.g, light.b)), 1.0);
+
+    out.position = view_proj() * vec4<f32>(to_eye(center) + corner, 1.0);
+    out.color = vec4<f32>(instance.color.rgb * (0.25 + 0.75 * lit), instance.color.a);
     out.uv = quad_pos;
     out.world_pos = world_pos;
 
@@ -91,16 +219,162 @@ fn fs_particle(in: ParticleVsOut) -> @location(0) vec4<f32> {
 }
 
 // ============================================================================
-// Celestial body rendering (sphere impostor via billboards)
+// Per-body scene data for the body and ring pipelines, indexed like the
+// body buffer: current body states, ring systems and surfaces
+// ============================================================================
+
+struct Body {
+    position: vec4<f32>,
+    velocity: vec4<f32>,
+    color: vec4<f32>,
+    data: vec4<f32>,
+    position_low: vec4<f32>, // df64 low parts; rendering uses the f32 high parts
+    velocity_low: vec4<f32>,
+};
+
+struct Ring {
+    normal: vec4<f32>,              // xyz = plane normal, w = body index
+    radii: vec4<f32>,               // x = inner, y = outer, in body radii
+    color: vec4<f32>,
+    profile: array<vec4<f32>, 4>,   // 16 opacity samples, inner to outer
+};
+
+struct Surface {
+    axis: vec4<f32>,        // xyz = spin axis, w = rotation period (years)
+    meridian: vec4<f32>,    // xyz = prime meridian at t = 0, w = texture layer (-1 = procedural)
+    secondary: vec4<f32>,   // rgb = second procedural colour
+    params: vec4<f32>,      // x = style, y = seed, z = rotation angle, w = luminosity
+};
+
+@group(1) @binding(0) var<storage, read> bodies: array<Body>;
+@group(1) @binding(1) var<storage, read> rings: array<Ring>;
+@group(1) @binding(2) var<storage, read> surfaces: array<Surface>;
+@group(1) @binding(3) var surface_textures: texture_2d_array<f32>;
+@group(1) @binding(4) var surface_sampler: sampler;
+
+// ============================================================================
+// Lighting from every star body, with soft shadows where another sphere
+// covers part of a star's disk
+// ============================================================================
+
+const NO_BODY: u32 = 0xffffffffu;
+
+struct Lighting {
+    incident: vec3<f32>,    // arriving light, ignoring orientation
+    diffuse: vec3<f32>,     // Lambert term for the given normal
+    two_sided: vec3<f32>,   // Lambert term lit from either face
+    specular: vec3<f32>,    // Blinn-Phong term for the given view direction
+};
+
+fn body_radius(b: Body) -> f32 {
+    return select(b.velocity.w, b.data.w, camera.screen_size.w > 0.5);
+}
+
+fn star_flux(star: u32, p: vec3<f32>) -> f32 {
+    let d = bodies[star].position.xyz - p;
+    return surfaces[star].params.w / max(dot(d, d), 1e-12);
+}
+
+/// Fraction of a star's disk visible from `p` past every other sphere,
+/// except `skip` (the body being lit)
+fn star_visibility(p: vec3<f32>, star: u32, skip: u32) -> f32 {
+    let to_star = bodies[star].position.xyz - p;
+    let star_dist = length(to_star);
+    let dir = to_star / star_dist;
+    let star_size = asin(min(body_radius(bodies[star]) / star_dist, 1.0));
+
+    var visible = 1.0;
+    let count = u32(camera.scene.x);
+    for (var j = 0u; j < count; j++) {
+        if (j == star || j == skip) { continue; }
+        let to_body = bodies[j].position.xyz - p;
+        let along = dot(to_body, dir);
+        let dist = length(to_body);
+        let radius = body_radius(bodies[j]);
+        if (along <= 0.0 || along >= star_dist || dist <= radius) { continue; }
+
+        // Angular radii and separation; atan2 stays accurate at small angles
+        let size = asin(radius / dist);
+        let sep = atan2(length(cross(to_body, dir)), along);
+        // Covered fraction at full overlap, eased across the penumbra
+        let full = min(size * size / max(star_size * star_size, 1e-12), 1.0);
+        let coverage = full * (1.0 - smoothstep(abs(star_size - size), star_size + size, sep));
+        visible *= 1.0 - coverage;
+    }
+    return visible;
+}
+
+/// Light at `p` from all stars. The brightest star there is normalised to
+/// full strength so outer planets stay visible; the others scale with
+/// their flux relative to it. Without stars, a white light at the origin.
+fn lighting(p: vec3<f32>, normal: vec3<f32>, view_dir: vec3<f32>, skip: u32) -> Lighting {
+    var out: Lighting;
+    let count = u32(camera.scene.x);
+    var brightest = 0.0;
+    for (var i = 0u; i < count; i++) {
+        if (bodies[i].data.x > 0.5) {
+            brightest = max(brightest, star_flux(i, p));
+        }
+    }
+
+    if (brightest <= 0.0) {
+        let l = normalize(-p);
+        out.incident = vec3<f32>(1.0);
+        out.diffuse = vec3<f32>(max(dot(normal, l), 0.0));
+        out.two_sided = vec3<f32>(abs(dot(normal, l)));
+        out.specular = vec3<f32>(pow(max(dot(normal, normalize(l + view_dir)), 0.0), 32.0));
+        return out;
+    }
+
+    for (var i = 0u; i < count; i++) {
+        let star = bodies[i];
+        if (star.data.x < 0.5) { continue; }
+        let weight = star_flux(i, p) / brightest;
+        if (weight < 1e-3) { continue; }
+        // Half-saturated star colour, so a yellow sun doesn't stain everything
+        let tint = mix(vec3<f32>(1.0), star.color.rgb / max(max(star.color.r, star.color.g), max(star.color.b, 1e-3)), 0.5);
+        let light = tint * weight * star_visibility(p, i, skip);
+        let l = normalize(star.position.xyz - p);
+        out.incident += light;
+        out.diffuse += light * max(dot(normal, l), 0.0);
+        out.two_sided += light * abs(dot(normal, l));
+        out.specular += light * pow(max(dot(normal, normalize(l + view_dir)), 0.0), 32.0);
+    }
+    return out;
+}
+
+// ============================================================================
+// Celestial body rendering (sphere impostor via billboards). The eye ray
+// through each fragment is intersected with the sphere for the normal
+// and the depth, so bodies cut into trails and particles correctly.
 // ============================================================================
 
 struct BodyInstance {
     @location(0) position: vec4<f32>,  // xyz = pos, w = mass
     @location(1) velocity: vec4<f32>,  // xyz = vel, w = radius
     @location(2) color: vec4<f32>,
-    @location(3) data: vec4<f32>,      // x = is_star
+    @location(3) data: vec4<f32>,      // x = is_star, w = physical radius
 };
 
+// True-scale mode: bodies never shrink below this many pixels in radius,
+// and anything smaller than MARKER_BELOW_PIXELS gets a ring marker
+const MIN_BODY_PIXELS: f32 = 1.5;
+const MARKER_BELOW_PIXELS: f32 = 3.0;
+const MARKER_PIXELS: f32 = 9.0;
+
+// Glow and markers are written at the far end of the depth range, so
+// they never hide trails or particles drawn after the bodies
+const GLOW_DEPTH: f32 = 1e-9;
+
+const PI: f32 = 3.14159265;
+const TAU: f32 = 6.28318531;
+
+// Procedural surface styles, as in surface::SurfaceStyle
+const STYLE_ROCKY: u32 = 0u;
+const STYLE_BANDED: u32 = 1u;
+const STYLE_TERRAN: u32 = 2u;
+const STYLE_ICY: u32 = 3u;
+
 struct BodyVsOut {
     @builtin(position) position: vec4<f32>,
     @location(0) color: vec4<f32>,
@@ -108,11 +382,20 @@ struct BodyVsOut {
     @location(2) world_center: vec3<f32>,
     @location(3) radius: f32,
     @location(4) is_star: f32,
+    @location(5) pixels_per_radius: f32, // > 0 when a marker ring is drawn
+    @location(6) offset: vec3<f32>,      // billboard point relative to the centre
+    @location(7) @interpolate(flat) index: u32,
+};
+
+struct BodyFsOut {
+    @location(0) color: vec4<f32>,
+    @builtin(frag_depth) depth: f32,
 };
 
 @vertex
 fn vs_body(
     @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
     instance: BodyInstance,
 ) -> BodyVsOut {
     var out: BodyVsOut;
@@ -128,29 +411,164 @@ fn vs_body(
     );
 
     let quad_pos = quad_positions[vertex_index % 6u];
-    let cam_right = vec3<f32>(camera.view[0][0], camera.view[1][0], camera.view[2][0]);
-    let cam_up = vec3<f32>(camera.view[0][1], camera.view[1][1], camera.view[2][1]);
+    let cam_right = from_frame_axes(vec3<f32>(camera.view[0][0], camera.view[1][0], camera.view[2][0]));
+    let cam_up = from_frame_axes(vec3<f32>(camera.view[0][1], camera.view[1][1], camera.view[2][1]));
 
-    let radius = instance.velocity.w;
     let center = instance.position.xyz;
+    var radius = instance.velocity.w;
     // Make billboard slightly larger to accommodate glow
-    let billboard_size = radius * 2.5;
-    let world_pos = center + (cam_right * quad_pos.x + cam_up * quad_pos.y) * billboard_size;
+    var extent = 2.5;
+    out.pixels_per_radius = 0.0;
+
+    if (camera.screen_size.w > 0.5) {
+        // World size of one pixel at the body's view depth
+        let depth = max(-(view() * vec4<f32>(to_eye(center), 1.0)).z, 1e-6);
+        let pixel = pixel_size(depth);
+        let physical = instance.data.w;
+        radius = max(physical, pixel * MIN_BODY_PIXELS * camera.scene.w);
+        if (physical < pixel * MARKER_BELOW_PIXELS * camera.scene.w) {
+            out.pixels_per_radius = radius / (pixel * camera.scene.w);
+            extent = max(extent, MARKER_PIXELS * 1.2 / out.pixels_per_radius);
+        }
+    }
+
+    let billboard_size = radius * extent;
+    let offset = (cam_right * quad_pos.x + cam_up * quad_pos.y) * billboard_size;
 
-    out.position = camera.view_proj * vec4<f32>(world_pos, 1.0);
+    out.position = view_proj() * vec4<f32>(to_eye(center) + offset, 1.0);
     out.color = instance.color;
-    out.uv = quad_pos * 2.5; // scale UV to match enlarged billboard
+    out.uv = quad_pos * extent; // scale UV to match enlarged billboard
     out.world_center = center;
     out.radius = radius;
     out.is_star = instance.data.x;
+    out.offset = offset;
+    out.index = instance_index;
 
     return out;
 }
 
+// ----------------------------------------------------------------------------
+// Procedural surfaces: value-noise fBm on the body-fixed unit sphere
+// ----------------------------------------------------------------------------
+
+fn hash3(p: vec3<f32>) -> f32 {
+    let q = fract(p * 0.3183099 + vec3<f32>(0.71, 0.113, 0.419)) * 17.0;
+    return fract(q.x * q.y * q.z * (q.x + q.y + q.z));
+}
+
+fn value_noise(p: vec3<f32>) -> f32 {
+    let i = floor(p);
+    let f = fract(p);
+    let u = f * f * (3.0 - 2.0 * f);
+    let x00 = mix(hash3(i), hash3(i + vec3<f32>(1.0, 0.0, 0.0)), u.x);
+    let x10 = mix(hash3(i + vec3<f32>(0.0, 1.0, 0.0)), hash3(i + vec3<f32>(1.0, 1.0, 0.0)), u.x);
+    let x01 = mix(hash3(i + vec3<f32>(0.0, 0.0, 1.0)), hash3(i + vec3<f32>(1.0, 0.0, 1.0)), u.x);
+    let x11 = mix(hash3(i + vec3<f32>(0.0, 1.0, 1.0)), hash3(i + vec3<f32>(1.0, 1.0, 1.0)), u.x);
+    return mix(mix(x00, x10, u.y), mix(x01, x11, u.y), u.z);
+}
+
+fn fbm(p: vec3<f32>) -> f32 {
+    var sum = 0.0;
+    var amplitude = 0.5;
+    var q = p;
+    for (var i = 0; i < 5; i++) {
+        sum += amplitude * value_noise(q);
+        q = q * 2.03 + vec3<f32>(1.7, 9.2, 4.1);
+        amplitude *= 0.5;
+    }
+    return sum / 0.96875;
+}
+
+fn procedural_surface(s: Surface, p: vec3<f32>, base: vec3<f32>) -> vec3<f32> {
+    let seed = s.params.y;
+    let q = p + vec3<f32>(seed * 13.7, seed * 7.3, seed * 3.1);
+    let dark = s.secondary.rgb;
+    switch (u32(s.params.x)) {
+        case STYLE_BANDED: {
+            // Zonal bands, warped by turbulence near their edges
+            let warp = (fbm(q * 4.0) - 0.5) * 0.25;
+            let bands = sin((p.y + warp) * PI * 7.0 + seed);
+            let fine = sin((p.y + warp * 0.5) * PI * 23.0) * 0.08;
+            return mix(dark, base, 0.5 + 0.5 * bands) * (1.0 + fine);
+        }
+        case STYLE_TERRAN: {
+            // Oceans in the body colour, continents and polar caps
+            let h = fbm(q * 2.5);
+            let land = smoothstep(0.52, 0.55, h);
+            let terrain = dark * (0.7 + 0.6 * fbm(q * 11.0));
+            let ice = smoothstep(0.78, 0.84, abs(p.y) + (h - 0.5) * 0.3);
+            return mix(mix(base, terrain, land), vec3<f32>(0.92, 0.94, 0.97), ice);
+        }
+        case STYLE_ICY: {
+            // Bright ice crossed by thin dark fractures
+            let crack = 1.0 - smoothstep(0.0, 0.025, abs(value_noise(q * 6.0) - 0.5));
+            let frost = 0.85 + 0.3 * fbm(q * 9.0);
+            return mix(base * frost, dark, crack * 0.8);
+        }
+        default: {
+            // Mottled rock, darker in the lowlands
+            let h = fbm(q * 3.0);
+            let detail = 0.8 + 0.4 * fbm(q * 12.0);
+            return mix(dark, base, smoothstep(0.35, 0.65, h)) * detail;
+        }
+    }
+}
+
+/// Surface colour at world-space unit normal `n`. `texel_angle` is the
+/// angle a screen pixel covers on the sphere, which picks the mip level.
+fn surface_color(index: u32, n: vec3<f32>, base: vec3<f32>, texel_angle: f32) -> vec3<f32> {
+    let s = surfaces[index];
+    let axis = normalize(s.axis.xyz);
+    let meridian = normalize(s.meridian.xyz);
+    let east = cross(axis, meridian);
+
+    // Undo the spin to get body-fixed coordinates, y along the axis
+    let x = dot(n, meridian);
+    let z = dot(n, east);
+    let c = cos(s.params.z);
+    let sn = sin(s.params.z);
+    let p = vec3<f32>(x * c + z * sn, dot(n, axis), z * c - x * sn);
+
+    if (s.meridian.w < 0.0) {
+        return procedural_surface(s, p, base);
+    }
+
+    // Equirectangular: longitude east from the meridian, north at the top.
+    // An explicit level avoids the derivative spike along the seam.
+    let uv = vec2<f32>(atan2(p.z, p.x) / TAU + 0.5, 0.5 - asin(clamp(p.y, -1.0, 1.0)) / PI);
+    let lod = log2(max(texel_angle * f32(textureDimensions(surface_textures).x) / TAU, 1.0));
+    return textureSampleLevel(surface_textures, surface_sampler, uv, i32(s.meridian.w), lod).rgb;
+}
+
 @fragment
-fn fs_body(in: BodyVsOut) -> @location(0) vec4<f32> {
+fn fs_body(in: BodyVsOut) -> BodyFsOut {
+    var out: BodyFsOut;
+    out.depth = GLOW_DEPTH;
     let dist = length(in.uv);
 
+    // Marker ring around bodies too small to see at true scale
+    if (in.pixels_per_radius > 0.0) {
+        let ring = abs(dist * in.pixels_per_radius - MARKER_PIXELS);
+        if (ring < 0.75) {
+            out.color = vec4<f32>(in.color.rgb, 0.8);
+            return out;
+        }
+        if (dist > 2.5) { discard; }
+    }
+
+    // Eye ray through this fragment, relative to the centre
+    let to_center = to_eye(in.world_center);
+    let dir = view_ray(to_center + in.offset);
+    let closest = in.offset - dir * dot(in.offset, dir);
+    let miss = length(closest) / in.radius;
+    var hit = vec3<f32>(0.0);
+    if (miss < 1.0) {
+        hit = closest - dir * sqrt(in.radius * in.radius - dot(closest, closest));
+        let view_pos = (view() * vec4<f32>(to_center + hit, 1.0)).xyz;
+        let clip = camera.proj * vec4<f32>(view_pos, 1.0);
+        out.depth = clip.z / clip.w;
+    }
+
     if (in.is_star > 0.5) {
         // Star rendering: bright core with corona
         let core = smoothstep(1.2, 0.0, dist);
@@ -162,90 +580,337 @@ fn fs_body(in: BodyVsOut) -> @location(0) vec4<f32> {
         let alpha = max(core, corona);
 
         if (alpha < 0.01) { discard; }
-        return vec4<f32>(color, min(alpha, 1.0));
-    } else {
-        // Planet rendering: sphere impostor with basic shading
-        if (dist > 1.3) { discard; }
-
-        // Sphere impostor: compute normal from UV
-        if (dist > 1.0) {
-            // Atmosphere glow
-            let atmo = smoothstep(1.3, 1.0, dist) * 0.3;
-            return vec4<f32>(in.color.rgb * 0.5, atmo);
-        }
+        out.color = vec4<f32>(color, min(alpha, 1.0));
+        return out;
+    }
 
-        // Ensure UV is clamped for sqrt to avoid NaNs
-        let clamped_uv = clamp(in.uv, vec2<f32>(-1.0), vec2<f32>(1.0));
-        let z = sqrt(max(0.0, 1.0 - clamped_uv.x * clamped_uv.x - clamped_uv.y * clamped_uv.y));
-        let normal = normalize(vec3<f32>(clamped_uv.x, clamped_uv.y, z));
+    // Planet rendering: lit sphere with a thin atmosphere glow around it
+    if (miss >= 1.0) {
+        if (miss > 1.3) { discard; }
+        let atmo = smoothstep(1.3, 1.0, miss) * 0.3;
+        out.color = vec4<f32>(in.color.rgb * 0.5, atmo);
+        return out;
+    }
 
-        // Simple directional lighting from star (assumed at origin)
-        let to_star = normalize(-in.world_center);
-        let ndotl = max(dot(normal, to_star), 0.0);
-        let ambient = 0.08;
-        let diffuse = ndotl * 0.9;
+    let normal = hit / in.radius;
+    let distance = length(to_center + hit);
+    let pixel = pixel_size(distance);
+    let albedo = surface_color(in.index, normal, in.color.rgb, pixel / in.radius);
 
-        // Specular
-        let view_dir = normalize(camera.eye_pos.xyz - in.world_center);
-        let half_dir = normalize(to_star + view_dir);
-        let spec = pow(max(dot(normal, half_dir), 0.0), 32.0) * 0.3;
+    // Every star, shadowed by moons and other planets
+    let light = lighting(in.world_center + hit, normal, -dir, in.index);
+    let ambient = 0.08;
+    out.color = vec4<f32>(albedo * (ambient + light.diffuse * 0.9 + light.specular * 0.3), 1.0);
+    return out;
+}
 
-        let lighting = ambient + diffuse + spec;
-        let color = in.color.rgb * lighting;
+// ============================================================================
+// Planetary rings: a quad in the ring plane around the body's current
+// position, cut to the inner/outer edge, lit on either face.
+// ============================================================================
 
-        return vec4<f32>(color, 1.0);
-    }
+struct RingVsOut {
+    @builtin(position) position: vec4<f32>,
+    @location(0) local: vec2<f32>,          // in body radii
+    @location(1) world_pos: vec3<f32>,
+    @location(2) @interpolate(flat) ring: u32,
+};
+
+@vertex
+fn vs_ring(
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
+) -> RingVsOut {
+    let ring = rings[instance_index];
+    let body = bodies[u32(ring.normal.w)];
+    let radius = select(body.velocity.w, body.data.w, camera.screen_size.w > 0.5);
+
+    var quad_positions = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>( 1.0, -1.0),
+        vec2<f32>(-1.0,  1.0),
+        vec2<f32>(-1.0,  1.0),
+        vec2<f32>( 1.0, -1.0),
+        vec2<f32>( 1.0,  1.0),
+    );
+    let local = quad_positions[vertex_index % 6u] * ring.radii.y;
+
+    let n = normalize(ring.normal.xyz);
+    let helper = select(vec3<f32>(1.0, 0.0, 0.0), vec3<f32>(0.0, 0.0, 1.0), abs(n.x) > 0.9);
+    let u = normalize(cross(n, helper));
+    let v = cross(n, u);
+    let offset = (u * local.x + v * local.y) * radius;
+    let world_pos = body.position.xyz + offset;
+
+    var out: RingVsOut;
+    out.position = view_proj() * vec4<f32>(to_eye(body.position.xyz) + offset, 1.0);
+    out.local = local;
+    out.world_pos = world_pos;
+    out.ring = instance_index;
+    return out;
+}
+
+fn ring_opacity(index: u32, t: f32) -> f32 {
+    let x = clamp(t, 0.0, 1.0) * 15.0;
+    let i = min(u32(x), 14u);
+    let a = rings[index].profile[i / 4u][i % 4u];
+    let b = rings[index].profile[(i + 1u) / 4u][(i + 1u) % 4u];
+    return mix(a, b, x - f32(i));
+}
+
+@fragment
+fn fs_ring(in: RingVsOut) -> @location(0) vec4<f32> {
+    let ring = rings[in.ring];
+    let r = length(in.local);
+    if (r < ring.radii.x || r > ring.radii.y) { discard; }
+    let opacity = ring_opacity(in.ring, (r - ring.radii.x) / (ring.radii.y - ring.radii.x)) * ring.color.a;
+    if (opacity < 0.005) { discard; }
+
+    // Thin particle layer, lit from whichever side faces each star and
+    // shadowed by the planet like any other sphere
+    let view_dir = -view_ray(to_eye(in.world_pos));
+    let light = lighting(in.world_pos, normalize(ring.normal.xyz), view_dir, NO_BODY);
+    let lit = 0.05 + light.incident * 0.25 + light.two_sided * 0.75;
+
+    return vec4<f32>(ring.color.rgb * lit, opacity);
 }
 
 // ============================================================================
-// Grid / orbit path rendering
+// Orbit trails: screen-space ribbons of constant pixel width, one instance
+// per body. Each segment is a quad whose ends are mitred against the
+// neighbouring segments; alpha fades towards the oldest point.
 // ============================================================================
 
-struct GridVsOut {
-    @builtin(position) position: vec4<f32>,
-    @location(0) world_pos: vec3<f32>,
-    @location(1) color: vec4<f32>,
+struct TrailVertex {
+    position: vec4<f32>,
+    color: vec4<f32>,
 };
 
-struct GridVertex {
-    @location(0) position: vec3<f32>,
-    @location(1) color: vec4<f32>,
+@group(1) @binding(5) var<storage, read> trails: array<TrailVertex>;
+
+// Longest miter, in half-widths, before sharp turns are cut off
+const MAX_MITER: f32 = 4.0;
+
+struct TrailVsOut {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) world_pos: vec3<f32>,
+    @location(2) edge: f32,     // pixels from the centre line
 };
 
+fn trail_clip(index: u32) -> vec4<f32> {
+    return camera.view_proj * vec4<f32>(frame_to_eye(trails[index].position.xyz), 1.0);
+}
+
+fn to_pixels(clip: vec4<f32>) -> vec2<f32> {
+    return clip.xy / clip.w * 0.5 * camera.screen_size.xy;
+}
+
+fn safe_normalize(v: vec2<f32>) -> vec2<f32> {
+    let len = length(v);
+    return select(vec2<f32>(1.0, 0.0), v / len, len > 1e-6);
+}
+
 @vertex
-fn vs_grid(input: GridVertex) -> GridVsOut {
-    var out: GridVsOut;
-    out.position = camera.view_proj * vec4<f32>(input.position, 1.0);
-    out.world_pos = input.position;
-    out.color = input.color;
+fn vs_trail(
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) trail: u32,
+) -> TrailVsOut {
+    var out: TrailVsOut;
+
+    // x = which end of the segment, y = which side of the ribbon
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(0.0,  1.0),
+        vec2<f32>(0.0,  1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0,  1.0),
+    );
+    let corner = corners[vertex_index % 6u];
+    let points = u32(camera.scene.z);
+    let base = trail * points;
+    let segment = vertex_index / 6u;
+    let local = segment + u32(corner.x);
+
+    // Segments crossing behind the camera are dropped whole
+    if (trail_clip(base + segment).w <= 1e-6 || trail_clip(base + segment + 1u).w <= 1e-6) {
+        out.position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+        return out;
+    }
+
+    let clip = trail_clip(base + local);
+    let prev = trail_clip(base + max(local, 1u) - 1u);
+    let next = trail_clip(base + min(local + 1u, points - 1u));
+    let p = to_pixels(clip);
+    var d_in = p - select(to_pixels(prev), p, prev.w <= 1e-6);
+    var d_out = select(to_pixels(next), p, next.w <= 1e-6) - p;
+    if (dot(d_in, d_in) < 1e-8) { d_in = d_out; }
+    if (dot(d_out, d_out) < 1e-8) { d_out = d_in; }
+    let dir_in = safe_normalize(d_in);
+    let dir_out = safe_normalize(d_out);
+
+    // Miter: offset along the bisector's normal, lengthened so the
+    // ribbon keeps its width across the joint
+    let own = select(dir_out, dir_in, corner.x > 0.5);
+    let normal = vec2<f32>(-own.y, own.x);
+    let tangent = safe_normalize(dir_in + dir_out);
+    let miter = vec2<f32>(-tangent.y, tangent.x);
+    let half_width = 0.5 * camera.scene.y + 1.0; // + 1 px feather
+    let scale = half_width / max(dot(miter, normal), 1.0 / MAX_MITER);
+    let offset = miter * scale * corner.y;
+
+    out.position = vec4<f32>(clip.xy + offset * 2.0 / camera.screen_size.xy * clip.w, clip.zw);
+    let fade = f32(local) / f32(max(points, 2u) - 1u);
+    let color = trails[base + local].color;
+    out.color = vec4<f32>(color.rgb, color.a * fade);
+    out.world_pos = trails[base + local].position.xyz;
+    out.edge = half_width * corner.y;
     return out;
 }
 
 @fragment
-fn fs_grid(in: GridVsOut) -> @location(0) vec4<f32> {
+fn fs_trail(in: TrailVsOut) -> @location(0) vec4<f32> {
+    // Anti-aliased edges, and thinner than a pixel fades instead
+    let width = camera.scene.y;
+    let coverage = clamp(0.5 * width + 0.5 - abs(in.edge), 0.0, 1.0) * min(width, 1.0);
+
     // Fade with distance
-    let dist = length(in.world_pos - camera.eye_pos.xyz);
+    let dist = length(frame_to_eye(in.world_pos));
     let fade = smoothstep(80.0, 5.0, dist);
-    return vec4<f32>(in.color.rgb, in.color.a * fade);
+    return vec4<f32>(in.color.rgb, in.color.a * fade * coverage);
 }
 
 // ============================================================================
-// Fullscreen post-process (bloom approximation)
+// Particle picking: each particle writes its index + 1 into an R32Uint
+// target, drawn at least PICK_MIN_PIXELS across so distant particles can
+// still be clicked. Group 0 only.
 // ============================================================================
 
-struct FullscreenVsOut {
+const PICK_MIN_PIXELS: f32 = 4.0;
+
+struct PickVsOut {
     @builtin(position) position: vec4<f32>,
     @location(0) uv: vec2<f32>,
+    @location(1) @interpolate(flat) id: u32,
 };
 
 @vertex
-fn vs_fullscreen(@builtin(vertex_index) vertex_index: u32) -> FullscreenVsOut {
-    var out: FullscreenVsOut;
-    let uv = vec2<f32>(
-        f32((vertex_index << 1u) & 2u),
-        f32(vertex_index & 2u)
+fn vs_pick(
+    @builtin(vertex_index) vertex_index: u32,
+    @builtin(instance_index) instance_index: u32,
+    instance: ParticleInstance,
+) -> PickVsOut {
+    var out: PickVsOut;
+    if (instance.data.w < 0.5) {
+        out.position = vec4<f32>(0.0, 0.0, -999.0, 1.0);
+        return out;
+    }
+
+    var quad_positions = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>( 1.0, -1.0),
+        vec2<f32>(-1.0,  1.0),
+        vec2<f32>(-1.0,  1.0),
+        vec2<f32>( 1.0, -1.0),
+        vec2<f32>( 1.0,  1.0),
     );
-    out.position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
-    out.uv = vec2<f32>(uv.x, 1.0 - uv.y);
+    let quad_pos = quad_positions[vertex_index % 6u];
+    let cam_right = from_frame_axes(vec3<f32>(camera.view[0][0], camera.view[1][0], camera.view[2][0]));
+    let cam_up = from_frame_axes(vec3<f32>(camera.view[0][1], camera.view[1][1], camera.view[2][1]));
+
+    let relative = to_eye(instance.position.xyz);
+    let depth = max(-(view() * vec4<f32>(relative, 1.0)).z, 1e-6);
+    let pixel = pixel_size(depth);
+    let size = max(instance.data.x * 2.0, pixel * PICK_MIN_PIXELS * 0.5);
+
+    out.position = view_proj() * vec4<f32>(relative + (cam_right * quad_pos.x + cam_up * quad_pos.y) * size, 1.0);
+    out.uv = quad_pos;
+    out.id = instance_index + 1u;
     return out;
-}
\ No newline at end of file
+}
+
+@fragment
+fn fs_pick(in: PickVsOut) -> @location(0) u32 {
+    if (length(in.uv) > 1.0) { discard; }
+    return in.id;
+}
+
+// ============================================================================
+// Selection outline: a screen-space ring around the selected body or
+// particle, drawn over everything
+// ============================================================================
+
+struct Particle {
+    position: vec4<f32>,
+    velocity: vec4<f32>,
+    color: vec4<f32>,
+    data: vec4<f32>,
+};
+
+@group(1) @binding(6) var<storage, read> particles: array<Particle>;
+
+const SELECTION_BODY: u32 = 1u;
+const SELECTION_PARTICLE: u32 = 2u;
+const OUTLINE_MIN_PIXELS: f32 = 5.0;   // smallest ring radius
+const OUTLINE_GAP_PIXELS: f32 = 3.0;   // space between object and ring
+const OUTLINE_PIXELS: f32 = 1.5;       // ring thickness
+const OUTLINE_COLOR: vec3<f32> = vec3<f32>(0.55, 0.85, 0.95);
+
+struct SelectionVsOut {
+    @builtin(position) position: vec4<f32>,
+    @location(0) pixels: vec2<f32>,  // offset from the centre in pixels
+    @location(1) ring: f32,          // ring radius in pixels
+};
+
+@vertex
+fn vs_selection(@builtin(vertex_index) vertex_index: u32) -> SelectionVsOut {
+    var out: SelectionVsOut;
+    out.position = vec4<f32>(0.0, 0.0, -999.0, 1.0);
+
+    let kind = u32(camera.selection.x);
+    let index = u32(camera.selection.y);
+    var center: vec3<f32>;
+    var radius: f32;
+    if (kind == SELECTION_BODY) {
+        let b = bodies[index];
+        center = b.position.xyz;
+        radius = body_radius(b);
+    } else if (kind == SELECTION_PARTICLE) {
+        let p = particles[index];
+        if (p.data.w < 0.5) { return out; }
+        center = p.position.xyz;
+        radius = p.data.x * 2.0;
+    } else {
+        return out;
+    }
+
+    let clip = view_proj() * vec4<f32>(to_eye(center), 1.0);
+    if (clip.w <= 0.0) { return out; }  // behind the camera
+
+    var quad_positions = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>( 1.0, -1.0),
+        vec2<f32>(-1.0,  1.0),
+        vec2<f32>(-1.0,  1.0),
+        vec2<f32>( 1.0, -1.0),
+        vec2<f32>( 1.0,  1.0),
+    );
+    let quad_pos = quad_positions[vertex_index % 6u];
+
+    let scale = camera.scene.w;
+    let pixel = pixel_size(clip.w);
+    out.ring = max(radius / pixel, OUTLINE_MIN_PIXELS * scale) + OUTLINE_GAP_PIXELS * scale;
+    out.pixels = quad_pos * (out.ring + OUTLINE_PIXELS * scale + 1.0);
+    out.position = vec4<f32>(clip.xy + out.pixels * 2.0 / camera.screen_size.xy * clip.w, clip.zw);
+    return out;
+}
+
+@fragment
+fn fs_selection(in: SelectionVsOut) -> @location(0) vec4<f32> {
+    let half_width = 0.5 * OUTLINE_PIXELS * camera.scene.w;
+    let edge = abs(length(in.pixels) - in.ring);
+    let alpha = 1.0 - smoothstep(half_width, half_width + 1.0, edge);
+    if (alpha <= 0.0) { discard; }
+    return vec4<f32>(OUTLINE_COLOR, alpha);
+}
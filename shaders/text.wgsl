@@ -0,0 +1,58 @@
+// ============================================================================
+// Screen-space text: one instanced quad per glyph, sampled from a
+// single-channel glyph atlas. Rectangles are given in pixels, top-left
+// origin. Solid boxes sample a white texel at the atlas corner.
+// ============================================================================
+
+struct Screen {
+    size: vec2<f32>,
+    _pad: vec2<f32>,
+};
+
+@group(0) @binding(0) var<uniform> screen: Screen;
+@group(0) @binding(1) var atlas: texture_2d<f32>;
+@group(0) @binding(2) var atlas_sampler: sampler;
+
+struct GlyphInstance {
+    @location(0) rect: vec4<f32>,   // xy = top-left, zw = size (pixels)
+    @location(1) uv: vec4<f32>,     // xy = top-left, zw = bottom-right
+    @location(2) color: vec4<f32>,
+};
+
+struct TextVsOut {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vs_text(
+    @builtin(vertex_index) vertex_index: u32,
+    instance: GlyphInstance,
+) -> TextVsOut {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+    );
+    let corner = corners[vertex_index % 6u];
+
+    let pixel = instance.rect.xy + corner * instance.rect.zw;
+    let ndc = pixel / screen.size * 2.0 - 1.0;
+
+    var out: TextVsOut;
+    out.position = vec4<f32>(ndc.x, -ndc.y, 0.0, 1.0);
+    out.uv = mix(instance.uv.xy, instance.uv.zw, corner);
+    out.color = instance.color;
+    return out;
+}
+
+@fragment
+fn fs_text(in: TextVsOut) -> @location(0) vec4<f32> {
+    let coverage = textureSample(atlas, atlas_sampler, in.uv).r;
+    if (coverage <= 0.0) { discard; }
+    return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
@@ -1,6 +1,35 @@
-use glam::{Mat4, Vec3};
+use glam::{DVec3, Mat4, Quat, Vec3};
+use serde::{Deserialize, Serialize};
 
+/// Mouse-look rate, radians per pixel
+const LOOK_SPEED: f32 = 0.003;
+/// Roll rate, radians per second
+const ROLL_SPEED: f32 = 1.5;
+/// Fly speed multiplier while Shift is held
+pub const FLY_BOOST: f32 = 5.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraMode {
+    /// Spherical orbit around `target`
+    Orbit,
+    /// Free flight with six degrees of freedom
+    Fly,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Projection {
+    #[default]
+    Perspective,
+    /// Parallel rays; the view is as tall as the field of view is at
+    /// `distance`, so zooming and switching keep the scale at the target
+    Orthographic,
+}
+
+#[derive(Clone)]
 pub struct Camera {
+    pub mode: CameraMode,
+    pub projection: Projection,
     /// Spherical coordinates
     pub distance: f32,
     pub theta: f32,    // horizontal angle (radians)
@@ -18,9 +47,17 @@ pub struct Camera {
     pub max_distance: f32,
     pub fov: f32,
     pub near: f32,
-    pub far: f32,
     pub aspect: f32,
 
+    /// Fly mode: eye position and orientation (looking down -z). The orbit
+    /// fields above are kept describing the same view, with the target
+    /// `distance` ahead, so switching back preserves it.
+    position: Vec3,
+    orientation: Quat,
+    velocity: Vec3,
+    /// Fly speed, in distances to the nearest body per second
+    pub fly_speed: f32,
+
     /// Interaction state
     pub is_panning: bool,
     pub is_orbiting: bool,
@@ -34,6 +71,8 @@ impl Camera {
         let phi = 0.5;
 
         Self {
+            mode: CameraMode::Orbit,
+            projection: Projection::Perspective,
             distance,
             theta,
             phi,
@@ -43,30 +82,150 @@ impl Camera {
             target_phi: phi,
             target_target: Vec3::ZERO,
             min_distance: 0.1,
-            max_distance: 100.0,
+            max_distance: 500.0,
             fov: 60.0_f32.to_radians(),
             near: 0.001,
-            far: 300.0,
             aspect: 16.0 / 9.0,
+            position: Vec3::ZERO,
+            orientation: Quat::IDENTITY,
+            velocity: Vec3::ZERO,
+            fly_speed: 1.0,
             is_panning: false,
             is_orbiting: false,
             last_mouse: None,
         }
     }
 
+    /// Jump straight to an orbit view, skipping the smoothing
+    pub fn set_orbit(&mut self, target: Vec3, distance: f32, theta: f32, phi: f32) {
+        self.mode = CameraMode::Orbit;
+        self.target = target;
+        self.target_target = target;
+        self.distance = distance.clamp(self.min_distance, self.max_distance);
+        self.target_distance = self.distance;
+        self.theta = theta;
+        self.target_theta = theta;
+        self.phi = phi;
+        self.target_phi = phi;
+    }
+
+    /// Head smoothly for an orbit view, turning the shorter way round
+    pub fn orbit_towards(&mut self, target: Vec3, distance: f32, theta: f32, phi: f32) {
+        self.set_mode(CameraMode::Orbit);
+        self.target_target = target;
+        self.target_distance = distance.clamp(self.min_distance, self.max_distance);
+        let turns = ((self.target_theta - theta) / std::f32::consts::TAU).round();
+        self.target_theta = theta + turns * std::f32::consts::TAU;
+        self.target_phi = phi;
+    }
+
+    /// Switch between orbiting and flying, keeping the current view. Roll
+    /// is levelled out and elevation limited on the way back to orbiting.
+    pub fn set_mode(&mut self, mode: CameraMode) {
+        if mode == self.mode {
+            return;
+        }
+        match mode {
+            CameraMode::Fly => {
+                self.position = self.eye_position();
+                self.orientation = Quat::from_mat4(&self.view_matrix().inverse()).normalize();
+                self.velocity = Vec3::ZERO;
+            }
+            CameraMode::Orbit => {
+                let limit = std::f32::consts::FRAC_PI_2 - 0.1;
+                self.set_orbit(self.target, self.distance, self.theta, self.phi.clamp(-limit, limit));
+            }
+        }
+        self.mode = mode;
+    }
+
+    /// Move the view, and where it is heading, by `delta`
+    pub fn translate(&mut self, delta: Vec3) {
+        self.target += delta;
+        self.target_target += delta;
+        self.position += delta;
+    }
+
+    /// Turn the view about the vertical axis through the target
+    pub fn rotate(&mut self, angle: f32) {
+        self.theta += angle;
+        self.target_theta += angle;
+        if self.mode == CameraMode::Fly {
+            let turn = Quat::from_rotation_y(angle);
+            self.position = self.target + turn * (self.position - self.target);
+            self.orientation = (turn * self.orientation).normalize();
+        }
+    }
+
+    /// Zoom smoothly to `distance`
+    pub fn zoom_to(&mut self, distance: f32) {
+        self.target_distance = distance.clamp(self.min_distance, self.max_distance);
+    }
+
     pub fn eye_position(&self) -> Vec3 {
-        let x = self.distance * self.phi.cos() * self.theta.sin();
-        let y = self.distance * self.phi.sin();
-        let z = self.distance * self.phi.cos() * self.theta.cos();
-        self.target + Vec3::new(x, y, z)
+        self.eye_position_f64().as_vec3()
+    }
+
+    /// The eye without rounding it to f32, for rendering relative to it:
+    /// close to a body far out, the rounding alone is a visible jump
+    pub fn eye_position_f64(&self) -> DVec3 {
+        if self.mode == CameraMode::Fly {
+            return self.position.as_dvec3();
+        }
+        self.target.as_dvec3() + self.eye_offset()
+    }
+
+    /// Orbit mode: the eye relative to the target
+    fn eye_offset(&self) -> DVec3 {
+        let (distance, theta, phi) = (self.distance as f64, self.theta as f64, self.phi as f64);
+        let x = distance * phi.cos() * theta.sin();
+        let y = distance * phi.sin();
+        let z = distance * phi.cos() * theta.cos();
+        DVec3::new(x, y, z)
     }
 
     pub fn view_matrix(&self) -> Mat4 {
-        Mat4::look_at_rh(self.eye_position(), self.target, Vec3::Y)
+        match self.mode {
+            // Turned by the offset alone, which the eye's rounding far
+            // from the origin would otherwise shake
+            CameraMode::Orbit => {
+                Mat4::look_at_rh(self.eye_offset().as_vec3(), Vec3::ZERO, Vec3::Y) * Mat4::from_translation(-self.target)
+            }
+            CameraMode::Fly => Mat4::from_rotation_translation(self.orientation, self.position).inverse(),
+        }
     }
 
+    /// Reversed-Z with an infinite far plane: depth is 1 at the near plane and
+    /// falls towards 0 at infinity, which keeps float precision spread evenly
+    /// from moons up close to the outer system. Depth tests use `Greater`.
+    /// Orthographic views are reversed too, and take in `max_distance` on
+    /// either side of the target, behind the eye included.
     pub fn proj_matrix(&self) -> Mat4 {
-        Mat4::perspective_rh(self.fov, self.aspect, self.near, self.far)
+        match self.projection {
+            Projection::Perspective => Mat4::perspective_infinite_reverse_rh(self.fov, self.aspect, self.near),
+            Projection::Orthographic => {
+                let half_height = self.distance * (self.fov * 0.5).tan();
+                let half_width = half_height * self.aspect;
+                Mat4::orthographic_rh(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    self.distance + self.max_distance,
+                    self.distance - self.max_distance,
+                )
+            }
+        }
+    }
+
+    /// World size of one pixel at view depth `depth`, on a target `height`
+    /// pixels tall
+    pub fn pixel_size(&self, depth: f32, height: f32) -> f32 {
+        let scale = match self.projection {
+            Projection::Perspective => depth,
+            Projection::Orthographic => self.distance,
+        };
+        2.0 * scale * (self.fov * 0.5).tan() / height
     }
 
     pub fn view_proj_matrix(&self) -> Mat4 {
@@ -74,6 +233,10 @@ impl Camera {
     }
 
     pub fn handle_scroll(&mut self, delta: f32) {
+        if self.mode == CameraMode::Fly {
+            self.fly_speed = (self.fly_speed * 1.2_f32.powf(delta)).clamp(0.01, 100.0);
+            return;
+        }
         self.target_distance = (self.target_distance - delta * self.target_distance * 0.1)
             .clamp(self.min_distance, self.max_distance);
     }
@@ -83,7 +246,16 @@ impl Camera {
             let dx = x - lx;
             let dy = y - ly;
 
-            if self.is_orbiting {
+            if self.mode == CameraMode::Fly {
+                // Mouse-look about the camera's own axes, so there is no gimbal lock
+                if self.is_orbiting {
+                    self.orientation = (self.orientation
+                        * Quat::from_rotation_y(-dx * LOOK_SPEED)
+                        * Quat::from_rotation_x(-dy * LOOK_SPEED))
+                    .normalize();
+                    self.sync_orbit();
+                }
+            } else if self.is_orbiting {
                 self.target_theta -= dx * 0.005;
                 self.target_phi = (self.target_phi + dy * 0.005).clamp(
                     -std::f32::consts::FRAC_PI_2 + 0.1,
@@ -91,7 +263,7 @@ impl Camera {
                 );
             }
 
-            if self.is_panning {
+            if self.is_panning && self.mode == CameraMode::Orbit {
                 let view = self.view_matrix();
                 let right = Vec3::new(view.col(0).x, view.col(1).x, view.col(2).x);
                 let up = Vec3::new(view.col(0).y, view.col(1).y, view.col(2).y);
@@ -116,6 +288,10 @@ impl Camera {
 
     /// Smooth interpolation update
     pub fn update(&mut self, dt: f32) {
+        if self.mode == CameraMode::Fly {
+            // Flying moves the view directly, see `fly`
+            return;
+        }
         let lerp_speed = 8.0 * dt;
         self.distance += (self.target_distance - self.distance) * lerp_speed;
         self.theta += (self.target_theta - self.theta) * lerp_speed;
@@ -123,17 +299,53 @@ impl Camera {
         self.target += (self.target_target - self.target) * lerp_speed;
     }
 
+    /// Fly mode: move along the camera's axes (x right, y up, z back, each
+    /// -1 to 1) and roll (positive anticlockwise). `reference` is the
+    /// distance to the nearest body, which sets the pace so approaches
+    /// slow down by themselves.
+    pub fn fly(&mut self, movement: Vec3, roll: f32, reference: f32, dt: f32) {
+        if self.mode != CameraMode::Fly {
+            return;
+        }
+        let wanted = self.orientation * movement.clamp_length_max(1.0) * reference * self.fly_speed;
+        self.velocity += (wanted - self.velocity) * (8.0 * dt).min(1.0);
+        self.position += self.velocity * dt;
+        self.orientation = (self.orientation * Quat::from_rotation_z(roll * ROLL_SPEED * dt)).normalize();
+        self.sync_orbit();
+    }
+
+    /// Point the orbit fields at the fly view, with the target `distance`
+    /// straight ahead
+    fn sync_orbit(&mut self) {
+        let back = self.orientation * Vec3::Z;
+        self.target = self.position - back * self.distance;
+        self.target_target = self.target;
+        self.target_distance = self.distance;
+        self.theta = back.x.atan2(back.z);
+        self.target_theta = self.theta;
+        self.phi = back.y.clamp(-1.0, 1.0).asin();
+        self.target_phi = self.phi;
+    }
+
     pub fn resize(&mut self, width: u32, height: u32) {
         if height > 0 {
             self.aspect = width as f32 / height as f32;
         }
     }
 
-    /// Get a world-space ray from screen coordinates for picking
+    /// Get a world-space ray from screen coordinates for picking. An
+    /// orthographic ray starts on the near plane, behind the eye.
     pub fn screen_to_world_ray(&self, screen_x: f32, screen_y: f32, width: f32, height: f32) -> (Vec3, Vec3) {
         let ndc_x = (2.0 * screen_x / width) - 1.0;
         let ndc_y = 1.0 - (2.0 * screen_y / height);
 
+        if self.projection == Projection::Orthographic {
+            let inv_view_proj = self.view_proj_matrix().inverse();
+            let near = inv_view_proj.project_point3(Vec3::new(ndc_x, ndc_y, 1.0));
+            let far = inv_view_proj.project_point3(Vec3::new(ndc_x, ndc_y, 0.0));
+            return (near, (far - near).normalize());
+        }
+
         let inv_proj = self.proj_matrix().inverse();
         let inv_view = self.view_matrix().inverse();
 
@@ -159,6 +371,7 @@ impl Camera {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MouseButton {
     Left,
     Right,
@@ -0,0 +1,343 @@
+use std::path::Path;
+
+use glam::Vec3;
+use serde::{Deserialize, Serialize};
+
+use crate::camera::Camera;
+
+// ============================================================================
+// Camera paths: camera keyframes at simulated times, for exported
+// animations and repeatable fly-throughs. A key gives the target and either
+// the eye position or an orbit about the target. Between keys the view
+// follows a spline through the target, distance, azimuth, elevation and
+// field of view, so turns about the target stay turns; the distance moves
+// geometrically so zooms keep a steady pace.
+//
+//     spline = "catmull-rom"  # or "linear" (default), "bezier"
+//
+//     [[key]]
+//     time = 0.0          # years
+//     target = [0.0, 0.0, 0.0]
+//     distance = 5.0      # AU
+//     azimuth = 45.0      # degrees; 45 -> 405 is one full turn
+//     elevation = 30.0
+//     fov = 60.0
+//     ease = "in-out"     # towards the next key: "linear" (default), "in", "out"
+//
+//     [[key]]
+//     time = 2.0
+//     eye = [1.0, 0.5, 2.0]   # instead of distance, azimuth and elevation
+//     target = [1.0, 0.0, 1.0]
+//
+// Paths live in their own file (--camera-path) or as [camera_path] in a
+// scenario, with the keys as [[camera_path.key]].
+// ============================================================================
+
+/// How the view moves between keys
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Spline {
+    /// Straight from key to key
+    #[default]
+    Linear,
+    /// Smooth, through every key
+    CatmullRom,
+    /// Smoother still, through the first and last keys and cutting the
+    /// corners at the others, which act as Bezier control points
+    Bezier,
+}
+
+impl Spline {
+    fn is_linear(&self) -> bool {
+        *self == Spline::Linear
+    }
+}
+
+/// Pacing of the move from a key to the next
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Easing {
+    #[default]
+    Linear,
+    /// Start slowly
+    In,
+    /// Arrive slowly
+    Out,
+    /// Start and arrive slowly
+    InOut,
+}
+
+impl Easing {
+    fn is_linear(&self) -> bool {
+        *self == Easing::Linear
+    }
+
+    fn apply(self, t: f32) -> f32 {
+        match self {
+            Easing::Linear => t,
+            Easing::In => t * t,
+            Easing::Out => t * (2.0 - t),
+            Easing::InOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraKey {
+    pub time: f32,
+    #[serde(default)]
+    pub target: [f32; 3],
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eye: Option<[f32; 3]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distance: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub azimuth: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub elevation: Option<f32>,
+    #[serde(default = "default_fov")]
+    pub fov: f32,
+    #[serde(default, skip_serializing_if = "Easing::is_linear")]
+    pub ease: Easing,
+}
+
+fn default_fov() -> f32 { 60.0 }
+
+impl CameraKey {
+    /// The view `camera` has now, at sim time `time`
+    pub fn from_camera(camera: &Camera, time: f32) -> Self {
+        Self {
+            time,
+            target: camera.target.to_array(),
+            eye: Some(camera.eye_position().to_array()),
+            distance: None,
+            azimuth: None,
+            elevation: None,
+            fov: camera.fov.to_degrees(),
+            ease: Easing::Linear,
+        }
+    }
+}
+
+/// A view as an orbit about its target, the form splines run through.
+/// Angles in degrees.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Pose {
+    pub target: Vec3,
+    pub distance: f32,
+    pub azimuth: f32,
+    pub elevation: f32,
+    pub fov: f32,
+}
+
+impl Pose {
+    /// Interpolated channels: target, log distance, azimuth, elevation, fov
+    fn channels(&self) -> [f32; 7] {
+        let t = self.target;
+        [t.x, t.y, t.z, self.distance.ln(), self.azimuth, self.elevation, self.fov]
+    }
+
+    fn from_channels(c: [f32; 7]) -> Self {
+        Self {
+            target: Vec3::new(c[0], c[1], c[2]),
+            distance: c[3].exp(),
+            azimuth: c[4],
+            elevation: c[5],
+            fov: c[6],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraPath {
+    #[serde(default, skip_serializing_if = "Spline::is_linear")]
+    pub spline: Spline,
+    #[serde(rename = "key")]
+    pub keys: Vec<CameraKey>,
+}
+
+impl CameraPath {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let path: Self = toml::from_str(text).map_err(|e| e.to_string())?;
+        path.validate()?;
+        Ok(path)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Check every key, reporting all problems at once
+    pub fn validate(&self) -> Result<(), String> {
+        let mut errors = Vec::new();
+        if self.keys.is_empty() {
+            errors.push("at least one key is required".to_string());
+        }
+        for (i, key) in self.keys.iter().enumerate() {
+            if !key.time.is_finite() {
+                errors.push(format!("key {}: time must be finite", i));
+            }
+            if i > 0 && key.time <= self.keys[i - 1].time {
+                errors.push(format!("key {}: times must increase, {} follows {}", i, key.time, self.keys[i - 1].time));
+            }
+            let orbit = [key.distance, key.azimuth, key.elevation];
+            match key.eye {
+                Some(_) if orbit.iter().any(Option::is_some) => {
+                    errors.push(format!("key {}: give either eye or distance, azimuth and elevation", i));
+                }
+                Some(eye) if !(Vec3::from(eye) - Vec3::from(key.target)).length().is_normal() => {
+                    errors.push(format!("key {}: eye must differ from target", i));
+                }
+                None if orbit.iter().any(Option::is_none) => {
+                    errors.push(format!("key {}: needs eye, or distance, azimuth and elevation", i));
+                }
+                _ => {}
+            }
+            if let Some(distance) = key.distance.filter(|d| !(d.is_finite() && *d > 0.0)) {
+                errors.push(format!("key {}: distance must be positive, got {}", i, distance));
+            }
+            if let Some(elevation) = key.elevation.filter(|e| !(-90.0..=90.0).contains(e)) {
+                errors.push(format!("key {}: elevation must be between -90 and 90, got {}", i, elevation));
+            }
+            if !(key.fov > 0.0 && key.fov < 180.0) {
+                errors.push(format!("key {}: fov must be between 0 and 180, got {}", i, key.fov));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("\n"))
+        }
+    }
+
+    /// Add `key`, in time order, replacing any key at the same time
+    pub fn insert(&mut self, key: CameraKey) {
+        let at = self.keys.partition_point(|k| k.time < key.time);
+        if self.keys.get(at).is_some_and(|k| k.time == key.time) {
+            self.keys[at] = key;
+        } else {
+            self.keys.insert(at, key);
+        }
+    }
+
+    /// Move every key by `delta`, e.g. after the simulation origin moved
+    pub fn translate(&mut self, delta: Vec3) {
+        for key in &mut self.keys {
+            key.target = (Vec3::from(key.target) + delta).into();
+            key.eye = key.eye.map(|eye| (Vec3::from(eye) + delta).into());
+        }
+    }
+
+    /// Every key as an orbit. Azimuths worked out from an eye position take
+    /// the shorter way round from the key before.
+    fn poses(&self) -> Vec<Pose> {
+        let mut poses: Vec<Pose> = Vec::with_capacity(self.keys.len());
+        for key in &self.keys {
+            let target = Vec3::from(key.target);
+            let pose = match key.eye {
+                Some(eye) => {
+                    let offset = Vec3::from(eye) - target;
+                    let distance = offset.length();
+                    let mut azimuth = offset.x.atan2(offset.z).to_degrees();
+                    if let Some(last) = poses.last() {
+                        azimuth += ((last.azimuth - azimuth) / 360.0).round() * 360.0;
+                    }
+                    Pose {
+                        target,
+                        distance,
+                        azimuth,
+                        elevation: (offset.y / distance).clamp(-1.0, 1.0).asin().to_degrees(),
+                        fov: key.fov,
+                    }
+                }
+                None => Pose {
+                    target,
+                    distance: key.distance.unwrap_or(1.0),
+                    azimuth: key.azimuth.unwrap_or(0.0),
+                    elevation: key.elevation.unwrap_or(0.0),
+                    fov: key.fov,
+                },
+            };
+            poses.push(pose);
+        }
+        poses
+    }
+
+    /// The view at sim time `time`, holding the first and last keys
+    /// before and after the path
+    pub fn sample(&self, time: f32) -> Pose {
+        let poses: Vec<[f32; 7]> = self.poses().iter().map(Pose::channels).collect();
+        let times: Vec<f32> = self.keys.iter().map(|k| k.time).collect();
+        let n = poses.len();
+        if n == 1 || time <= times[0] {
+            return Pose::from_channels(poses[0]);
+        }
+        if time >= times[n - 1] {
+            return Pose::from_channels(poses[n - 1]);
+        }
+
+        let channels = match self.spline {
+            Spline::Linear | Spline::CatmullRom => {
+                let i = times.partition_point(|&t| t <= time) - 1;
+                let span = times[i + 1] - times[i];
+                let s = self.keys[i].ease.apply((time - times[i]) / span);
+                if self.spline == Spline::Linear {
+                    mix(poses[i], poses[i + 1], s)
+                } else {
+                    // Hermite segment with tangents from the neighbouring
+                    // keys, scaled by time so uneven spacing stays smooth
+                    let tangent = |k: usize| {
+                        let (a, b) = (k.saturating_sub(1), (k + 1).min(n - 1));
+                        map2(poses[a], poses[b], |x, y| (y - x) / (times[b] - times[a]) * span)
+                    };
+                    let (m0, m1) = (tangent(i), tangent(i + 1));
+                    let (s2, s3) = (s * s, s * s * s);
+                    let h = [2.0 * s3 - 3.0 * s2 + 1.0, s3 - 2.0 * s2 + s, -2.0 * s3 + 3.0 * s2, s3 - s2];
+                    std::array::from_fn(|c| h[0] * poses[i][c] + h[1] * m0[c] + h[2] * poses[i + 1][c] + h[3] * m1[c])
+                }
+            }
+            Spline::Bezier => {
+                // One quadratic segment per key, from the midpoint with the
+                // key before to the midpoint with the key after, with the key
+                // as control point; the ends start and finish on their keys
+                let bound = |k: usize| match k {
+                    0 => times[0],
+                    k if k == n => times[n - 1],
+                    k => 0.5 * (times[k - 1] + times[k]),
+                };
+                let k = (1..n).filter(|&k| bound(k) <= time).count();
+                let s = self.keys[k].ease.apply((time - bound(k)) / (bound(k + 1) - bound(k)));
+                let start = if k == 0 { poses[0] } else { mix(poses[k - 1], poses[k], 0.5) };
+                let end = if k == n - 1 { poses[n - 1] } else { mix(poses[k], poses[k + 1], 0.5) };
+                mix(mix(start, poses[k], s), mix(poses[k], end, s), s)
+            }
+        };
+        Pose::from_channels(channels)
+    }
+
+    /// Place `camera` exactly where the path is at `time`
+    pub fn apply(&self, camera: &mut Camera, time: f32) {
+        let pose = self.sample(time);
+        camera.fov = pose.fov.to_radians();
+        camera.set_orbit(pose.target, pose.distance, pose.azimuth.to_radians(), pose.elevation.to_radians());
+    }
+
+    /// Send `camera` towards where the path is at `time`, letting its
+    /// smoothing take out any jumps in sim time
+    pub fn steer(&self, camera: &mut Camera, time: f32) {
+        let pose = self.sample(time);
+        camera.fov = pose.fov.to_radians();
+        camera.orbit_towards(pose.target, pose.distance, pose.azimuth.to_radians(), pose.elevation.to_radians());
+    }
+}
+
+fn map2(a: [f32; 7], b: [f32; 7], f: impl Fn(f32, f32) -> f32) -> [f32; 7] {
+    std::array::from_fn(|c| f(a[c], b[c]))
+}
+
+fn mix(a: [f32; 7], b: [f32; 7], t: f32) -> [f32; 7] {
+    map2(a, b, |x, y| x + (y - x) * t)
+}
@@ -0,0 +1,325 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use glam::{Mat4, Vec3};
+
+use crate::camera::Camera;
+use crate::gpu::GpuState;
+use crate::post::{PostProcess, RenderSettings, Tiling};
+use crate::simulation::Simulation;
+use crate::types::CameraUniform;
+use crate::viewports::Viewport;
+
+// ============================================================================
+// Screenshots: the scene is rendered again offscreen at any resolution, in
+// tiles when it is larger than one render target, and saved as PNG with the
+// simulation state in text chunks. Labels, HUD and panel are left out.
+// ============================================================================
+
+/// Largest tile rendered in one pass
+const TILE_SIZE: u32 = 2048;
+/// Longest side of a capture
+pub const MAX_CAPTURE_SIZE: u32 = 32768;
+/// Most pixels in a capture, 1 GiB of RGBA8 held in memory at once
+pub const MAX_CAPTURE_PIXELS: u64 = 16384 * 16384;
+/// Tiles are tone mapped into this and read back as RGBA8
+const OUTPUT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// Tightly packed RGBA8 pixels, top row first
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>,
+}
+
+/// Clip-space transform that stretches the pixel rectangle starting at
+/// (x, y) of size `tile` within a `full` image over the whole viewport
+pub fn tile_matrix(full: (u32, u32), (x, y): (i64, i64), tile: (u32, u32)) -> Mat4 {
+    let (w, h) = (full.0 as f64, full.1 as f64);
+    let (left, right) = (2.0 * x as f64 / w - 1.0, 2.0 * (x + tile.0 as i64) as f64 / w - 1.0);
+    let (top, bottom) = (1.0 - 2.0 * y as f64 / h, 1.0 - 2.0 * (y + tile.1 as i64) as f64 / h);
+    let scale = Vec3::new((2.0 / (right - left)) as f32, (2.0 / (top - bottom)) as f32, 1.0);
+    let offset = Vec3::new(
+        (-(right + left) / (right - left)) as f32,
+        (-(top + bottom) / (top - bottom)) as f32,
+        0.0,
+    );
+    Mat4::from_translation(offset) * Mat4::from_scale(scale)
+}
+
+/// Offscreen targets for rendering images of one size, reusable from
+/// frame to frame
+pub struct Capture {
+    width: u32,
+    height: u32,
+    /// Pixels of each tile
+    tile: (u32, u32),
+    /// Image pixels averaged into each pixel of the bloom source, each way
+    reduction: u32,
+    /// Scene and tone mapping of one tile
+    post: PostProcess,
+    /// Bloom of the whole image, at about the window's resolution
+    bloom: PostProcess,
+    /// A tile's scene with the whole image's glow
+    composite: wgpu::BindGroup,
+    depth: wgpu::TextureView,
+    output: wgpu::Texture,
+    output_view: wgpu::TextureView,
+    row_bytes: u32,
+    staging: wgpu::Buffer,
+}
+
+impl Capture {
+    pub fn new(gpu: &GpuState, (width, height): (u32, u32)) -> Result<Self, String> {
+        if width == 0 || height == 0 || width.max(height) > MAX_CAPTURE_SIZE {
+            return Err(format!(
+                "Capture size {}x{} must be between 1 and {} pixels a side",
+                width, height, MAX_CAPTURE_SIZE
+            ));
+        }
+        if width as u64 * height as u64 > MAX_CAPTURE_PIXELS {
+            return Err(format!(
+                "Capture size {}x{} is over {} pixels, as many as 16384x16384",
+                width, height, MAX_CAPTURE_PIXELS
+            ));
+        }
+        let device = &gpu.device;
+        let tile_limit = TILE_SIZE.min(device.limits().max_texture_dimension_2d);
+        let tile = (width.min(tile_limit), height.min(tile_limit));
+
+        // Bloom runs once over the whole image, reduced by a power of two
+        // to about the window's size, so it spreads as far as it does there
+        // and runs across the seams. Tiles are powers of two, so each
+        // covers whole pixels of the reduced image.
+        let pixel_scale = height as f32 / gpu.config.height.max(1) as f32;
+        let reduction = (1u32 << pixel_scale.max(1.0).log2().floor() as u32).min(width.min(height));
+        let reduced = (width.div_ceil(reduction), height.div_ceil(reduction));
+
+        let settings = gpu.post.settings;
+        let post = PostProcess::new(device, OUTPUT_FORMAT, tile.0, tile.1, settings, gpu.post.sample_count);
+        let bloom = PostProcess::new(device, OUTPUT_FORMAT, reduced.0, reduced.1, settings, 1);
+        let composite = post.composite_with(device, &bloom);
+        let depth = GpuState::create_depth_texture(device, tile.0, tile.1, gpu.post.sample_count);
+        let output = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Capture Tile"),
+            size: wgpu::Extent3d { width: tile.0, height: tile.1, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: OUTPUT_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let output_view = output.create_view(&wgpu::TextureViewDescriptor::default());
+        let row_bytes = (tile.0 * 4).next_multiple_of(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT);
+        let staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Capture Readback"),
+            size: (row_bytes * tile.1) as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        Ok(Self {
+            width,
+            height,
+            tile,
+            reduction,
+            post,
+            bloom,
+            composite,
+            depth,
+            output,
+            output_view,
+            row_bytes,
+            staging,
+        })
+    }
+
+    /// Render the scene as `camera` sees it, reading bodies and particles
+    /// from ping-pong buffer `buffer`. Pixel-sized features (trails, stars,
+    /// markers) and bloom scale with the height relative to the window, so
+    /// the image looks like an enlarged copy of it. Larger images render
+    /// each tile twice: once for the bloom, once to tone map it.
+    pub fn render(&mut self, gpu: &GpuState, camera: &Camera, sim: &Simulation, dpi: f32, buffer: usize) -> Image {
+        let (width, height, tile, reduction) = (self.width, self.height, self.tile, self.reduction);
+        let device = &gpu.device;
+
+        let mut camera = camera.clone();
+        camera.resize(width, height);
+        let pixel_scale = height as f32 / gpu.config.height.max(1) as f32;
+        let base = gpu.camera_uniform(&camera, sim, tile, dpi, pixel_scale);
+        let settings = gpu.post.settings;
+        self.post.settings = settings;
+        self.bloom.settings =
+            RenderSettings { bloom_radius: settings.bloom_radius * pixel_scale / reduction as f32, ..settings };
+        self.bloom.update(&gpu.queue);
+
+        let tiles: Vec<(u32, u32)> = (0..height)
+            .step_by(tile.1 as usize)
+            .flat_map(|y| (0..width).step_by(tile.0 as usize).map(move |x| (x, y)))
+            .collect();
+        // The bloom texture spans whole reduced pixels, a little past the image
+        let span = (width.div_ceil(reduction) * reduction, height.div_ceil(reduction) * reduction);
+
+        // Every tile's scene, averaged into the bloom source
+        for &(x, y) in &tiles {
+            let mut encoder = self.draw_tile(gpu, &base, sim, buffer, (x, y), span);
+            let scissor = [
+                x / reduction,
+                y / reduction,
+                tile.0.min(width - x).div_ceil(reduction),
+                tile.1.min(height - y).div_ceil(reduction),
+            ];
+            self.post.reduce_into(&mut encoder, &self.bloom, scissor);
+            gpu.queue.submit(std::iter::once(encoder.finish()));
+        }
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Capture Bloom") });
+        self.bloom.bloom(&mut encoder);
+        gpu.queue.submit(std::iter::once(encoder.finish()));
+
+        // Each tile tone mapped with its part of the glow; a lone tile is
+        // still in place from the first pass
+        let mut image = Image { width, height, pixels: vec![0; width as usize * height as usize * 4] };
+        for &(x, y) in &tiles {
+            let mut encoder = match tiles.len() {
+                1 => device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Capture") }),
+                _ => self.draw_tile(gpu, &base, sim, buffer, (x, y), span),
+            };
+            self.post.composite(&mut encoder, &self.output_view, &self.composite);
+            encoder.copy_texture_to_buffer(
+                self.output.as_image_copy(),
+                wgpu::ImageCopyBuffer {
+                    buffer: &self.staging,
+                    layout: wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: Some(self.row_bytes),
+                        rows_per_image: Some(tile.1),
+                    },
+                },
+                self.output.size(),
+            );
+            gpu.queue.submit(std::iter::once(encoder.finish()));
+
+            let slice = self.staging.slice(..);
+            slice.map_async(wgpu::MapMode::Read, |_| {});
+            device.poll(wgpu::Maintain::Wait);
+            {
+                // Copy the tile, clipped to the image
+                let data = slice.get_mapped_range();
+                let cols = tile.0.min(width - x) as usize;
+                for row in 0..tile.1.min(height - y) {
+                    let src = (row * self.row_bytes) as usize;
+                    let dst = ((y + row) as usize * width as usize + x as usize) * 4;
+                    image.pixels[dst..dst + cols * 4].copy_from_slice(&data[src..src + cols * 4]);
+                }
+            }
+            self.staging.unmap();
+        }
+        image
+    }
+
+    /// Draw the scene for the tile at (`x`, `y`) into the tile's HDR target,
+    /// with the bloom texture spanning `span` image pixels
+    fn draw_tile(
+        &mut self,
+        gpu: &GpuState,
+        base: &CameraUniform,
+        sim: &Simulation,
+        buffer: usize,
+        (x, y): (u32, u32),
+        span: (u32, u32),
+    ) -> wgpu::CommandEncoder {
+        let tile = self.tile;
+        let matrix = tile_matrix((self.width, self.height), (x as i64, y as i64), tile);
+        let mut uniform = *base;
+        uniform.view_proj = (matrix * Mat4::from_cols_array_2d(&base.view_proj)).to_cols_array_2d();
+        uniform.proj = (matrix * Mat4::from_cols_array_2d(&base.proj)).to_cols_array_2d();
+        gpu.queue.write_buffer(&gpu.camera_buffer, 0, bytemuck::bytes_of(&uniform));
+
+        let (span_w, span_h) = (span.0 as f32, span.1 as f32);
+        self.post.tiling = Tiling {
+            reduction: self.reduction,
+            bloom_rect: [x as f32 / span_w, y as f32 / span_h, tile.0 as f32 / span_w, tile.1 as f32 / span_h],
+            origin: [x as f32, y as f32],
+        };
+        self.post.update(&gpu.queue);
+
+        let mut encoder = gpu.device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Capture") });
+        gpu.draw_scene(
+            &mut encoder,
+            self.post.scene_target(),
+            &self.depth,
+            &[Viewport::full(tile.0, tile.1)],
+            buffer,
+            sim.bodies.len() as u32,
+            sim.params.trail_length,
+        );
+        encoder
+    }
+}
+
+/// Render a single `width` x `height` image, see `Capture::render`
+pub fn capture(
+    gpu: &GpuState,
+    camera: &Camera,
+    sim: &Simulation,
+    size: (u32, u32),
+    dpi: f32,
+    buffer: usize,
+) -> Result<Image, String> {
+    Ok(Capture::new(gpu, size)?.render(gpu, camera, sim, dpi, buffer))
+}
+
+/// Text chunks describing the simulation state an image was taken at
+pub fn metadata(sim: &Simulation, scenario: &str) -> Vec<(&'static str, String)> {
+    vec![
+        ("Software", format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))),
+        ("Scenario", scenario.to_string()),
+        ("Seed", sim.seed.to_string()),
+        ("Time", format!("{} yr", sim.time)),
+        ("Step", sim.step.to_string()),
+        ("Integrator", format!("{:?}", sim.integrator())),
+        ("Precision", format!("{:?}", sim.precision())),
+        ("Frame", sim.frame().describe(sim)),
+    ]
+}
+
+/// Write an image as PNG with the given text chunks
+pub fn save_png(path: &Path, image: &Image, text: &[(&str, String)]) -> Result<(), String> {
+    let error = |e: &dyn std::fmt::Display| format!("{}: {}", path.display(), e);
+    let file = File::create(path).map_err(|e| error(&e))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), image.width, image.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    for (keyword, value) in text {
+        // tEXt is Latin-1, so anything else goes into an international chunk
+        let result = if value.is_ascii() {
+            encoder.add_text_chunk(keyword.to_string(), value.clone())
+        } else {
+            encoder.add_itxt_chunk(keyword.to_string(), value.clone())
+        };
+        result.map_err(|e| error(&e))?;
+    }
+    let mut writer = encoder.write_header().map_err(|e| error(&e))?;
+    writer.write_image_data(&image.pixels).map_err(|e| error(&e))?;
+    writer.finish().map_err(|e| error(&e))
+}
+
+/// A file name in `dir` that does not exist yet, built from the scenario
+/// name, step and size
+pub fn screenshot_path(dir: &Path, scenario: &str, sim: &Simulation, (width, height): (u32, u32)) -> PathBuf {
+    let slug: String = scenario
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    let stem = format!("{}-{:08}-{}x{}", slug.trim_matches('_'), sim.step, width, height);
+    let mut path = dir.join(format!("{}.png", stem));
+    for n in 2.. {
+        if !path.exists() {
+            break;
+        }
+        path = dir.join(format!("{}-{}.png", stem, n));
+    }
+    path
+}
@@ -0,0 +1,242 @@
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+
+use crate::capture::{MAX_CAPTURE_PIXELS, MAX_CAPTURE_SIZE};
+use crate::post::{RenderSettings, ToneMap};
+use crate::simulation::{Backend, Integrator, Precision};
+use crate::types::*;
+
+/// GPU-accelerated star system simulator with swarm behaviour
+#[derive(Debug, Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Built-in preset name or path to a scenario TOML file
+    /// [presets: solar_system, trappist1, alpha_centauri, figure_eight,
+    /// pythagorean, earth_moon, asteroid_belt]
+    #[arg(short, long, value_name = "NAME|FILE")]
+    pub scenario: Option<String>,
+
+    /// RNG seed (default: from the scenario, else random)
+    #[arg(long)]
+    pub seed: Option<u64>,
+
+    /// Particle buffer capacity
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_PARTICLE_CAPACITY, value_parser = particle_capacity)]
+    pub particles: usize,
+
+    /// Celestial body buffer capacity
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_BODY_CAPACITY, value_parser = body_capacity)]
+    pub bodies: usize,
+
+    /// Initial time scale, overriding the scenario's
+    #[arg(long, value_name = "SCALE", value_parser = positive)]
+    pub time_scale: Option<f32>,
+
+    /// Time integration scheme
+    #[arg(long, value_enum)]
+    pub integrator: Option<Integrator>,
+
+    /// Arithmetic for body positions and velocities; df64 keeps long runs
+    /// accurate at some cost in speed
+    #[arg(long, value_enum)]
+    pub precision: Option<Precision>,
+
+    /// Where the physics runs
+    #[arg(long, value_enum, default_value_t = Backend::Gpu)]
+    pub backend: Backend,
+
+    /// Window (or exported frame) width in pixels
+    #[arg(long, default_value_t = 1600)]
+    pub width: u32,
+
+    /// Window (or exported frame) height in pixels
+    #[arg(long, default_value_t = 900)]
+    pub height: u32,
+
+    /// Swap chain presentation mode
+    #[arg(long, value_enum, default_value_t = PresentMode::Fifo)]
+    pub present_mode: PresentMode,
+
+    /// Exposure multiplier applied before tone mapping
+    #[arg(long, default_value_t = 1.0, value_parser = positive)]
+    pub exposure: f32,
+
+    /// Tone mapping curve
+    #[arg(long, value_enum, default_value_t = ToneMap::Aces)]
+    pub tonemap: ToneMap,
+
+    /// Draw bodies at their physical radius (toggle with R)
+    #[arg(long)]
+    pub true_scale: bool,
+
+    /// MSAA samples per pixel for the scene (1, 2, 4 or 8)
+    #[arg(long, default_value_t = 4, value_parser = sample_count)]
+    pub msaa: u32,
+
+    /// Trail width in logical pixels
+    #[arg(long, default_value_t = 1.5, value_parser = positive)]
+    pub trail_width: f32,
+
+    /// Size of high-resolution screenshots (Shift+F12), e.g. 7680x4320
+    #[arg(long, value_name = "WxH", default_value = "7680x4320", value_parser = image_size)]
+    pub screenshot_size: (u32, u32),
+
+    /// Directory screenshots are saved to
+    #[arg(long, value_name = "DIR", default_value = ".")]
+    pub screenshot_dir: PathBuf,
+
+    /// Run without a window on the CPU backend until --duration is reached
+    #[arg(long, requires = "duration", conflicts_with = "backend")]
+    pub headless: bool,
+
+    /// Render frames offscreen into this directory as numbered PNGs until
+    /// --duration is reached, without a window
+    #[arg(long, value_name = "DIR", requires = "duration", conflicts_with_all = ["headless", "export_pipe"])]
+    pub export: Option<PathBuf>,
+
+    /// Like --export, but pipe raw RGBA frames to this shell command;
+    /// {width}, {height} and {fps} are filled in, e.g.
+    /// "ffmpeg -y -f rawvideo -pix_fmt rgba -s {width}x{height} -r {fps} -i - -pix_fmt yuv420p out.mp4"
+    #[arg(long, value_name = "COMMAND", requires = "duration", conflicts_with = "headless")]
+    pub export_pipe: Option<String>,
+
+    /// Frame rate of exported animations
+    #[arg(long, default_value_t = 30.0, value_parser = positive)]
+    pub fps: f32,
+
+    /// Simulated time per exported frame in years (default: the time
+    /// scale divided by --fps, the pace of the window)
+    #[arg(long, value_name = "YEARS", value_parser = positive)]
+    pub frame_time: Option<f32>,
+
+    /// Camera keyframes for exported animations (TOML), instead of the
+    /// scenario's [camera_path]
+    #[arg(long, value_name = "FILE")]
+    pub camera_path: Option<PathBuf>,
+
+    /// Stop after this much simulated time, in years
+    #[arg(long, value_name = "YEARS", value_parser = positive)]
+    pub duration: Option<f32>,
+
+    /// Keep the coordinate origin on this body, moving it whenever the body
+    /// strays more than an AU, for precision far from the scenario's centre
+    #[arg(long, value_name = "BODY")]
+    pub floating_origin: Option<String>,
+
+    /// Reference frame to view the system and write snapshots in:
+    /// "heliocentric", "barycentric", a body name to centre on, or two
+    /// names as "Sun:Jupiter" to turn with that pair
+    #[arg(long, value_name = "FRAME")]
+    pub frame: Option<String>,
+
+    /// Write the event log to this file on exit
+    #[arg(long, value_name = "FILE")]
+    pub record: Option<PathBuf>,
+
+    /// Replay an event log recorded with --record
+    #[arg(long, value_name = "FILE")]
+    pub replay: Option<PathBuf>,
+
+    /// Write final body and particle state as CSV on exit
+    #[arg(long, value_name = "FILE")]
+    pub snapshot: Option<PathBuf>,
+
+    /// Log verbosity: off, error, warn, info, debug, trace (overrides RUST_LOG)
+    #[arg(long, value_name = "LEVEL")]
+    pub log_level: Option<log::LevelFilter>,
+}
+
+/// Swap chain presentation mode
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum PresentMode {
+    /// VSync, supported everywhere
+    Fifo,
+    /// VSync without blocking; falls back to Fifo if unsupported
+    Mailbox,
+    /// No VSync, may tear
+    Immediate,
+}
+
+impl From<PresentMode> for wgpu::PresentMode {
+    fn from(mode: PresentMode) -> Self {
+        match mode {
+            PresentMode::Fifo => wgpu::PresentMode::Fifo,
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+            PresentMode::Immediate => wgpu::PresentMode::Immediate,
+        }
+    }
+}
+
+impl Cli {
+    pub fn capacity(&self) -> Capacity {
+        Capacity {
+            particles: self.particles,
+            bodies: self.bodies,
+        }
+    }
+
+    pub fn render_settings(&self) -> RenderSettings {
+        RenderSettings {
+            exposure: self.exposure,
+            tonemap: self.tonemap,
+            true_scale: self.true_scale,
+            trail_width: self.trail_width,
+            ..Default::default()
+        }
+    }
+
+    /// Whether frames are rendered offscreen instead of in a window
+    pub fn exporting(&self) -> bool {
+        self.export.is_some() || self.export_pipe.is_some()
+    }
+}
+
+fn capacity_in(s: &str, max: usize) -> Result<usize, String> {
+    let n: usize = s.parse().map_err(|_| format!("'{}' is not a number", s))?;
+    if !(1..=max).contains(&n) {
+        return Err(format!("must be between 1 and {}", max));
+    }
+    Ok(n)
+}
+
+fn particle_capacity(s: &str) -> Result<usize, String> {
+    capacity_in(s, MAX_PARTICLES)
+}
+
+fn body_capacity(s: &str) -> Result<usize, String> {
+    capacity_in(s, MAX_BODIES)
+}
+
+fn sample_count(s: &str) -> Result<u32, String> {
+    match s.parse::<u32>() {
+        Ok(n @ (1 | 2 | 4 | 8)) => Ok(n),
+        _ => Err(format!("'{}' is not a sample count (1, 2, 4 or 8)", s)),
+    }
+}
+
+fn image_size(s: &str) -> Result<(u32, u32), String> {
+    let size = s
+        .split_once('x')
+        .and_then(|(w, h)| Some((w.parse::<u32>().ok()?, h.parse::<u32>().ok()?)));
+    match size {
+        Some((w, h))
+            if (1..=MAX_CAPTURE_SIZE).contains(&w)
+                && (1..=MAX_CAPTURE_SIZE).contains(&h)
+                && w as u64 * h as u64 <= MAX_CAPTURE_PIXELS =>
+        {
+            Ok((w, h))
+        }
+        _ => Err(format!(
+            "'{}' is not a size like 7680x4320, at most {} a side and 16384x16384 in all",
+            s, MAX_CAPTURE_SIZE
+        )),
+    }
+}
+
+fn positive(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(v) if v.is_finite() && v > 0.0 => Ok(v),
+        _ => Err(format!("'{}' is not a positive number", s)),
+    }
+}
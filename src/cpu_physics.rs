@@ -0,0 +1,286 @@
+use glam::{DVec3, Vec3};
+use crate::frames::Frame;
+use crate::types::*;
+
+// ============================================================================
+// CPU reference backend: a straight port of physics.wgsl.
+// Slower than the compute shaders, but fully deterministic for a given seed
+// and event sequence, which makes recorded runs reproducible bit-for-bit.
+// ============================================================================
+
+/// Must match the hardcoded G in `cs_orbit`
+pub const ORBIT_G: f32 = 39.478_416;
+
+fn xyz(v: [f32; 4]) -> Vec3 {
+    Vec3::new(v[0], v[1], v[2])
+}
+
+/// Length of the opening drift: zero for Euler, half a step for leapfrog
+fn half_drift(params: &SimParams) -> f32 {
+    if params.integrator == 1 { 0.5 * params.dt } else { 0.0 }
+}
+
+fn clamp_length(v: Vec3, max_len: f32) -> Vec3 {
+    let len = v.length();
+    if len > max_len && len > 0.0 {
+        return v * (max_len / len);
+    }
+    v
+}
+
+/// Port of `cs_orbit`: advance bodies
+pub fn step_bodies(bodies: &mut [GpuCelestialBody], params: &SimParams) {
+    if params.body_precision == 1 {
+        return step_bodies_f64(bodies, params);
+    }
+    let bodies_in = bodies.to_vec();
+    let dt = params.dt;
+    let half = half_drift(params);
+    let soft_sq = params.softening * params.softening;
+
+    for (index, body) in bodies.iter_mut().enumerate() {
+        // Fixed bodies (by default the central star) don't move
+        if body.data[2] > 0.5 {
+            continue;
+        }
+
+        let vel = xyz(body.velocity);
+        let pos = xyz(body.position) + vel * half;
+        let mass = body.position[3];
+
+        let mut accel = Vec3::ZERO;
+        for (i, other) in bodies_in.iter().enumerate() {
+            if i == index {
+                continue;
+            }
+            let mut other_pos = xyz(other.position);
+            if other.data[2] < 0.5 {
+                other_pos += xyz(other.velocity) * half;
+            }
+            let diff = other_pos - pos;
+            let dist_sq = diff.dot(diff) + soft_sq;
+            let dist = dist_sq.sqrt();
+            let inv_dist3 = 1.0 / (dist * dist_sq);
+            accel += diff * (ORBIT_G * other.position[3] * inv_dist3);
+        }
+
+        let new_vel = vel + accel * dt;
+        let new_pos = pos + new_vel * (dt - half);
+        body.position = [new_pos.x, new_pos.y, new_pos.z, mass];
+        body.velocity = [new_vel.x, new_vel.y, new_vel.z, body.velocity[3]];
+        body.position_low = [0.0; 4];
+        body.velocity_low = [0.0; 4];
+    }
+}
+
+/// `step_bodies` for df64 precision. The host has real doubles, so this
+/// works in f64 rather than emulating the shader's pairs of floats.
+fn step_bodies_f64(bodies: &mut [GpuCelestialBody], params: &SimParams) {
+    let bodies_in = bodies.to_vec();
+    let dt = params.dt as f64;
+    let half = half_drift(params) as f64;
+    let soft_sq = params.softening as f64 * params.softening as f64;
+
+    for (index, body) in bodies.iter_mut().enumerate() {
+        if body.data[2] > 0.5 {
+            continue;
+        }
+
+        let vel = body.velocity_f64();
+        let pos = body.position_f64() + vel * half;
+
+        let mut accel = DVec3::ZERO;
+        for (i, other) in bodies_in.iter().enumerate() {
+            if i == index {
+                continue;
+            }
+            let mut other_pos = other.position_f64();
+            if other.data[2] < 0.5 {
+                other_pos += other.velocity_f64() * half;
+            }
+            let diff = other_pos - pos;
+            let dist_sq = diff.dot(diff) + soft_sq;
+            let inv_dist3 = 1.0 / (dist_sq.sqrt() * dist_sq);
+            accel += diff * (ORBIT_G as f64 * other.position[3] as f64 * inv_dist3);
+        }
+
+        let new_vel = vel + accel * dt;
+        body.set_position_f64(pos + new_vel * (dt - half));
+        body.set_velocity_f64(new_vel);
+    }
+}
+
+/// Port of `cs_trails`: shift each body's trail along by one point and end
+/// it at the body, as seen in `frame`
+pub fn push_trails(bodies: &[GpuCelestialBody], trails: &mut [GridVertex], trail_length: usize, frame: &Frame) {
+    for (index, body) in bodies.iter().enumerate() {
+        let start = index * trail_length;
+        let Some(trail) = trails.get_mut(start..start + trail_length) else {
+            continue;
+        };
+        trail.copy_within(1.., 0);
+        let c = body.color;
+        trail[trail_length - 1] = GridVertex {
+            position: frame.frame_position(body.position_f64()).as_vec3().into(),
+            _pad: 0.0,
+            color: [c[0] * 0.5, c[1] * 0.5, c[2] * 0.5, c[3] * 0.5],
+        };
+    }
+}
+
+fn compute_gravity(pos: Vec3, rewind: f32, bodies: &[GpuCelestialBody], params: &SimParams) -> Vec3 {
+    let mut accel = Vec3::ZERO;
+    let soft_sq = params.softening * params.softening;
+    for body in bodies {
+        let mut body_pos = xyz(body.position);
+        if body.data[2] < 0.5 {
+            body_pos -= xyz(body.velocity) * rewind;
+        }
+        let diff = body_pos - pos;
+        let dist_sq = diff.dot(diff) + soft_sq;
+        let dist = dist_sq.sqrt();
+        let inv_dist3 = 1.0 / (dist * dist_sq);
+        accel += diff * (params.gravitational_constant * body.position[3] * inv_dist3);
+    }
+    accel
+}
+
+fn compute_swarm(
+    index: usize,
+    pos: Vec3,
+    vel: Vec3,
+    particles: &[GpuParticle],
+    swarm: &[usize],
+    params: &SimParams,
+) -> Vec3 {
+    let mut separation = Vec3::ZERO;
+    let mut alignment = Vec3::ZERO;
+    let mut cohesion = Vec3::ZERO;
+    let (mut sep_count, mut align_count, mut coh_count) = (0u32, 0u32, 0u32);
+
+    for &i in swarm {
+        if i == index {
+            continue;
+        }
+        let other = &particles[i];
+        let other_pos = xyz(other.position);
+        let diff = pos - other_pos;
+        let dist = diff.length();
+
+        if dist < params.separation_radius && dist > 0.001 {
+            separation += diff.normalize() / dist;
+            sep_count += 1;
+        }
+        if dist < params.alignment_radius {
+            alignment += xyz(other.velocity);
+            align_count += 1;
+        }
+        if dist < params.cohesion_radius {
+            cohesion += other_pos;
+            coh_count += 1;
+        }
+    }
+
+    let mut force = Vec3::ZERO;
+
+    if sep_count > 0 {
+        separation /= sep_count as f32;
+        if separation.length() > 0.0 {
+            separation = clamp_length(separation.normalize() * params.max_speed - vel, params.max_force);
+        }
+        force += separation * params.separation_weight;
+    }
+
+    if align_count > 0 {
+        alignment /= align_count as f32;
+        if alignment.length() > 0.0 {
+            alignment = clamp_length(alignment.normalize() * params.max_speed - vel, params.max_force);
+        }
+        force += alignment * params.alignment_weight;
+    }
+
+    if coh_count > 0 {
+        cohesion /= coh_count as f32;
+        cohesion -= pos;
+        if cohesion.length() > 0.0 {
+            cohesion = clamp_length(cohesion.normalize() * params.max_speed - vel, params.max_force);
+        }
+        force += cohesion * params.cohesion_weight;
+    }
+
+    if params.target_active > 0.5 {
+        let goal = Vec3::new(params.target_x, params.target_y, params.target_z);
+        let mut seek = goal - pos;
+        if seek.length() > 0.0 {
+            seek = clamp_length(seek.normalize() * params.max_speed - vel, params.max_force * 2.0);
+        }
+        force += seek * 1.5;
+    }
+
+    force
+}
+
+/// Port of `cs_main`: gravity + swarm update for every live particle
+pub fn step_particles(particles: &mut [GpuParticle], bodies: &[GpuCelestialBody], params: &SimParams) {
+    let particles_in = particles.to_vec();
+    // The shader scans every slot and skips dead / non-swarm ones; scanning
+    // only live swarm members visits the same neighbours in the same order.
+    let swarm: Vec<usize> = particles_in
+        .iter()
+        .enumerate()
+        .filter(|(_, p)| p.data[3] >= 0.5 && p.velocity[3] >= 0.5)
+        .map(|(i, _)| i)
+        .collect();
+    let dt = params.dt;
+    let half = half_drift(params);
+
+    for (index, particle) in particles.iter_mut().enumerate() {
+        if particle.data[3] < 0.5 {
+            continue;
+        }
+
+        let vel = xyz(particle.velocity);
+        let pos = xyz(particle.position) + vel * half;
+        let mass = particle.position[3];
+        let is_swarm = particle.velocity[3] > 0.5;
+
+        let mut accel = compute_gravity(pos, half, bodies, params);
+        if is_swarm {
+            let swarm_force = compute_swarm(index, pos, vel, &particles_in, &swarm, params);
+            accel = accel * params.swarm_gravity_weight + swarm_force / mass.max(0.01);
+        }
+
+        let mut new_vel = vel + accel * dt;
+        if is_swarm {
+            new_vel = clamp_length(new_vel, params.max_speed);
+        }
+        new_vel *= params.damping;
+
+        let new_pos = pos + new_vel * (dt - half);
+
+        let mut alive = particle.data[3];
+        for body in bodies {
+            if (new_pos - xyz(body.position)).length() < body.velocity[3] * 1.1 {
+                alive = 0.0;
+            }
+        }
+        if new_pos.length() > params.despawn_radius {
+            alive = 0.0;
+        }
+
+        particle.position = [new_pos.x, new_pos.y, new_pos.z, mass];
+        particle.velocity = [new_vel.x, new_vel.y, new_vel.z, particle.velocity[3]];
+        particle.data[3] = alive;
+        particle.data[2] += dt;
+
+        if is_swarm {
+            let speed_ratio = (new_vel.length() / params.max_speed).clamp(0.0, 1.0);
+            particle.color = [
+                0.3 + speed_ratio * 0.7,
+                0.6 + speed_ratio * 0.4,
+                1.0,
+                0.8 + speed_ratio * 0.2,
+            ];
+        }
+    }
+}
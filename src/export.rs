@@ -0,0 +1,70 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+use crate::capture::{self, Image};
+
+// ============================================================================
+// Frame export: where the frames of an offscreen render go. Either numbered
+// PNG files with the simulation state in text chunks, or raw RGBA8 frames
+// piped to the standard input of an encoder such as ffmpeg.
+// ============================================================================
+
+pub enum FrameSink {
+    /// `frame_000000.png`, `frame_000001.png`, ... in a directory
+    Files(PathBuf),
+    /// An encoder reading raw frames from its standard input
+    Pipe { child: Child, stdin: ChildStdin },
+}
+
+impl FrameSink {
+    /// Write PNG files into `dir`, creating it if needed
+    pub fn files(dir: PathBuf) -> Result<Self, String> {
+        std::fs::create_dir_all(&dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        Ok(FrameSink::Files(dir))
+    }
+
+    /// Start `command` through the shell, after replacing {width}, {height}
+    /// and {fps} with the frame size and rate
+    pub fn pipe(command: &str, (width, height): (u32, u32), fps: f32) -> Result<Self, String> {
+        let command = command
+            .replace("{width}", &width.to_string())
+            .replace("{height}", &height.to_string())
+            .replace("{fps}", &fps.to_string());
+        let (shell, flag) = if cfg!(windows) { ("cmd", "/C") } else { ("sh", "-c") };
+        let mut child = Command::new(shell)
+            .args([flag, &command])
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("{}: {}", command, e))?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        log::info!("Piping frames to: {}", command);
+        Ok(FrameSink::Pipe { child, stdin })
+    }
+
+    /// Write frame number `index` with the given PNG text chunks
+    pub fn write(&mut self, index: u64, image: &Image, text: &[(&str, String)]) -> Result<(), String> {
+        match self {
+            FrameSink::Files(dir) => capture::save_png(&dir.join(format!("frame_{:06}.png", index)), image, text),
+            FrameSink::Pipe { stdin, .. } => stdin
+                .write_all(&image.pixels)
+                .map_err(|e| format!("Encoder stopped reading frames: {}", e)),
+        }
+    }
+
+    /// Close the pipe and wait for the encoder to finish writing
+    pub fn finish(self) -> Result<(), String> {
+        match self {
+            FrameSink::Files(_) => Ok(()),
+            FrameSink::Pipe { mut child, stdin } => {
+                drop(stdin);
+                let status = child.wait().map_err(|e| e.to_string())?;
+                if status.success() {
+                    Ok(())
+                } else {
+                    Err(format!("Encoder exited with {}", status))
+                }
+            }
+        }
+    }
+}
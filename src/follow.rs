@@ -0,0 +1,155 @@
+use glam::Vec3;
+
+use crate::camera::Camera;
+use crate::picking::{self, Selection};
+use crate::simulation::Simulation;
+
+// ============================================================================
+// Follow modes: the orbit camera's target rides along with a body or
+// particle, optionally turning with it about its primary, or keeps a set of
+// objects framed. Only the change in the followed point is applied each
+// frame, so orbiting and zooming by hand keep working, and the gap left
+// when a mode starts closes smoothly instead of jumping.
+// ============================================================================
+
+/// Fraction per second of the remaining gap to the subject closed
+const CATCH_UP_RATE: f32 = 4.0;
+/// Room left around a framed set, as a multiple of its radius
+const FRAME_MARGIN: f32 = 1.3;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FollowMode {
+    /// Free orbit camera
+    Off,
+    /// Keep the target on the subject
+    Centre(Selection),
+    /// Centre on the subject and turn with it about its primary, so the
+    /// primary holds its place on screen
+    OrbitLock(Selection),
+    /// Keep every object of the set in view
+    Frame(Vec<Selection>),
+}
+
+impl FollowMode {
+    pub fn describe(&self, sim: &Simulation) -> String {
+        match self {
+            FollowMode::Off => "free".to_string(),
+            FollowMode::Centre(s) => format!("following {}", s.name(sim)),
+            FollowMode::OrbitLock(s) => format!("locked to the orbit of {}", s.name(sim)),
+            FollowMode::Frame(set) => format!("framing {} objects", set.len()),
+        }
+    }
+}
+
+pub struct Follow {
+    pub mode: FollowMode,
+    /// Offset of the target from the followed point, shrinking to zero
+    lag: Vec3,
+    /// Followed point, lag included, as of the last update
+    anchor: Vec3,
+    /// Subject's angle about its primary at the last update
+    angle: Option<f32>,
+}
+
+impl Follow {
+    pub fn new() -> Self {
+        Self { mode: FollowMode::Off, lag: Vec3::ZERO, anchor: Vec3::ZERO, angle: None }
+    }
+
+    /// Switch modes, starting from wherever the camera is looking.
+    /// `lead` is how many years the host copy of the simulation lags
+    /// behind what is drawn.
+    pub fn set(&mut self, mode: FollowMode, camera: &Camera, sim: &Simulation, lead: f32) {
+        self.mode = mode;
+        self.angle = None;
+        self.anchor = camera.target;
+        self.lag = match self.focus(sim, lead) {
+            Some((point, _)) => camera.target - point,
+            None => Vec3::ZERO,
+        };
+    }
+
+    /// Move the followed point by `delta` without moving the camera, for
+    /// when the camera was moved with it
+    pub fn translate(&mut self, delta: Vec3) {
+        self.anchor += delta;
+    }
+
+    /// Move `camera` along with the subject; a pan by hand stays as an
+    /// offset from it. The mode ends if the subject disappears.
+    pub fn update(&mut self, camera: &mut Camera, sim: &Simulation, lead: f32, dt: f32) {
+        if self.mode == FollowMode::Off {
+            return;
+        }
+        let Some((point, radius)) = self.focus(sim, lead) else {
+            log::info!("Follow subject is gone, camera released");
+            self.mode = FollowMode::Off;
+            return;
+        };
+
+        self.lag *= (-CATCH_UP_RATE * dt).exp();
+        let anchor = point + self.lag;
+        camera.translate(anchor - self.anchor);
+        self.anchor = anchor;
+
+        match &self.mode {
+            FollowMode::OrbitLock(subject) => {
+                let angle = picking::primary(sim, *subject).and_then(|j| {
+                    let offset = point - position(sim, Selection::Body(j), lead)?;
+                    Some(offset.x.atan2(offset.z))
+                });
+                if let (Some(angle), Some(last)) = (angle, self.angle) {
+                    let turn = (angle - last + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU)
+                        - std::f32::consts::PI;
+                    camera.rotate(turn);
+                }
+                self.angle = angle;
+            }
+            FollowMode::Frame(_) => {
+                // Fit the bounding sphere inside the narrower field of view
+                let half_fov = (camera.fov * 0.5).min(((camera.fov * 0.5).tan() * camera.aspect).atan());
+                camera.zoom_to(radius * FRAME_MARGIN / half_fov.sin());
+            }
+            _ => {}
+        }
+    }
+
+    /// Point to keep centred and the radius around it to keep in view,
+    /// with positions moved on by `lead` years at their current velocity
+    fn focus(&self, sim: &Simulation, lead: f32) -> Option<(Vec3, f32)> {
+        let position = |s: &Selection| position(sim, *s, lead);
+        match &self.mode {
+            FollowMode::Off => None,
+            FollowMode::Centre(s) | FollowMode::OrbitLock(s) => Some((position(s)?, 0.0)),
+            FollowMode::Frame(set) => {
+                let points: Vec<(Vec3, f32)> = set
+                    .iter()
+                    .filter_map(|s| {
+                        let point = position(s)?;
+                        let radius = match *s {
+                            Selection::Body(i) => sim.bodies[i].velocity[3],
+                            Selection::Particle(_) => 0.0,
+                        };
+                        Some((point, radius))
+                    })
+                    .collect();
+                if points.is_empty() {
+                    return None;
+                }
+                let (min, max) = points.iter().fold((Vec3::MAX, Vec3::MIN), |(lo, hi), (p, r)| {
+                    (lo.min(*p - Vec3::splat(*r)), hi.max(*p + Vec3::splat(*r)))
+                });
+                let centre = (min + max) * 0.5;
+                let radius = points.iter().map(|(p, r)| (*p - centre).length() + r).fold(0.0, f32::max);
+                Some((centre, radius))
+            }
+        }
+    }
+}
+
+/// Where `selection` is in the reference frame, moved on by `lead` years
+/// at its current velocity
+fn position(sim: &Simulation, selection: Selection, lead: f32) -> Option<Vec3> {
+    let (p, v, _) = selection.state(sim)?;
+    Some(sim.frame_at(lead).point_in_frame(p + v * lead))
+}
@@ -0,0 +1,204 @@
+use glam::{DMat3, DVec3, Vec3};
+
+use crate::simulation::Simulation;
+use crate::types::*;
+
+// ============================================================================
+// Reference frames: the scene, the trails and snapshots can be given
+// relative to a star, the barycentre, any body, or a pair of bodies turning
+// with the line between them. The physics always runs in the scenario's
+// coordinates; `cs_frame` works the frame out on the GPU from the drawn
+// bodies each frame, and the host resolves the same frame from its copy
+// for labels, picking, following and output.
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ReferenceFrame {
+    /// The scenario's own coordinates
+    #[default]
+    Scenario,
+    /// Centred on the first star
+    Heliocentric,
+    /// Centred on the centre of mass of every body
+    Barycentric,
+    /// Centred on a body
+    Body(usize),
+    /// Centred on the barycentre of two bodies, x along the line from the
+    /// first to the second and y along their orbital angular momentum, so
+    /// the pair stays put (a synodic frame)
+    Rotating(usize, usize),
+}
+
+impl ReferenceFrame {
+    /// Parse a frame from the command line: "scenario", "heliocentric",
+    /// "barycentric", a body name, or two body names as "Sun:Jupiter"
+    pub fn parse(text: &str, sim: &Simulation) -> Result<Self, String> {
+        let body = |name: &str| sim.find_body(name).ok_or_else(|| format!("no body named '{}'", name));
+        Ok(match text {
+            "scenario" => ReferenceFrame::Scenario,
+            "heliocentric" => ReferenceFrame::Heliocentric,
+            "barycentric" => ReferenceFrame::Barycentric,
+            _ => match text.split_once(':') {
+                Some((a, b)) => {
+                    let (a, b) = (body(a)?, body(b)?);
+                    if a == b {
+                        return Err(format!("a rotating frame needs two different bodies, not {} twice", text));
+                    }
+                    ReferenceFrame::Rotating(a, b)
+                }
+                None => ReferenceFrame::Body(body(text)?),
+            },
+        })
+    }
+
+    pub fn describe(&self, sim: &Simulation) -> String {
+        let name = |i: usize| sim.body_info.get(i).map_or_else(|| format!("Body {}", i), |b| b.name.clone());
+        match *self {
+            ReferenceFrame::Scenario => "scenario".to_string(),
+            ReferenceFrame::Heliocentric => "heliocentric".to_string(),
+            ReferenceFrame::Barycentric => "barycentric".to_string(),
+            ReferenceFrame::Body(i) => format!("{}-centred", name(i)),
+            ReferenceFrame::Rotating(a, b) => format!("{}-{} rotating", name(a), name(b)),
+        }
+    }
+
+    /// Whether every body the frame refers to exists
+    pub fn fits(&self, body_count: usize) -> bool {
+        match *self {
+            ReferenceFrame::Body(i) => i < body_count,
+            ReferenceFrame::Rotating(a, b) => a < body_count && b < body_count,
+            _ => true,
+        }
+    }
+
+    /// The `frame_kind`, `frame_a` and `frame_b` fields of `SimParams`
+    pub fn params(&self, bodies: &[GpuCelestialBody]) -> [u32; 3] {
+        match *self {
+            ReferenceFrame::Scenario => [0, 0, 0],
+            ReferenceFrame::Heliocentric => [1, star(bodies) as u32, 0],
+            ReferenceFrame::Body(i) => [1, i as u32, 0],
+            ReferenceFrame::Barycentric => [2, 0, 0],
+            ReferenceFrame::Rotating(a, b) => [3, a as u32, b as u32],
+        }
+    }
+
+    /// The frame as it stands for `bodies`, each moved on by `lead` years
+    /// at its current velocity. Mirrors `cs_frame`, in f64.
+    pub fn resolve(&self, bodies: &[GpuCelestialBody], lead: f32) -> Frame {
+        let state = |i: usize| {
+            bodies.get(i).map(|b| {
+                let v = b.velocity_f64();
+                (b.position_f64() + v * lead as f64, v, b.position[3] as f64)
+            })
+        };
+        match *self {
+            ReferenceFrame::Scenario => Frame::IDENTITY,
+            ReferenceFrame::Heliocentric => Frame::centred(state(star(bodies))),
+            ReferenceFrame::Body(i) => Frame::centred(state(i)),
+            ReferenceFrame::Barycentric => {
+                let (mut weighted, mut momentum, mut total) = (DVec3::ZERO, DVec3::ZERO, 0.0);
+                for (p, v, m) in (0..bodies.len()).filter_map(state) {
+                    weighted += p * m;
+                    momentum += v * m;
+                    total += m;
+                }
+                if total <= 0.0 {
+                    return Frame::IDENTITY;
+                }
+                Frame { origin: weighted / total, velocity: momentum / total, ..Frame::IDENTITY }
+            }
+            ReferenceFrame::Rotating(a, b) => match (state(a), state(b)) {
+                (Some(a), Some(b)) => Frame::rotating(a, b),
+                _ => Frame::IDENTITY,
+            },
+        }
+    }
+}
+
+/// The first star, or the first body if there is none
+fn star(bodies: &[GpuCelestialBody]) -> usize {
+    bodies.iter().position(|b| b.data[0] > 0.5).unwrap_or(0)
+}
+
+/// A resolved reference frame: where it is and how it moves, in
+/// simulation coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frame {
+    pub origin: DVec3,
+    pub velocity: DVec3,
+    /// Simulation axes to frame axes; its rows are the frame's axes
+    pub rotation: DMat3,
+    /// Angular velocity of the frame's axes
+    pub spin: DVec3,
+}
+
+impl Frame {
+    pub const IDENTITY: Self =
+        Self { origin: DVec3::ZERO, velocity: DVec3::ZERO, rotation: DMat3::IDENTITY, spin: DVec3::ZERO };
+
+    /// Centred on a body, if it exists
+    fn centred(state: Option<(DVec3, DVec3, f64)>) -> Self {
+        state.map_or(Self::IDENTITY, |(origin, velocity, _)| Self { origin, velocity, ..Self::IDENTITY })
+    }
+
+    /// Turning with the pair `a`, `b` (position, velocity, mass) about
+    /// their barycentre. Keeps the simulation's axes while the pair is
+    /// on top of itself or moving straight along the line between them.
+    fn rotating((ra, va, ma): (DVec3, DVec3, f64), (rb, vb, mb): (DVec3, DVec3, f64)) -> Self {
+        let total = ma + mb;
+        let (wa, wb) = if total > 0.0 { (ma / total, mb / total) } else { (0.5, 0.5) };
+        let mut frame = Self { origin: ra * wa + rb * wb, velocity: va * wa + vb * wb, ..Self::IDENTITY };
+
+        let (r, v) = (rb - ra, vb - va);
+        let h = r.cross(v);
+        let x = r.normalize_or_zero();
+        // Normal on the +y side, so the frame is upright like the scenario
+        let y = if h.y < 0.0 { -h } else { h }.normalize_or_zero();
+        if x == DVec3::ZERO || y == DVec3::ZERO {
+            return frame;
+        }
+        frame.rotation = DMat3::from_cols(x, y, x.cross(y)).transpose();
+        frame.spin = h / r.length_squared();
+        frame
+    }
+
+    pub fn frame_position(&self, p: DVec3) -> DVec3 {
+        self.rotation * (p - self.origin)
+    }
+
+    pub fn sim_position(&self, q: DVec3) -> DVec3 {
+        self.origin + self.rotation.transpose() * q
+    }
+
+    /// Velocity seen in the frame of something at `p` moving at `v`
+    pub fn frame_velocity(&self, p: DVec3, v: DVec3) -> DVec3 {
+        self.rotation * (v - self.velocity - self.spin.cross(p - self.origin))
+    }
+
+    /// `frame_position` in f32
+    pub fn point_in_frame(&self, p: Vec3) -> Vec3 {
+        self.frame_position(p.as_dvec3()).as_vec3()
+    }
+
+    /// `sim_position` in f32
+    pub fn point_in_sim(&self, q: Vec3) -> Vec3 {
+        self.sim_position(q.as_dvec3()).as_vec3()
+    }
+
+    /// A direction in the frame, turned back into simulation axes
+    pub fn direction_in_sim(&self, d: Vec3) -> Vec3 {
+        (self.rotation.transpose() * d.as_dvec3()).as_vec3()
+    }
+
+    /// Copies of `bodies` moved into the frame, for drawing on the host
+    pub fn bodies_in_frame(&self, bodies: &[GpuCelestialBody]) -> Vec<GpuCelestialBody> {
+        bodies
+            .iter()
+            .map(|b| {
+                let mut b = *b;
+                b.set_position_f64(self.frame_position(b.position_f64()));
+                b
+            })
+            .collect()
+    }
+}
@@ -1,11 +1,28 @@
 use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use wgpu::util::DeviceExt;
+use crate::camera::Camera;
+use crate::post::{PostProcess, RenderSettings, HDR_FORMAT};
+use crate::simulation::Simulation;
+use crate::starfield;
+use crate::surface::{MipChain, TEXTURE_HEIGHT, TEXTURE_MIPS, TEXTURE_WIDTH};
+use crate::text::TextRenderer;
 use crate::types::*;
+use crate::viewports::Viewport;
+
+/// Side of the square of pixels around the cursor searched for particles
+pub const PICK_SIZE: u32 = 9;
+const PICK_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+/// Inset views that can be drawn beside the main one, each with its own
+/// camera uniform
+pub const MAX_INSETS: usize = 2;
 
 /// Holds all GPU resources and pipelines
 pub struct GpuState {
-    // Core
-    pub surface: wgpu::Surface<'static>,
+    // Core. Without a window there is no surface, and `config` only
+    // records the size and output format of offscreen renders.
+    pub surface: Option<wgpu::Surface<'static>>,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub config: wgpu::SurfaceConfiguration,
@@ -15,42 +32,106 @@ pub struct GpuState {
     pub body_buffers: [wgpu::Buffer; 2],     // ping-pong
     pub sim_params_buffer: wgpu::Buffer,
     pub camera_buffer: wgpu::Buffer,
+    inset_camera_buffers: Vec<wgpu::Buffer>,
     pub orbit_vertex_buffer: wgpu::Buffer,
     pub orbit_vertex_count: u32,
+    pub star_buffer: wgpu::Buffer,
+    pub ring_buffer: wgpu::Buffer,
+    pub ring_count: u32,
+    pub surface_buffer: wgpu::Buffer,
 
     // Compute pipelines
     pub particle_compute_pipeline: wgpu::ComputePipeline,
     pub orbit_compute_pipeline: wgpu::ComputePipeline,
     pub particle_compute_bind_groups: [wgpu::BindGroup; 2], // ping-pong
     pub orbit_compute_bind_groups: [wgpu::BindGroup; 2],
+    pub frame_compute_pipeline: wgpu::ComputePipeline,
+    pub trail_compute_pipeline: wgpu::ComputePipeline,
+    pub frame_compute_bind_groups: [wgpu::BindGroup; 2], // by body buffer
 
     // Render pipelines
     pub particle_render_pipeline: wgpu::RenderPipeline,
     pub body_render_pipeline: wgpu::RenderPipeline,
     pub orbit_render_pipeline: wgpu::RenderPipeline,
+    pub star_render_pipeline: wgpu::RenderPipeline,
+    pub ring_render_pipeline: wgpu::RenderPipeline,
+    pub selection_render_pipeline: wgpu::RenderPipeline,
     pub render_bind_group: wgpu::BindGroup,
+    inset_bind_groups: Vec<wgpu::BindGroup>,
+    pub world_bind_groups: [wgpu::BindGroup; 2], // by body buffer
+    world_bind_group_layout: wgpu::BindGroupLayout,
+    surface_textures: wgpu::TextureView,
+    surface_sampler: wgpu::Sampler,
 
     // Depth buffer
     pub depth_texture: wgpu::TextureView,
 
+    // Particle picking around the cursor
+    pick_pipeline: wgpu::RenderPipeline,
+    pick_target: wgpu::Texture,
+    pick_depth: wgpu::TextureView,
+    pick_staging: wgpu::Buffer,
+
+    // HDR scene target, bloom and tone mapping
+    pub post: PostProcess,
+
+    // Labels, HUD and help overlay
+    pub text: TextRenderer,
+
+    // Non-blocking body readback for the GPU backend
+    body_staging: wgpu::Buffer,
+    body_staging_ready: Arc<AtomicBool>,
+    body_readback: Option<BodyReadback>,
+    body_generation: u64,
+
     // State
+    pub capacity: Capacity,
     pub frame_index: usize,
 }
 
+/// A body buffer copy in flight
+struct BodyReadback {
+    count: usize,
+    particle: Option<usize>,
+    time: f32,
+    generation: u64,
+    mapped: bool,
+}
+
+impl BodyReadback {
+    fn size(&self) -> u64 {
+        ((self.count + self.particle.is_some() as usize) * mem::size_of::<GpuCelestialBody>()) as u64
+    }
+}
+
+/// Bodies and, if one was asked for, a particle copied back from the GPU
+pub struct StateReadback {
+    /// Sim time of the copy
+    pub time: f32,
+    pub bodies: Vec<GpuCelestialBody>,
+    pub particle: Option<(usize, GpuParticle)>,
+}
+
 impl GpuState {
-    pub async fn new(window: std::sync::Arc<winit::window::Window>) -> Self {
-        let size = window.inner_size();
+    pub async fn new(
+        window: Option<std::sync::Arc<winit::window::Window>>,
+        (width, height): (u32, u32),
+        capacity: Capacity,
+        present_mode: wgpu::PresentMode,
+        render_settings: RenderSettings,
+        msaa: u32,
+    ) -> Self {
         let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
             backends: wgpu::Backends::all(),
             ..Default::default()
         });
 
-        let surface = instance.create_surface(window.clone()).unwrap();
+        let surface = window.map(|window| instance.create_surface(window).unwrap());
 
         let adapter = instance
             .request_adapter(&wgpu::RequestAdapterOptions {
                 power_preference: wgpu::PowerPreference::HighPerformance,
-                compatible_surface: Some(&surface),
+                compatible_surface: surface.as_ref(),
                 force_fallback_adapter: false,
             })
             .await
@@ -72,25 +153,49 @@ impl GpuState {
             .await
             .expect("Failed to create device");
 
-        let surface_caps = surface.get_capabilities(&adapter);
-        let surface_format = surface_caps
-            .formats
-            .iter()
-            .find(|f| f.is_srgb())
-            .copied()
-            .unwrap_or(surface_caps.formats[0]);
-
-        let config = wgpu::SurfaceConfiguration {
+        let mut config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface_format,
-            width: size.width.max(1),
-            height: size.height.max(1),
-            present_mode: wgpu::PresentMode::Fifo, // VSync
-            alpha_mode: surface_caps.alpha_modes[0],
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            width: width.max(1),
+            height: height.max(1),
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
             view_formats: vec![],
             desired_maximum_frame_latency: 2,
         };
-        surface.configure(&device, &config);
+        if let Some(surface) = &surface {
+            let surface_caps = surface.get_capabilities(&adapter);
+            config.format = surface_caps
+                .formats
+                .iter()
+                .find(|f| f.is_srgb())
+                .copied()
+                .unwrap_or(surface_caps.formats[0]);
+            config.present_mode = if surface_caps.present_modes.contains(&present_mode) {
+                present_mode
+            } else {
+                log::warn!("Present mode {:?} not supported, using Fifo", present_mode);
+                wgpu::PresentMode::Fifo
+            };
+            config.alpha_mode = surface_caps.alpha_modes[0];
+            surface.configure(&device, &config);
+        }
+        let surface_format = config.format;
+
+        // MSAA on the scene pass, falling back to the largest supported count
+        let supported = |n: u32| {
+            [HDR_FORMAT, wgpu::TextureFormat::Depth32Float]
+                .iter()
+                .all(|f| adapter.get_texture_format_features(*f).flags.sample_count_supported(n))
+        };
+        let sample_count = [msaa, 8, 4, 2, 1]
+            .into_iter()
+            .find(|&n| n <= msaa && supported(n))
+            .unwrap_or(1);
+        if sample_count != msaa {
+            log::warn!("{}x MSAA not supported, using {}x", msaa, sample_count);
+        }
+        let multisample = wgpu::MultisampleState { count: sample_count, ..Default::default() };
 
         // Create shader modules
         let physics_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
@@ -103,15 +208,20 @@ impl GpuState {
             source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/render.wgsl").into()),
         });
 
+        let frame_shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Frame Compute Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/frame.wgsl").into()),
+        });
+
         // ====================================================================
         // Create buffers
         // ====================================================================
 
         let particle_size = mem::size_of::<GpuParticle>();
-        let _particle_buf_size = (MAX_PARTICLES * particle_size) as u64;
+        let _particle_buf_size = (capacity.particles * particle_size) as u64;
 
         // Initialize with dead particles
-        let initial_particles: Vec<GpuParticle> = vec![GpuParticle::dead(); MAX_PARTICLES];
+        let initial_particles: Vec<GpuParticle> = vec![GpuParticle::dead(); capacity.particles];
         let particle_data = bytemuck::cast_slice(&initial_particles);
 
         let particle_buffers = [
@@ -134,9 +244,9 @@ impl GpuState {
         ];
 
         let body_size = mem::size_of::<GpuCelestialBody>();
-        let _body_buf_size = (MAX_BODIES * body_size) as u64;
+        let _body_buf_size = (capacity.bodies * body_size) as u64;
         let initial_bodies: Vec<GpuCelestialBody> =
-            vec![bytemuck::Zeroable::zeroed(); MAX_BODIES];
+            vec![bytemuck::Zeroable::zeroed(); capacity.bodies];
         let body_data = bytemuck::cast_slice(&initial_bodies);
 
         let body_buffers = [
@@ -171,16 +281,29 @@ impl GpuState {
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
             mapped_at_creation: false,
         });
+        let inset_camera_buffers: Vec<wgpu::Buffer> = (0..MAX_INSETS)
+            .map(|_| {
+                device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Inset Camera"),
+                    size: mem::size_of::<CameraUniform>() as u64,
+                    usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                })
+            })
+            .collect();
 
         // Orbit lines / Trails
-        // 32 bodies * 512 points per trail
-        let trail_length = 512;
-        let orbit_buffer_size = (32 * trail_length * mem::size_of::<GridVertex>()) as u64;
+        // One trail of up to MAX_TRAIL_LENGTH points per body slot
+        let orbit_buffer_size = (capacity.bodies * MAX_TRAIL_LENGTH * mem::size_of::<GridVertex>()) as u64;
 
         let orbit_vertex_buffer = device.create_buffer(&wgpu::BufferDescriptor {
             label: Some("Orbit Trails"),
             size: orbit_buffer_size,
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::STORAGE,
+            // Read back to shift the trails when the floating origin moves
+            usage: wgpu::BufferUsages::VERTEX
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::STORAGE,
             mapped_at_creation: false,
         });
 
@@ -320,17 +443,6 @@ impl GpuState {
                         },
                         count: None,
                     },
-                    // Trails Out
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 3,
-                        visibility: wgpu::ShaderStages::COMPUTE,
-                        ty: wgpu::BindingType::Buffer {
-                            ty: wgpu::BufferBindingType::Storage { read_only: false },
-                            has_dynamic_offset: false,
-                            min_binding_size: None,
-                        },
-                        count: None,
-                    },
                 ],
             });
 
@@ -358,7 +470,6 @@ impl GpuState {
                     wgpu::BindGroupEntry { binding: 0, resource: body_buffers[0].as_entire_binding() },
                     wgpu::BindGroupEntry { binding: 1, resource: body_buffers[1].as_entire_binding() },
                     wgpu::BindGroupEntry { binding: 2, resource: sim_params_buffer.as_entire_binding() },
-                    wgpu::BindGroupEntry { binding: 3, resource: orbit_vertex_buffer.as_entire_binding() },
                 ],
             }),
             device.create_bind_group(&wgpu::BindGroupDescriptor {
@@ -368,38 +479,120 @@ impl GpuState {
                     wgpu::BindGroupEntry { binding: 0, resource: body_buffers[1].as_entire_binding() },
                     wgpu::BindGroupEntry { binding: 1, resource: body_buffers[0].as_entire_binding() },
                     wgpu::BindGroupEntry { binding: 2, resource: sim_params_buffer.as_entire_binding() },
-                    wgpu::BindGroupEntry { binding: 3, resource: orbit_vertex_buffer.as_entire_binding() },
                 ],
             }),
         ];
 
+        // ====================================================================
+        // Compute pipelines: reference frame and trails
+        // ====================================================================
+
+        // Reference frame of the drawn bodies, written by cs_frame
+        let frame_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Reference Frame"),
+            contents: bytemuck::bytes_of(&GpuFrame::IDENTITY),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::STORAGE,
+        });
+
+        let compute_storage_entry = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let frame_compute_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Frame Compute BGL"),
+                entries: &[
+                    // Bodies
+                    compute_storage_entry(0, true),
+                    // Params
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
+                    // Frame
+                    compute_storage_entry(2, false),
+                    // Trails
+                    compute_storage_entry(3, false),
+                ],
+            });
+
+        let frame_compute_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Frame Compute PL"),
+                bind_group_layouts: &[&frame_compute_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let frame_pipeline = |label, entry_point| {
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&frame_compute_pipeline_layout),
+                module: &frame_shader,
+                entry_point,
+                compilation_options: Default::default(),
+            })
+        };
+        let frame_compute_pipeline = frame_pipeline("Frame Compute", "cs_frame");
+        let trail_compute_pipeline = frame_pipeline("Trail Compute", "cs_trails");
+
+        let frame_compute_bind_groups = [0, 1].map(|i| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(&format!("Frame Compute BG {}", i)),
+                layout: &frame_compute_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry { binding: 0, resource: body_buffers[i].as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 1, resource: sim_params_buffer.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 2, resource: frame_buffer.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 3, resource: orbit_vertex_buffer.as_entire_binding() },
+                ],
+            })
+        });
+
         // ====================================================================
         // Render pipelines
         // ====================================================================
 
+        let uniform_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        // Camera and reference frame
         let render_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 label: Some("Render BGL"),
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
-                    },
-                    count: None,
-                }],
+                entries: &[uniform_entry(0), uniform_entry(1)],
             });
 
-        let render_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: Some("Render BG"),
-            layout: &render_bind_group_layout,
-            entries: &[wgpu::BindGroupEntry {
-                binding: 0,
-                resource: camera_buffer.as_entire_binding(),
-            }],
-        });
+        let camera_bind_group = |label, camera: &wgpu::Buffer| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(label),
+                layout: &render_bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry { binding: 0, resource: camera.as_entire_binding() },
+                    wgpu::BindGroupEntry { binding: 1, resource: frame_buffer.as_entire_binding() },
+                ],
+            })
+        };
+        let render_bind_group = camera_bind_group("Render BG", &camera_buffer);
+        let inset_bind_groups =
+            inset_camera_buffers.iter().map(|buffer| camera_bind_group("Inset Render BG", buffer)).collect();
 
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
@@ -438,28 +631,107 @@ impl GpuState {
         let depth_stencil_state = wgpu::DepthStencilState {
             format: wgpu::TextureFormat::Depth32Float,
             depth_write_enabled: false, // no depth write for transparent
-            depth_compare: wgpu::CompareFunction::Less,
+            depth_compare: wgpu::CompareFunction::Greater, // reversed-Z
             stencil: wgpu::StencilState::default(),
             bias: wgpu::DepthBiasState::default(),
         };
 
+        // Per-body scene data for the particle, body, trail, ring and
+        // selection pipelines: rings, surfaces, surface textures, trails and
+        // particles, next to the body buffer
+        let ring_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Rings"),
+            size: (capacity.bodies * mem::size_of::<GpuRing>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let surface_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Surfaces"),
+            size: (capacity.bodies * mem::size_of::<GpuSurface>()) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let surface_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Surface Sampler"),
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+        let surface_textures = Self::create_surface_textures(&device, &queue, &[]);
+        let storage_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let world_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("World BGL"),
+                entries: &[
+                    storage_entry(0),
+                    storage_entry(1),
+                    storage_entry(2),
+                    storage_entry(5),
+                    storage_entry(6),
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2Array,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            });
+        let world_bind_groups = [0, 1].map(|i| {
+            Self::create_world_bind_group(
+                &device,
+                &world_bind_group_layout,
+                [&body_buffers[i], &ring_buffer, &surface_buffer, &orbit_vertex_buffer, &particle_buffers[i]],
+                &surface_textures,
+                &surface_sampler,
+                i,
+            )
+        });
+        let world_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("World PL"),
+                bind_group_layouts: &[&render_bind_group_layout, &world_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
         // Particle render pipeline
         let particle_render_pipeline =
             device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
                 label: Some("Particle Render"),
-                layout: Some(&render_pipeline_layout),
+                layout: Some(&world_pipeline_layout),
                 vertex: wgpu::VertexState {
                     module: &render_shader,
                     entry_point: "vs_particle",
                     compilation_options: Default::default(),
-                    buffers: &[particle_instance_layout.clone()],
+                    buffers: std::slice::from_ref(&particle_instance_layout),
                 },
                 fragment: Some(wgpu::FragmentState {
                     module: &render_shader,
                     entry_point: "fs_particle",
                     compilation_options: Default::default(),
                     targets: &[Some(wgpu::ColorTargetState {
-                        format: surface_format,
+                        format: HDR_FORMAT,
                         blend: Some(blend_additive),
                         write_mask: wgpu::ColorWrites::ALL,
                     })],
@@ -469,7 +741,7 @@ impl GpuState {
                     ..Default::default()
                 },
                 depth_stencil: Some(depth_stencil_state.clone()),
-                multisample: wgpu::MultisampleState::default(),
+                multisample,
                 multiview: None,
             });
 
@@ -489,7 +761,7 @@ impl GpuState {
         let body_render_pipeline =
             device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
                 label: Some("Body Render"),
-                layout: Some(&render_pipeline_layout),
+                layout: Some(&world_pipeline_layout),
                 vertex: wgpu::VertexState {
                     module: &render_shader,
                     entry_point: "vs_body",
@@ -501,7 +773,7 @@ impl GpuState {
                     entry_point: "fs_body",
                     compilation_options: Default::default(),
                     targets: &[Some(wgpu::ColorTargetState {
-                        format: surface_format,
+                        format: HDR_FORMAT,
                         blend: Some(blend_alpha),
                         write_mask: wgpu::ColorWrites::ALL,
                     })],
@@ -513,56 +785,232 @@ impl GpuState {
                 depth_stencil: Some(wgpu::DepthStencilState {
                     format: wgpu::TextureFormat::Depth32Float,
                     depth_write_enabled: true,
-                    depth_compare: wgpu::CompareFunction::Less,
+                    depth_compare: wgpu::CompareFunction::Greater,
                     stencil: wgpu::StencilState::default(),
                     bias: wgpu::DepthBiasState::default(),
                 }),
-                multisample: wgpu::MultisampleState::default(),
+                multisample,
                 multiview: None,
             });
 
-        // Orbit line vertex layout
-        let grid_vertex_layout = wgpu::VertexBufferLayout {
-            array_stride: mem::size_of::<GridVertex>() as u64,
-            step_mode: wgpu::VertexStepMode::Vertex,
+        // Trails as screen-space ribbons, read from the trail buffer
+        let orbit_render_pipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Orbit Render"),
+                layout: Some(&world_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &render_shader,
+                    entry_point: "vs_trail",
+                    compilation_options: Default::default(),
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &render_shader,
+                    entry_point: "fs_trail",
+                    compilation_options: Default::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: HDR_FORMAT,
+                        blend: Some(blend_alpha),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    ..Default::default()
+                },
+                depth_stencil: Some(depth_stencil_state),
+                multisample,
+                multiview: None,
+            });
+
+        // Background starfield
+        let star_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Starfield"),
+            contents: bytemuck::cast_slice(&starfield::generate()),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+        let star_instance_layout = wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<StarVertex>() as u64,
+            step_mode: wgpu::VertexStepMode::Instance,
             attributes: &[
-                wgpu::VertexAttribute { format: wgpu::VertexFormat::Float32x3, offset: 0, shader_location: 0 },
+                wgpu::VertexAttribute { format: wgpu::VertexFormat::Float32x4, offset: 0, shader_location: 0 },
                 wgpu::VertexAttribute { format: wgpu::VertexFormat::Float32x4, offset: 16, shader_location: 1 },
             ],
         };
 
-        let orbit_render_pipeline =
+        let star_render_pipeline =
             device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                label: Some("Orbit Render"),
+                label: Some("Star Render"),
                 layout: Some(&render_pipeline_layout),
                 vertex: wgpu::VertexState {
                     module: &render_shader,
-                    entry_point: "vs_grid",
+                    entry_point: "vs_star",
+                    compilation_options: Default::default(),
+                    buffers: &[star_instance_layout],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &render_shader,
+                    entry_point: "fs_star",
+                    compilation_options: Default::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: HDR_FORMAT,
+                        blend: Some(blend_additive),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    ..Default::default()
+                },
+                // At infinity: behind everything, so skip the depth test
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Always,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample,
+                multiview: None,
+            });
+
+        let ring_render_pipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Ring Render"),
+                layout: Some(&world_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &render_shader,
+                    entry_point: "vs_ring",
                     compilation_options: Default::default(),
-                    buffers: &[grid_vertex_layout],
+                    buffers: &[],
                 },
                 fragment: Some(wgpu::FragmentState {
                     module: &render_shader,
-                    entry_point: "fs_grid",
+                    entry_point: "fs_ring",
                     compilation_options: Default::default(),
                     targets: &[Some(wgpu::ColorTargetState {
-                        format: surface_format,
+                        format: HDR_FORMAT,
                         blend: Some(blend_alpha),
                         write_mask: wgpu::ColorWrites::ALL,
                     })],
                 }),
                 primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::LineStrip,
-                    strip_index_format: None,
+                    topology: wgpu::PrimitiveTopology::TriangleList,
                     ..Default::default()
                 },
-                depth_stencil: Some(depth_stencil_state),
+                // Tested against the planet, but translucent: no depth write
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Greater,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample,
+                multiview: None,
+            });
+
+        // Selection outline, over everything else
+        let selection_render_pipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Selection Render"),
+                layout: Some(&world_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &render_shader,
+                    entry_point: "vs_selection",
+                    compilation_options: Default::default(),
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &render_shader,
+                    entry_point: "fs_selection",
+                    compilation_options: Default::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: HDR_FORMAT,
+                        blend: Some(blend_alpha),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    ..Default::default()
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Always,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample,
+                multiview: None,
+            });
+
+        // Particle picking: indices into a small R32Uint target around the
+        // cursor, nearest particle in front
+        let pick_pipeline =
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Particle Pick"),
+                layout: Some(&render_pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &render_shader,
+                    entry_point: "vs_pick",
+                    compilation_options: Default::default(),
+                    buffers: std::slice::from_ref(&particle_instance_layout),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &render_shader,
+                    entry_point: "fs_pick",
+                    compilation_options: Default::default(),
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: PICK_FORMAT,
+                        blend: None,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::TriangleList,
+                    ..Default::default()
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: wgpu::TextureFormat::Depth32Float,
+                    depth_write_enabled: true,
+                    depth_compare: wgpu::CompareFunction::Greater,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
                 multisample: wgpu::MultisampleState::default(),
                 multiview: None,
             });
+        let pick_target = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Pick Target"),
+            size: wgpu::Extent3d { width: PICK_SIZE, height: PICK_SIZE, depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: PICK_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let pick_depth = Self::create_depth_texture(&device, PICK_SIZE, PICK_SIZE, 1);
+        let pick_staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Pick Readback"),
+            size: (wgpu::COPY_BYTES_PER_ROW_ALIGNMENT * PICK_SIZE) as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
 
         // Depth texture
-        let depth_texture = Self::create_depth_texture(&device, &config);
+        let depth_texture = Self::create_depth_texture(&device, config.width, config.height, sample_count);
+        let post = PostProcess::new(&device, surface_format, config.width, config.height, render_settings, sample_count);
+        let text = TextRenderer::new(&device, &queue, surface_format);
+        let body_staging = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Body Readback"),
+            // Room for the selected particle after the bodies
+            size: ((capacity.bodies + 1) * mem::size_of::<GpuCelestialBody>()) as u64,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
 
         Self {
             surface,
@@ -573,34 +1021,58 @@ impl GpuState {
             body_buffers,
             sim_params_buffer,
             camera_buffer,
+            inset_camera_buffers,
             orbit_vertex_buffer,
             orbit_vertex_count: 0,
+            star_buffer,
+            ring_buffer,
+            ring_count: 0,
+            surface_buffer,
             particle_compute_pipeline,
             orbit_compute_pipeline,
             particle_compute_bind_groups,
             orbit_compute_bind_groups,
+            frame_compute_pipeline,
+            trail_compute_pipeline,
+            frame_compute_bind_groups,
             particle_render_pipeline,
             body_render_pipeline,
             orbit_render_pipeline,
+            star_render_pipeline,
+            ring_render_pipeline,
+            selection_render_pipeline,
             render_bind_group,
+            inset_bind_groups,
+            world_bind_groups,
+            world_bind_group_layout,
+            surface_textures,
+            surface_sampler,
             depth_texture,
+            pick_pipeline,
+            pick_target,
+            pick_depth,
+            pick_staging,
+            post,
+            text,
+            body_staging,
+            body_staging_ready: Arc::new(AtomicBool::new(false)),
+            body_readback: None,
+            body_generation: 0,
+            capacity,
             frame_index: 0,
         }
     }
 
-    pub fn create_depth_texture(
-        device: &wgpu::Device,
-        config: &wgpu::SurfaceConfiguration,
-    ) -> wgpu::TextureView {
+    pub fn create_depth_texture(device: &wgpu::Device, width: u32, height: u32, sample_count: u32) -> wgpu::TextureView {
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label: Some("Depth"),
             size: wgpu::Extent3d {
-                width: config.width.max(1),
-                height: config.height.max(1),
+                width: width.max(1),
+                height: height.max(1),
                 depth_or_array_layers: 1,
             },
             mip_level_count: 1,
-            sample_count: 1,
+            sample_count,
             dimension: wgpu::TextureDimension::D2,
             format: wgpu::TextureFormat::Depth32Float,
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
@@ -609,12 +1081,501 @@ impl GpuState {
         texture.create_view(&wgpu::TextureViewDescriptor::default())
     }
 
+    /// Record one physics step of the GPU backend: bodies from ping-pong
+    /// buffer `frame_index % 2` into the other, their trails in the
+    /// reference frame, then the particles against the updated bodies.
+    /// Bump `frame_index` once it is submitted.
+    pub fn step_physics(&self, encoder: &mut wgpu::CommandEncoder, body_count: usize) {
+        let frame_idx = self.frame_index;
+
+        // Pass 1: celestial body orbits
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Orbit Compute"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.orbit_compute_pipeline);
+            pass.set_bind_group(0, &self.orbit_compute_bind_groups[frame_idx % 2], &[]);
+            pass.dispatch_workgroups((body_count as u32).div_ceil(32), 1, 1);
+        }
+
+        // Sync: copy updated bodies so particle compute can read them
+        encoder.copy_buffer_to_buffer(
+            &self.body_buffers[(frame_idx + 1) % 2],
+            0,
+            &self.body_buffers[frame_idx % 2],
+            0,
+            (self.capacity.bodies * mem::size_of::<GpuCelestialBody>()) as u64,
+        );
+
+        // Pass 2: trails, in the frame the updated bodies set
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Trail Compute"),
+                timestamp_writes: None,
+            });
+            pass.set_bind_group(0, &self.frame_compute_bind_groups[(frame_idx + 1) % 2], &[]);
+            pass.set_pipeline(&self.frame_compute_pipeline);
+            pass.dispatch_workgroups(1, 1, 1);
+            pass.set_pipeline(&self.trail_compute_pipeline);
+            pass.dispatch_workgroups((body_count as u32).div_ceil(32), 1, 1);
+        }
+
+        // Pass 3: particles (gravity + swarm)
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("Particle Compute"),
+            timestamp_writes: None,
+        });
+        pass.set_pipeline(&self.particle_compute_pipeline);
+        pass.set_bind_group(0, &self.particle_compute_bind_groups[frame_idx % 2], &[]);
+        pass.dispatch_workgroups((self.capacity.particles as u32).div_ceil(256), 1, 1);
+    }
+
+    /// Camera uniform for a `width` x `height` target. Trails are
+    /// `trail_width` logical pixels wide at `dpi` physical pixels per
+    /// logical one; `pixel_scale` additionally enlarges every pixel-sized
+    /// feature, so a high-resolution capture looks like the window.
+    pub fn camera_uniform(
+        &self,
+        camera: &Camera,
+        sim: &Simulation,
+        (width, height): (u32, u32),
+        dpi: f32,
+        pixel_scale: f32,
+    ) -> CameraUniform {
+        let settings = &self.post.settings;
+        // Rotation only: shaders subtract the eye, split into a high and
+        // a low part, before anything else
+        let mut view = camera.view_matrix();
+        view.w_axis = glam::Vec4::W;
+        let proj = camera.proj_matrix();
+        let eye = camera.eye_position_f64();
+        let high = eye.as_vec3();
+        let low = (eye - high.as_dvec3()).as_vec3();
+        CameraUniform {
+            view_proj: (proj * view).to_cols_array_2d(),
+            view: view.to_cols_array_2d(),
+            proj: proj.to_cols_array_2d(),
+            eye_pos: [high.x, high.y, high.z, 1.0],
+            eye_low: [low.x, low.y, low.z, 0.0],
+            screen_size: [
+                width as f32,
+                height as f32,
+                sim.time,
+                if settings.true_scale { 1.0 } else { 0.0 },
+            ],
+            background: [settings.starfield, 0.0, 0.0, 0.0],
+            scene: [
+                sim.bodies.len() as f32,
+                settings.trail_width * dpi * pixel_scale,
+                sim.params.trail_length as f32,
+                pixel_scale,
+            ],
+            selection: [0.0; 4],
+        }
+    }
+
+    /// Record the main scene pass: starfield, bodies, trails, rings and
+    /// particles, read from ping-pong buffer `buffer`, once for each of
+    /// `viewports`, in the reference frame those bodies set. `target` is
+    /// the colour attachment and its MSAA resolve target, if any.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_scene(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        (view, resolve): (&wgpu::TextureView, Option<&wgpu::TextureView>),
+        depth: &wgpu::TextureView,
+        viewports: &[Viewport],
+        buffer: usize,
+        body_count: u32,
+        trail_length: u32,
+    ) {
+        {
+            let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                label: Some("Frame Compute"),
+                timestamp_writes: None,
+            });
+            pass.set_pipeline(&self.frame_compute_pipeline);
+            pass.set_bind_group(0, &self.frame_compute_bind_groups[buffer], &[]);
+            pass.dispatch_workgroups(1, 1, 1);
+        }
+
+        let mut rp = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Main Render"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: resolve,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color { r: 0.005, g: 0.005, b: 0.02, a: 1.0 }),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth,
+                depth_ops: Some(wgpu::Operations {
+                    // Reversed-Z: 0 is infinitely far
+                    load: wgpu::LoadOp::Clear(0.0),
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        for viewport in viewports {
+            let [x, y, width, height] = viewport.rect;
+            rp.set_viewport(x as f32, y as f32, width as f32, height as f32, 0.0, 1.0);
+            rp.set_scissor_rect(x, y, width, height);
+            let camera = viewport.inset.map_or(&self.render_bind_group, |i| &self.inset_bind_groups[i]);
+            self.draw_view(&mut rp, camera, buffer, body_count, trail_length);
+        }
+        // The GL backend resolves MSAA with a blit that honours the scissor,
+        // so open it back up over every view before the pass ends
+        let (right, bottom) = viewports
+            .iter()
+            .fold((0, 0), |(r, b), v| (r.max(v.rect[0] + v.rect[2]), b.max(v.rect[1] + v.rect[3])));
+        rp.set_scissor_rect(0, 0, right, bottom);
+    }
+
+    /// Draw the scene into the current viewport of `rp`, through the
+    /// camera uniform bound by `camera`
+    fn draw_view<'a>(
+        &'a self,
+        rp: &mut wgpu::RenderPass<'a>,
+        camera: &'a wgpu::BindGroup,
+        buffer: usize,
+        body_count: u32,
+        trail_length: u32,
+    ) {
+        // 0. Background starfield
+        rp.set_pipeline(&self.star_render_pipeline);
+        rp.set_bind_group(0, camera, &[]);
+        rp.set_vertex_buffer(0, self.star_buffer.slice(..));
+        rp.draw(0..6, 0..starfield::STAR_COUNT as u32);
+
+        // 1. Celestial bodies
+        rp.set_pipeline(&self.body_render_pipeline);
+        rp.set_bind_group(0, camera, &[]);
+        rp.set_bind_group(1, &self.world_bind_groups[buffer], &[]);
+        rp.set_vertex_buffer(0, self.body_buffers[buffer].slice(..));
+        rp.draw(0..6, 0..body_count);
+
+        // 2. Orbit trails as ribbons, one instance per body, after the
+        //    bodies so their sphere depth hides the parts behind them
+        if self.orbit_vertex_count > 0 {
+            rp.set_pipeline(&self.orbit_render_pipeline);
+            rp.set_bind_group(0, camera, &[]);
+            rp.set_bind_group(1, &self.world_bind_groups[buffer], &[]);
+            let num_bodies = self.orbit_vertex_count / trail_length;
+            rp.draw(0..(trail_length - 1) * 6, 0..num_bodies);
+        }
+
+        // 2b. Rings, tested against the bodies' depth
+        if self.ring_count > 0 {
+            rp.set_pipeline(&self.ring_render_pipeline);
+            rp.set_bind_group(0, camera, &[]);
+            rp.set_bind_group(1, &self.world_bind_groups[buffer], &[]);
+            rp.draw(0..6, 0..self.ring_count);
+        }
+
+        // 3. All particles
+        rp.set_pipeline(&self.particle_render_pipeline);
+        rp.set_bind_group(0, camera, &[]);
+        rp.set_bind_group(1, &self.world_bind_groups[buffer], &[]);
+        rp.set_vertex_buffer(0, self.particle_buffers[buffer].slice(..));
+        rp.draw(0..6, 0..self.capacity.particles as u32);
+
+        // 4. Outline of the selection, if the uniform names one
+        rp.set_pipeline(&self.selection_render_pipeline);
+        rp.set_bind_group(0, camera, &[]);
+        rp.set_bind_group(1, &self.world_bind_groups[buffer], &[]);
+        rp.draw(0..6, 0..1);
+    }
+
+    /// Camera uniform buffer read by `viewport`
+    pub fn camera_buffer_for(&self, viewport: &Viewport) -> &wgpu::Buffer {
+        viewport.inset.map_or(&self.camera_buffer, |i| &self.inset_camera_buffers[i])
+    }
+
+    /// Index of the particle drawn nearest the middle of the `PICK_SIZE`
+    /// square that `uniform` projects onto the pick target, reading
+    /// ping-pong buffer `buffer`. Blocks until the GPU is done.
+    pub fn pick_particle(&self, uniform: &CameraUniform, buffer: usize) -> Option<usize> {
+        self.queue.write_buffer(&self.camera_buffer, 0, bytemuck::bytes_of(uniform));
+        let view = self.pick_target.create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Pick") });
+        {
+            let mut rp = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Particle Pick"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations { load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT), store: wgpu::StoreOp::Store },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.pick_depth,
+                    depth_ops: Some(wgpu::Operations { load: wgpu::LoadOp::Clear(0.0), store: wgpu::StoreOp::Store }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            rp.set_pipeline(&self.pick_pipeline);
+            rp.set_bind_group(0, &self.render_bind_group, &[]);
+            rp.set_vertex_buffer(0, self.particle_buffers[buffer].slice(..));
+            rp.draw(0..6, 0..self.capacity.particles as u32);
+        }
+        let row_bytes = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        encoder.copy_texture_to_buffer(
+            self.pick_target.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &self.pick_staging,
+                layout: wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(row_bytes), rows_per_image: Some(PICK_SIZE) },
+            },
+            self.pick_target.size(),
+        );
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = self.pick_staging.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        self.device.poll(wgpu::Maintain::Wait);
+        let picked = {
+            let data = slice.get_mapped_range();
+            let centre = (PICK_SIZE / 2) as i32;
+            (0..PICK_SIZE as i32)
+                .flat_map(|y| (0..PICK_SIZE as i32).map(move |x| (x, y)))
+                .filter_map(|(x, y)| {
+                    let at = (y as u32 * row_bytes + x as u32 * 4) as usize;
+                    let id = u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+                    (id > 0).then(|| ((x - centre).pow(2) + (y - centre).pow(2), id as usize - 1))
+                })
+                .min()
+                .map(|(_, index)| index)
+        };
+        self.pick_staging.unmap();
+        picked
+    }
+
+    /// Copy the first `count` elements of a buffer back to the host.
+    /// Blocks until the GPU has finished all submitted work.
+    pub fn read_buffer<T: bytemuck::Pod>(&self, buffer: &wgpu::Buffer, count: usize) -> Vec<T> {
+        let size = (count * mem::size_of::<T>()) as u64;
+        let staging = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Readback"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Readback") });
+        encoder.copy_buffer_to_buffer(buffer, 0, &staging, 0, size);
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = staging.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        self.device.poll(wgpu::Maintain::Wait);
+        let data = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+        staging.unmap();
+        data
+    }
+
+    /// Read element `index` of a buffer. Blocks like `read_buffer`.
+    pub fn read_element<T: bytemuck::Pod>(&self, buffer: &wgpu::Buffer, index: usize) -> T {
+        let size = mem::size_of::<T>() as u64;
+        let staging = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Readback"),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Readback") });
+        encoder.copy_buffer_to_buffer(buffer, index as u64 * size, &staging, 0, size);
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = staging.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        self.device.poll(wgpu::Maintain::Wait);
+        let value = bytemuck::pod_read_unaligned(&slice.get_mapped_range());
+        staging.unmap();
+        value
+    }
+
+    /// Start copying the latest body state, at sim time `time`, and the
+    /// particle `particle` if given, to the host, unless a copy is still in
+    /// flight. Call `map_body_readback` after submitting `encoder`.
+    pub fn copy_bodies_for_readback(
+        &mut self,
+        encoder: &mut wgpu::CommandEncoder,
+        count: usize,
+        particle: Option<usize>,
+        time: f32,
+    ) {
+        if self.body_readback.is_some() || count == 0 {
+            return;
+        }
+        // The render buffers hold the newest state whether or not we stepped
+        let latest = (self.frame_index + 1) % 2;
+        let stride = mem::size_of::<GpuCelestialBody>() as u64;
+        encoder.copy_buffer_to_buffer(&self.body_buffers[latest], 0, &self.body_staging, 0, count as u64 * stride);
+        if let Some(index) = particle {
+            // Particles are no larger than bodies, so one fits after them
+            encoder.copy_buffer_to_buffer(
+                &self.particle_buffers[latest],
+                (index * mem::size_of::<GpuParticle>()) as u64,
+                &self.body_staging,
+                count as u64 * stride,
+                mem::size_of::<GpuParticle>() as u64,
+            );
+        }
+        self.body_readback = Some(BodyReadback { count, particle, time, generation: self.body_generation, mapped: false });
+    }
+
+    pub fn map_body_readback(&mut self) {
+        if let Some(readback) = self.body_readback.as_mut().filter(|r| !r.mapped) {
+            readback.mapped = true;
+            let ready = self.body_staging_ready.clone();
+            self.body_staging
+                .slice(..readback.size())
+                .map_async(wgpu::MapMode::Read, move |result| ready.store(result.is_ok(), Ordering::Release));
+        }
+    }
+
+    /// State from a finished readback, typically a frame old. Copies
+    /// started before the last `discard_body_readback` are dropped.
+    pub fn poll_body_readback(&mut self) -> Option<StateReadback> {
+        self.device.poll(wgpu::Maintain::Poll);
+        if !self.body_staging_ready.swap(false, Ordering::Acquire) {
+            return None;
+        }
+        let readback = self.body_readback.take()?;
+        let state = {
+            let data = self.body_staging.slice(..readback.size()).get_mapped_range();
+            let (bodies, rest) = data.split_at(readback.count * mem::size_of::<GpuCelestialBody>());
+            StateReadback {
+                time: readback.time,
+                bodies: bytemuck::cast_slice(bodies).to_vec(),
+                particle: readback
+                    .particle
+                    .map(|index| (index, bytemuck::pod_read_unaligned(&rest[..mem::size_of::<GpuParticle>()]))),
+            }
+        };
+        self.body_staging.unmap();
+        (readback.generation == self.body_generation).then_some(state)
+    }
+
+    /// Array of equirectangular surface textures, one layer each. With
+    /// none, a single black texel keeps the binding valid.
+    fn create_surface_textures(device: &wgpu::Device, queue: &wgpu::Queue, textures: &[MipChain]) -> wgpu::TextureView {
+        let (width, height, mips) = if textures.is_empty() {
+            (1, 1, 1)
+        } else {
+            (TEXTURE_WIDTH, TEXTURE_HEIGHT, TEXTURE_MIPS)
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Surface Textures"),
+            // Two layers at least: GL backends treat a single layer as a plain 2D texture
+            size: wgpu::Extent3d { width, height, depth_or_array_layers: textures.len().max(2) as u32 },
+            mip_level_count: mips,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        for (layer, chain) in textures.iter().enumerate() {
+            for (level, pixels) in chain.iter().enumerate() {
+                let (w, h) = ((width >> level).max(1), (height >> level).max(1));
+                queue.write_texture(
+                    wgpu::ImageCopyTexture {
+                        texture: &texture,
+                        mip_level: level as u32,
+                        origin: wgpu::Origin3d { x: 0, y: 0, z: layer as u32 },
+                        aspect: wgpu::TextureAspect::All,
+                    },
+                    pixels,
+                    wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(4 * w), rows_per_image: Some(h) },
+                    wgpu::Extent3d { width: w, height: h, depth_or_array_layers: 1 },
+                );
+            }
+        }
+        if textures.is_empty() {
+            queue.write_texture(
+                texture.as_image_copy(),
+                &[0, 0, 0, 255],
+                wgpu::ImageDataLayout { offset: 0, bytes_per_row: Some(4), rows_per_image: Some(1) },
+                wgpu::Extent3d { width: 1, height: 1, depth_or_array_layers: 1 },
+            );
+        }
+        texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        })
+    }
+
+    fn create_world_bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        buffers: [&wgpu::Buffer; 5],
+        textures: &wgpu::TextureView,
+        sampler: &wgpu::Sampler,
+        index: usize,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some(&format!("World BG {}", index)),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: buffers[0].as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 1, resource: buffers[1].as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 2, resource: buffers[2].as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(textures) },
+                wgpu::BindGroupEntry { binding: 4, resource: wgpu::BindingResource::Sampler(sampler) },
+                wgpu::BindGroupEntry { binding: 5, resource: buffers[3].as_entire_binding() },
+                wgpu::BindGroupEntry { binding: 6, resource: buffers[4].as_entire_binding() },
+            ],
+        })
+    }
+
+    /// Replace the surface textures; body surfaces refer to them by layer
+    pub fn set_surface_textures(&mut self, textures: &[MipChain]) {
+        self.surface_textures = Self::create_surface_textures(&self.device, &self.queue, textures);
+        self.world_bind_groups = [0, 1].map(|i| {
+            Self::create_world_bind_group(
+                &self.device,
+                &self.world_bind_group_layout,
+                [
+                    &self.body_buffers[i],
+                    &self.ring_buffer,
+                    &self.surface_buffer,
+                    &self.orbit_vertex_buffer,
+                    &self.particle_buffers[i],
+                ],
+                &self.surface_textures,
+                &self.surface_sampler,
+                i,
+            )
+        });
+    }
+
+    /// Ignore any readback in flight, e.g. after the bodies were replaced
+    pub fn discard_body_readback(&mut self) {
+        self.body_generation += 1;
+    }
+
     pub fn resize(&mut self, width: u32, height: u32) {
         if width > 0 && height > 0 {
             self.config.width = width;
             self.config.height = height;
-            self.surface.configure(&self.device, &self.config);
-            self.depth_texture = Self::create_depth_texture(&self.device, &self.config);
+            if let Some(surface) = &self.surface {
+                surface.configure(&self.device, &self.config);
+            }
+            self.depth_texture = Self::create_depth_texture(&self.device, width, height, self.post.sample_count);
+            self.post.resize(&self.device, width, height);
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,275 @@
+use glam::{Vec3, Vec4Swizzles};
+
+use crate::camera::{Camera, Projection};
+use crate::simulation::{BodyInfo, Simulation};
+use crate::text::TextRenderer;
+use crate::types::*;
+use crate::viewports::Viewport;
+
+// ============================================================================
+// Screen overlays drawn with the glyph atlas: body name labels, Lagrange
+// point markers, the stats HUD and the controls help screen.
+// ============================================================================
+
+/// Which overlays are shown
+pub struct Overlay {
+    pub labels: bool,
+    pub hud: bool,
+    pub help: bool,
+}
+
+impl Default for Overlay {
+    fn default() -> Self {
+        Self { labels: true, hud: true, help: false }
+    }
+}
+
+/// Key bindings by section, shown by the help overlay and on stdout
+pub const CONTROLS: &[(&str, &[(&str, &str)])] = &[
+    ("CAMERA", &[
+        ("Left Mouse + Drag", "Orbit camera"),
+        ("Scroll Wheel", "Zoom in/out"),
+        ("F", "Follow selection / let go"),
+        ("K", "Lock to selection's orbit / let go"),
+        ("B", "Frame all bodies"),
+        ("V", "Orbit / fly camera"),
+        ("N", "Drop camera path key"),
+        ("U", "Perspective / orthographic"),
+        ("M", "Show / hide top and side views"),
+    ]),
+    ("FLY CAMERA", &[
+        ("W / A / S / D", "Forward / left / back / right"),
+        ("Q / E", "Down / up"),
+        ("Z / X", "Roll left / right"),
+        ("Left Mouse + Drag", "Look around"),
+        ("Shift / Scroll Wheel", "Boost / set speed"),
+    ]),
+    ("INTERACTION", &[
+        ("Left Click", "Select and inspect body / particle"),
+        ("Right Click", "Set swarm target (waypoint)"),
+        ("Middle Click", "Spawn 100 particles at cursor"),
+        ("S", "Spawn 200 swarm particles"),
+        ("O", "Spawn orbital swarm around Earth"),
+        ("T", "Clear swarm target"),
+        ("C", "Clear all particles"),
+        ("P", "Next scenario preset"),
+    ]),
+    ("MODES", &[
+        ("1", "Swarm mode (boids + gravity)"),
+        ("2", "Free mode  (gravity only)"),
+        ("3", "Burst mode (scatter)"),
+    ]),
+    ("TUNING", &[
+        ("Q / W / E", "Increase sep / align / cohesion"),
+        ("G", "Increase gravity influence on swarm"),
+        ("+/-", "Speed up / slow down time"),
+        ("Space", "Pause / Resume"),
+    ]),
+    ("DISPLAY", &[
+        ("R", "True-scale / visual body sizes"),
+        ("L", "Show / hide body labels"),
+        ("F1", "Show / hide parameter panel"),
+        ("F2", "Show / hide HUD"),
+        ("F12", "Screenshot (Shift: high resolution)"),
+        ("H", "Show / hide this help"),
+        ("Esc", "Quit"),
+    ]),
+];
+
+const KEY_COLUMN: usize = 21;
+const MARGIN: f32 = 12.0;
+const PADDING: f32 = 8.0;
+const BACKDROP: [f32; 4] = [0.0, 0.0, 0.0, 0.55];
+const TEXT_COLOR: [f32; 4] = [0.85, 0.9, 1.0, 1.0];
+const MARKER_COLOR: [f32; 4] = [1.0, 0.8, 0.35, 0.95];
+const SHADOW: [f32; 4] = [0.0, 0.0, 0.0, 0.8];
+
+fn controls_text() -> String {
+    let mut out = String::new();
+    for (i, (section, keys)) in CONTROLS.iter().enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        out.push_str(section);
+        out.push('\n');
+        for (key, action) in keys.iter() {
+            out.push_str(&format!("  {:<width$}{}\n", key, action, width = KEY_COLUMN));
+        }
+    }
+    out
+}
+
+pub fn print_controls() {
+    const WIDTH: usize = 62;
+    println!("\n╔{}╗", "═".repeat(WIDTH));
+    println!("║{:^WIDTH$}║", "STAR SYSTEM SIMULATOR");
+    println!("║{:^WIDTH$}║", "GPU-Accelerated Physics");
+    println!("╠{}╣", "═".repeat(WIDTH));
+    for line in controls_text().lines() {
+        println!("║  {:<w$}║", line, w = WIDTH - 2);
+    }
+    println!("╚{}╝\n", "═".repeat(WIDTH));
+}
+
+/// Text with a one-pixel drop shadow, readable over bright backgrounds
+fn shadowed(text: &mut TextRenderer, x: f32, y: f32, s: &str, color: [f32; 4]) {
+    text.text(x + 1.0, y + 1.0, s, SHADOW);
+    text.text(x, y, s, color);
+}
+
+/// Text on a translucent box
+fn boxed(text: &mut TextRenderer, x: f32, y: f32, s: &str) {
+    let (w, h) = text.measure(s);
+    text.rect(x, y, w + 2.0 * PADDING, h + 2.0 * PADDING, BACKDROP);
+    text.text(x + PADDING, y + PADDING, s, TEXT_COLOR);
+}
+
+/// Simulated time since the start, as years and days
+fn sim_date(time: f32) -> String {
+    let years = time.max(0.0).floor();
+    let days = (time.max(0.0) - years) * 365.25;
+    format!("T+ {} y {:05.1} d", years as u32, days)
+}
+
+/// Stats in the top-right corner
+pub fn draw_hud(text: &mut TextRenderer, sim: &Simulation, scenario: &str, fps: f32, width: f32) {
+    let p = &sim.params;
+    let hud = format!(
+        "{}\n{}\ntime scale {:.4}x{}\n{:.0} FPS\nparticles {} / {}\nmode {:?}  {:?}  {:?}  {:?}\nframe {}\nswarm sep {:.1}  align {:.1}  coh {:.1}\ngravity weight {:.1}",
+        scenario,
+        sim_date(sim.time),
+        sim.time_scale,
+        if sim.paused { "  (paused)" } else { "" },
+        fps,
+        sim.num_alive_particles,
+        sim.particles.len(),
+        sim.spawn_mode,
+        sim.backend,
+        sim.integrator(),
+        sim.precision(),
+        sim.frame().describe(sim),
+        p.separation_weight,
+        p.alignment_weight,
+        p.cohesion_weight,
+        p.swarm_gravity_weight,
+    );
+    let (w, _) = text.measure(&hud);
+    boxed(text, width - w - 2.0 * PADDING - MARGIN, MARGIN, &hud);
+}
+
+/// Controls, centred on screen
+pub fn draw_help(text: &mut TextRenderer, width: f32, height: f32) {
+    let help = controls_text();
+    let (w, h) = text.measure(&help);
+    let x = ((width - w) * 0.5 - PADDING).max(0.0);
+    let y = ((height - h) * 0.5 - PADDING).max(0.0);
+    boxed(text, x, y, &help);
+}
+
+/// Caption in the top-left corner of a view
+pub fn draw_view_title(text: &mut TextRenderer, viewport: &Viewport, title: &str) {
+    shadowed(text, viewport.rect[0] as f32 + MARGIN, viewport.rect[1] as f32 + MARGIN, title, TEXT_COLOR);
+}
+
+/// True if another body lies between the eye and `target`
+fn occluded(eye: Vec3, target: Vec3, index: usize, bodies: &[GpuCelestialBody], radius: impl Fn(&GpuCelestialBody) -> f32) -> bool {
+    let to_target = target - eye;
+    let dist = to_target.length();
+    let dir = to_target / dist.max(1e-12);
+    bodies.iter().enumerate().any(|(j, b)| {
+        if j == index {
+            return false;
+        }
+        let center = Vec3::new(b.position[0], b.position[1], b.position[2]);
+        let t = (center - eye).dot(dir);
+        t > 0.0 && t < dist && (eye + dir * t).distance(center) < radius(b)
+    })
+}
+
+/// Name labels beside each visible body in `viewport`. Stars and heavier
+/// bodies win when labels would overlap; hidden bodies get no label.
+pub fn draw_labels(
+    text: &mut TextRenderer,
+    camera: &Camera,
+    viewport: &Viewport,
+    bodies: &[GpuCelestialBody],
+    info: &[BodyInfo],
+    true_scale: bool,
+) {
+    let radius = |b: &GpuCelestialBody| if true_scale { b.data[3] } else { b.velocity[3] };
+    let [left, top, width, height] = viewport.rect.map(|v| v as f32);
+    let view_proj = camera.view_proj_matrix();
+    let view = camera.view_matrix();
+    let forward = -Vec3::new(view.col(0).z, view.col(1).z, view.col(2).z);
+
+    let mut order: Vec<usize> = (0..bodies.len().min(info.len())).collect();
+    order.sort_by(|&a, &b| {
+        let key = |i: usize| (bodies[i].data[0] > 0.5, bodies[i].position[3]);
+        key(b).partial_cmp(&key(a)).unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let mut placed: Vec<[f32; 4]> = Vec::new();
+    for i in order {
+        let b = &bodies[i];
+        let pos = Vec3::new(b.position[0], b.position[1], b.position[2]);
+        let clip = view_proj * pos.extend(1.0);
+        if clip.w <= 0.0 {
+            continue;
+        }
+        let ndc = clip.xy() / clip.w;
+        if ndc.x.abs() > 1.0 || ndc.y.abs() > 1.0 {
+            continue;
+        }
+        // Orthographic sight lines are parallel, from beyond the far side
+        let eye = match camera.projection {
+            Projection::Perspective => camera.eye_position(),
+            Projection::Orthographic => pos - forward * 2.0 * camera.max_distance,
+        };
+        if occluded(eye, pos, i, bodies, radius) {
+            continue;
+        }
+
+        let depth = -(view * pos.extend(1.0)).z;
+        let on_screen = radius(b) / camera.pixel_size(depth, height);
+        let sx = left + (ndc.x + 1.0) * 0.5 * width + on_screen.max(4.0) + 6.0;
+        let sy = top + (1.0 - ndc.y) * 0.5 * height - text.line_height * 0.5;
+        let (w, h) = text.measure(&info[i].name);
+        let rect = [sx, sy, sx + w, sy + h];
+        if rect[2] > left + width {
+            continue;
+        }
+        let overlaps = placed
+            .iter()
+            .any(|r| rect[0] < r[2] && r[0] < rect[2] && rect[1] < r[3] && r[1] < rect[3]);
+        if overlaps {
+            continue;
+        }
+        placed.push(rect);
+
+        let c = b.color;
+        let color = [0.5 + 0.5 * c[0], 0.5 + 0.5 * c[1], 0.5 + 0.5 * c[2], 0.95];
+        shadowed(text, sx, sy, &info[i].name, color);
+    }
+}
+
+/// A cross and its name at each point in `viewport`, e.g. the Lagrange
+/// points of a pair. Positions are in the drawn frame.
+pub fn draw_markers(text: &mut TextRenderer, camera: &Camera, viewport: &Viewport, markers: &[(&str, Vec3)]) {
+    let [left, top, width, height] = viewport.rect.map(|v| v as f32);
+    let view_proj = camera.view_proj_matrix();
+    let (cross_w, _) = text.measure("+");
+    for &(name, pos) in markers {
+        let clip = view_proj * pos.extend(1.0);
+        if clip.w <= 0.0 {
+            continue;
+        }
+        let ndc = clip.xy() / clip.w;
+        if ndc.x.abs() > 1.0 || ndc.y.abs() > 1.0 {
+            continue;
+        }
+        let sx = left + (ndc.x + 1.0) * 0.5 * width;
+        let sy = top + (1.0 - ndc.y) * 0.5 * height - text.line_height * 0.5;
+        shadowed(text, sx - cross_w * 0.5, sy, "+", MARKER_COLOR);
+        shadowed(text, sx + cross_w, sy, name, MARKER_COLOR);
+    }
+}
@@ -0,0 +1,151 @@
+use glam::{DVec3, Vec3};
+
+use crate::frames::ReferenceFrame;
+use crate::simulation::Simulation;
+use crate::types::*;
+
+// ============================================================================
+// Lagrange points of a primary-secondary pair, from the circular restricted
+// three-body problem. The pair's current separation and angular velocity
+// stand in for a circular orbit, so the points follow the bodies as they
+// move. L1-L3 lie on the line through the pair and are found by Newton
+// iteration; L4 and L5 make equilateral triangles with the pair, L4
+// leading the secondary along its orbit and L5 trailing it. A primary
+// pinned in place (stars are, by default) feels no pull from the secondary,
+// so for such a pair only the points on the line are true equilibria.
+// ============================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LagrangePoint {
+    L1,     // Between the pair
+    L2,     // Beyond the secondary
+    L3,     // Beyond the primary, opposite the secondary
+    L4,     // 60 degrees ahead of the secondary
+    L5,     // 60 degrees behind the secondary
+}
+
+impl LagrangePoint {
+    pub const ALL: [LagrangePoint; 5] =
+        [LagrangePoint::L1, LagrangePoint::L2, LagrangePoint::L3, LagrangePoint::L4, LagrangePoint::L5];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LagrangePoint::L1 => "L1",
+            LagrangePoint::L2 => "L2",
+            LagrangePoint::L3 => "L3",
+            LagrangePoint::L4 => "L4",
+            LagrangePoint::L5 => "L5",
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|p| p.name().eq_ignore_ascii_case(text))
+            .ok_or_else(|| format!("unknown Lagrange point '{}', expected L1 to L5", text))
+    }
+
+    /// Where the point is and how it moves for the pair `primary`,
+    /// `secondary` in `bodies`. None if either body is missing or the pair
+    /// is not orbiting.
+    pub fn locate(&self, bodies: &[GpuCelestialBody], primary: usize, secondary: usize) -> Option<Placement> {
+        let (p, s) = (bodies.get(primary)?, bodies.get(secondary)?);
+        let (mp, ms) = (p.position[3] as f64, s.position[3] as f64);
+        if !(mp > 0.0 && ms >= 0.0) {
+            return None;
+        }
+        // The synodic frame: barycentre, x from primary to secondary
+        let frame = ReferenceFrame::Rotating(primary, secondary).resolve(bodies, 0.0);
+        if frame.spin == DVec3::ZERO {
+            return None;
+        }
+        let r = s.position_f64() - p.position_f64();
+        let separation = r.length();
+        let x = r / separation;
+        let ahead = frame.spin.normalize().cross(x);
+        let mu = ms / (mp + ms);
+
+        // In units of the separation, from the barycentre
+        let (along, across) = match self {
+            LagrangePoint::L1 => (collinear(mu, 1.0 - mu - (mu / 3.0).cbrt()), 0.0),
+            LagrangePoint::L2 => (collinear(mu, 1.0 - mu + (mu / 3.0).cbrt()), 0.0),
+            LagrangePoint::L3 => (collinear(mu, -1.0 - 5.0 * mu / 12.0), 0.0),
+            LagrangePoint::L4 => (0.5 - mu, 0.75f64.sqrt()),
+            LagrangePoint::L5 => (0.5 - mu, -(0.75f64.sqrt())),
+        };
+        let offset = (x * along + ahead * across) * separation;
+        Some(Placement {
+            position: (frame.origin + offset).as_vec3(),
+            velocity: (frame.velocity + frame.spin.cross(offset)).as_vec3(),
+            separation: separation as f32,
+            speed: (frame.spin.length() * separation) as f32,
+        })
+    }
+}
+
+/// Root of the collinear equilibrium condition along the pair's axis, with
+/// the primary at `-mu` and the secondary at `1 - mu`, starting from `x`
+fn collinear(mu: f64, mut x: f64) -> f64 {
+    for _ in 0..50 {
+        let (a, b) = (x + mu, x - 1.0 + mu);
+        let (a3, b3) = (a.abs().powi(3), b.abs().powi(3));
+        let f = x - (1.0 - mu) * a / a3 - mu * b / b3;
+        let slope = 1.0 + 2.0 * (1.0 - mu) / a3 + 2.0 * mu / b3;
+        let step = f / slope;
+        x -= step;
+        if step.abs() < 1e-15 {
+            break;
+        }
+    }
+    x
+}
+
+/// A located Lagrange point, in simulation coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Placement {
+    pub position: Vec3,
+    /// Velocity of something held at the point in the turning frame
+    pub velocity: Vec3,
+    /// Distance between the pair
+    pub separation: f32,
+    /// Speed of the secondary relative to the primary
+    pub speed: f32,
+}
+
+/// A Lagrange point of a particular pair of bodies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LagrangeSite {
+    pub primary: usize,
+    pub secondary: usize,
+    pub point: LagrangePoint,
+}
+
+impl LagrangeSite {
+    /// Parse "Sun:Jupiter:L4"
+    pub fn parse(text: &str, sim: &Simulation) -> Result<Self, String> {
+        let (primary, secondary, point) = split(text)?;
+        let body = |name: &str| sim.find_body(name).ok_or_else(|| format!("no body named '{}'", name));
+        Ok(Self { primary: body(primary)?, secondary: body(secondary)?, point })
+    }
+
+    pub fn describe(&self, sim: &Simulation) -> String {
+        let name = |i: usize| sim.body_info.get(i).map_or_else(|| format!("Body {}", i), |b| b.name.clone());
+        format!("{}-{} {}", name(self.primary), name(self.secondary), self.point.name())
+    }
+
+    pub fn locate(&self, bodies: &[GpuCelestialBody]) -> Option<Placement> {
+        self.point.locate(bodies, self.primary, self.secondary)
+    }
+}
+
+/// Split "Sun:Jupiter:L4" into the two body names and the point
+pub fn split(text: &str) -> Result<(&str, &str, LagrangePoint), String> {
+    let parts: Vec<&str> = text.split(':').collect();
+    let [primary, secondary, point] = parts[..] else {
+        return Err(format!("expected 'primary:secondary:L1..L5', got '{}'", text));
+    };
+    if primary == secondary {
+        return Err(format!("a Lagrange point needs two different bodies, not {} twice", primary));
+    }
+    Ok((primary, secondary, LagrangePoint::parse(point)?))
+}
@@ -1,75 +1,442 @@
 mod camera;
+mod camera_path;
+mod capture;
+mod cli;
+mod cpu_physics;
+mod export;
+mod follow;
+mod frames;
 mod gpu;
+mod hud;
+mod lagrange;
+mod orbit;
+mod picking;
+mod post;
+mod presets;
+mod replay;
+mod scenario;
 mod simulation;
-mod solar_system;
+mod starfield;
+mod surface;
+mod text;
 mod types;
+mod ui;
+mod viewports;
 
+use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::Instant;
 
-use glam::Vec3;
 use winit::{
     dpi::PhysicalSize,
     event::{ElementState, Event, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
-    keyboard::{Key, NamedKey},
+    keyboard::{Key, KeyCode, ModifiersState, NamedKey, PhysicalKey},
     window::WindowBuilder,
 };
 
-use camera::{Camera, MouseButton as CamButton};
+use clap::Parser;
+
+use camera::{Camera, CameraMode, MouseButton as CamButton, Projection, FLY_BOOST};
+use camera_path::{CameraKey, CameraPath};
+use capture::Capture;
+use cli::Cli;
+use export::FrameSink;
 use gpu::GpuState;
-use simulation::{Simulation, SpawnMode};
-use solar_system::create_solar_system;
+use follow::{Follow, FollowMode};
+use frames::ReferenceFrame;
+use lagrange::LagrangePoint;
+use picking::Selection;
+use replay::EventLog;
+use scenario::Scenario;
+use simulation::{Backend, Simulation, SpawnMode};
 use types::*;
+use viewports::Viewports;
 
-fn upload_particles(gpu: &GpuState, sim: &Simulation) {
-    let idx = gpu.frame_index % 2;
-    gpu.queue.write_buffer(
-        &gpu.particle_buffers[idx],
-        0,
-        bytemuck::cast_slice(&sim.particles),
+/// Fixed wall-clock step for the CPU backend (scaled by the time scale)
+const CPU_STEP: f32 = 1.0 / 60.0;
+/// Pixels the cursor may move between press and release of a click
+const CLICK_SLOP: f32 = 4.0;
+/// Keys that steer the fly camera instead of doing their usual job
+const FLY_KEYS: [&str; 8] = ["w", "a", "s", "d", "q", "e", "z", "x"];
+
+/// Fly camera movement along its own axes, and roll, from the held keys
+fn fly_input(held: &HashSet<KeyCode>) -> (glam::Vec3, f32) {
+    let axis = |pos: KeyCode, neg: KeyCode| held.contains(&pos) as i32 as f32 - held.contains(&neg) as i32 as f32;
+    let movement = glam::Vec3::new(
+        axis(KeyCode::KeyD, KeyCode::KeyA),
+        axis(KeyCode::KeyE, KeyCode::KeyQ),
+        axis(KeyCode::KeyS, KeyCode::KeyW),
     );
-    gpu.queue.write_buffer(
-        &gpu.particle_buffers[(idx + 1) % 2],
-        0,
-        bytemuck::cast_slice(&sim.particles),
+    (movement, axis(KeyCode::KeyZ, KeyCode::KeyX))
+}
+
+/// Upload the particle slots spawned or cleared since the last upload
+fn upload_particles(gpu: &GpuState, sim: &mut Simulation) {
+    for range in sim.take_dirty_particles() {
+        let offset = (range.start * std::mem::size_of::<GpuParticle>()) as u64;
+        let data = bytemuck::cast_slice(&sim.particles[range]);
+        for buffer in &gpu.particle_buffers {
+            gpu.queue.write_buffer(buffer, offset, data);
+        }
+    }
+}
+
+/// Upload everything a freshly built simulation starts from
+fn upload_world(gpu: &mut GpuState, sim: &mut Simulation) {
+    // The simulation fills the trails with the bodies' starting positions
+    // repeated trail_length times; the orbit pass shifts them from there.
+    gpu.queue.write_buffer(&gpu.orbit_vertex_buffer, 0, bytemuck::cast_slice(&sim.trails));
+    gpu.orbit_vertex_count = sim.trails.len() as u32;
+    gpu.queue.write_buffer(&gpu.ring_buffer, 0, bytemuck::cast_slice(&sim.rings));
+    gpu.ring_count = sim.rings.len() as u32;
+
+    // Surface textures, by layer; bodies whose texture fails to load fall
+    // back to their procedural surface
+    let mut textures = Vec::new();
+    for (surface, info) in sim.surfaces.iter_mut().zip(&sim.body_info) {
+        surface.meridian[3] = -1.0;
+        let Some(path) = &info.texture else { continue };
+        match surface::load_texture(path) {
+            Ok(mips) => {
+                surface.meridian[3] = textures.len() as f32;
+                textures.push(mips);
+            }
+            Err(e) => log::warn!("{}: using a procedural surface ({})", info.name, e),
+        }
+    }
+    gpu.set_surface_textures(&textures);
+
+    // Bodies and particles go to both ping-pong buffers
+    gpu.discard_body_readback();
+    sim.take_dirty_particles();
+    for buffer in &gpu.body_buffers {
+        gpu.queue.write_buffer(buffer, 0, bytemuck::cast_slice(&sim.bodies));
+    }
+    for buffer in &gpu.particle_buffers {
+        gpu.queue.write_buffer(buffer, 0, bytemuck::cast_slice(&sim.particles));
+    }
+}
+
+/// CPU backend: the host copy is authoritative, mirror all of it for rendering
+fn upload_cpu_state(gpu: &GpuState, sim: &mut Simulation) {
+    sim.take_dirty_particles();
+    let idx = (gpu.frame_index + 1) % 2;
+    gpu.queue.write_buffer(&gpu.particle_buffers[idx], 0, bytemuck::cast_slice(&sim.particles));
+    gpu.queue.write_buffer(&gpu.body_buffers[idx], 0, bytemuck::cast_slice(&sim.bodies));
+    gpu.queue.write_buffer(&gpu.orbit_vertex_buffer, 0, bytemuck::cast_slice(&sim.trails));
+}
+
+/// GPU backend: pull the latest bodies and particles back into the host copy
+fn read_back_state(gpu: &GpuState, sim: &mut Simulation) {
+    let idx = gpu.frame_index % 2;
+    sim.bodies = gpu.read_buffer(&gpu.body_buffers[idx], sim.bodies.len());
+    sim.particles = gpu.read_buffer(&gpu.particle_buffers[idx], sim.particles.len());
+}
+
+/// GPU backend: pull just the latest bodies back, for decisions made from
+/// their positions
+fn read_back_bodies(gpu: &GpuState, sim: &mut Simulation) {
+    sim.bodies = gpu.read_buffer(&gpu.body_buffers[gpu.frame_index % 2], sim.bodies.len());
+}
+
+/// GPU backend: after the origin moved by `shift`, move the bodies,
+/// particles and trails on the GPU with it, and refresh the host bodies.
+/// Trails only move in the scenario's frame; others are body-relative.
+fn rebase_gpu_state(gpu: &mut GpuState, sim: &mut Simulation, shift: glam::Vec3) {
+    let idx = gpu.frame_index % 2;
+    let mut bodies = gpu.read_buffer(&gpu.body_buffers[idx], sim.bodies.len());
+    let mut particles = gpu.read_buffer(&gpu.particle_buffers[idx], sim.particles.len());
+    let mut trails = match sim.frame() {
+        ReferenceFrame::Scenario => gpu.read_buffer(&gpu.orbit_vertex_buffer, sim.trails.len()),
+        _ => Vec::new(),
+    };
+    simulation::shift_positions(&mut bodies, &mut particles, &mut trails, -shift);
+
+    // Both ping-pong buffers, so a paused frame draws the same state
+    gpu.discard_body_readback();
+    for buffer in &gpu.body_buffers {
+        gpu.queue.write_buffer(buffer, 0, bytemuck::cast_slice(&bodies));
+    }
+    for buffer in &gpu.particle_buffers {
+        gpu.queue.write_buffer(buffer, 0, bytemuck::cast_slice(&particles));
+    }
+    if !trails.is_empty() {
+        gpu.queue.write_buffer(&gpu.orbit_vertex_buffer, 0, bytemuck::cast_slice(&trails));
+    }
+    sim.bodies = bodies;
+}
+
+/// Switch to another reference frame. The camera moves so it still looks
+/// at the same point, and the trails restart in the new frame.
+fn change_frame(gpu: &mut GpuState, sim: &mut Simulation, camera: &mut Camera, frame: ReferenceFrame) -> glam::Vec3 {
+    if sim.backend == Backend::Gpu {
+        read_back_state(gpu, sim);
+    }
+    let target = sim.frame_at(0.0).point_in_sim(camera.target);
+    sim.set_frame(frame);
+    let delta = sim.frame_at(0.0).point_in_frame(target) - camera.target;
+    camera.translate(delta);
+    gpu.queue.write_buffer(&gpu.orbit_vertex_buffer, 0, bytemuck::cast_slice(&sim.trails));
+    gpu.orbit_vertex_count = sim.trails.len() as u32;
+    delta
+}
+
+/// Step the CPU backend without a window until `duration` years have passed
+fn run_headless(sim: &mut Simulation, duration: f32) {
+    let started = Instant::now();
+    sim.apply_due_events();
+    // Counted rather than compared against the accumulated f32 time, which
+    // would run one step past the end
+    let steps = (duration / (CPU_STEP * sim.time_scale)).round() as u64;
+    for _ in 0..steps {
+        if sim.paused {
+            log::warn!("Paused with no further events at t = {:.3} yr, stopping", sim.time);
+            break;
+        }
+        sim.step_cpu(CPU_STEP);
+        sim.apply_due_events();
+        sim.rebase_if_due();
+    }
+    log::info!(
+        "Simulated {:.3} yr in {} steps ({:.1}s), {} particles alive",
+        sim.time,
+        sim.step,
+        started.elapsed().as_secs_f32(),
+        sim.num_alive_particles
     );
 }
 
-fn print_controls() {
-    println!("\n╔══════════════════════════════════════════════════════════════╗");
-    println!("║              ⭐  STAR SYSTEM SIMULATOR  ⭐                  ║");
-    println!("║                GPU-Accelerated Physics                      ║");
-    println!("╠══════════════════════════════════════════════════════════════╣");
-    println!("║  CAMERA                                                     ║");
-    println!("║    Left Mouse + Drag    Orbit camera                        ║");
-    println!("║    Scroll Wheel         Zoom in/out                         ║");
-    println!("║                                                             ║");
-    println!("║  INTERACTION                                                ║");
-    println!("║    Right Click          Set swarm target (waypoint)          ║");
-    println!("║    Middle Click         Spawn 100 particles at cursor       ║");
-    println!("║    S                    Spawn 200 swarm particles           ║");
-    println!("║    O                    Spawn orbital swarm around Earth    ║");
-    println!("║    T                    Clear swarm target                   ║");
-    println!("║    C                    Clear all particles                  ║");
-    println!("║                                                             ║");
-    println!("║  MODES                                                      ║");
-    println!("║    1                    Swarm mode (boids + gravity)        ║");
-    println!("║    2                    Free mode  (gravity only)           ║");
-    println!("║    3                    Burst mode (scatter)                ║");
-    println!("║                                                             ║");
-    println!("║  TUNING                                                     ║");
-    println!("║    Q / W / E            Increase sep / align / cohesion     ║");
-    println!("║    G                    Increase gravity influence on swarm ║");
-    println!("║    +/-                  Speed up / slow down time           ║");
-    println!("║    Space                Pause / Resume                      ║");
-    println!("║                                                             ║");
-    println!("║  H = Toggle help  |  Esc = Quit                            ║");
-    println!("╚══════════════════════════════════════════════════════════════╝\n");
+/// Render frames offscreen without a window, advancing the simulation by a
+/// fixed simulated time per frame however long each takes to compute
+fn run_export(cli: &Cli, sim: &mut Simulation, scenario: &Scenario, duration: f32) -> Result<(), String> {
+    let size = (cli.width, cli.height);
+    let mut camera_path = match cli.camera_path.as_deref() {
+        Some(path) => Some(CameraPath::load(path)?),
+        None => scenario.camera_path.clone(),
+    };
+    let mut sink = match (&cli.export, &cli.export_pipe) {
+        (Some(dir), _) => FrameSink::files(dir.clone())?,
+        (None, Some(command)) => FrameSink::pipe(command, size, cli.fps)?,
+        (None, None) => unreachable!("checked by Cli::exporting"),
+    };
+
+    let mut gpu = pollster::block_on(GpuState::new(
+        None,
+        size,
+        cli.capacity(),
+        cli.present_mode.into(),
+        cli.render_settings(),
+        cli.msaa,
+    ));
+    let mut capture = Capture::new(&gpu, size)?;
+    let mut camera = Camera::new();
+    camera.resize(size.0, size.1);
+    scenario.camera.apply(&mut camera);
+
+    upload_world(&mut gpu, sim);
+    sim.apply_due_events();
+    upload_particles(&gpu, sim);
+    if sim.backend == Backend::Cpu {
+        upload_cpu_state(&gpu, sim);
+    }
+
+    let frame_time = cli.frame_time.unwrap_or(sim.time_scale / cli.fps);
+    let frames = (duration / frame_time).ceil() as u64;
+    log::info!("Exporting {} frames of {:.3e} yr at {}x{}", frames, frame_time, size.0, size.1);
+    let started = Instant::now();
+    let mut last_report = started;
+    for frame in 0..frames {
+        if let Some(path) = &camera_path {
+            path.apply(&mut camera, sim.time);
+        }
+        let surfaces: Vec<GpuSurface> = sim.surfaces.iter().map(|s| s.spun(sim.time)).collect();
+        gpu.queue.write_buffer(&gpu.surface_buffer, 0, bytemuck::cast_slice(&surfaces));
+
+        // The GPU backend's newest state is in the buffer its last step
+        // wrote; the CPU backend uploads into the render buffer
+        let buffer = match sim.backend {
+            Backend::Gpu => gpu.frame_index % 2,
+            Backend::Cpu => (gpu.frame_index + 1) % 2,
+        };
+        let image = capture.render(&gpu, &camera, sim, 1.0, buffer);
+        let mut text = capture::metadata(sim, &scenario.name);
+        text.push(("Frame", frame.to_string()));
+        sink.write(frame, &image, &text)?;
+
+        if last_report.elapsed().as_secs_f32() >= 2.0 {
+            last_report = Instant::now();
+            log::info!("Frame {}/{}, t = {:.3} yr", frame + 1, frames, sim.time);
+        }
+        if sim.paused {
+            log::warn!("Paused with no further events at t = {:.3} yr, stopping", sim.time);
+            break;
+        }
+
+        // Steps no longer than the window's CPU step, so the physics is as
+        // accurate as it is interactively
+        let substeps = (frame_time / (CPU_STEP * sim.time_scale)).ceil().max(1.0) as u32;
+        let dt = frame_time / substeps as f32 / sim.time_scale;
+        for _ in 0..substeps {
+            match sim.backend {
+                Backend::Gpu => {
+                    // A Lagrange target is placed from the bodies as they are now
+                    if sim.target_site().is_some() {
+                        read_back_bodies(&gpu, sim);
+                    }
+                    sim.update_params(dt);
+                    upload_particles(&gpu, sim);
+                    gpu.queue.write_buffer(&gpu.sim_params_buffer, 0, bytemuck::bytes_of(&sim.params));
+                    let mut encoder = gpu
+                        .device
+                        .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("Export Step") });
+                    gpu.step_physics(&mut encoder, sim.bodies.len());
+                    gpu.queue.submit(std::iter::once(encoder.finish()));
+                    gpu.frame_index += 1;
+                }
+                Backend::Cpu => sim.step_cpu(dt),
+            }
+            // Replayed spawns around bodies and Lagrange points go where
+            // the bodies are now
+            if sim.backend == Backend::Gpu && sim.events_due() {
+                read_back_bodies(&gpu, sim);
+            }
+            sim.apply_due_events();
+        }
+        if sim.backend == Backend::Gpu && sim.floating_origin.is_some() {
+            read_back_bodies(&gpu, sim);
+        }
+        sim.rebase_if_due();
+        if let Some(shift) = sim.take_rebase() {
+            if sim.backend == Backend::Gpu {
+                rebase_gpu_state(&mut gpu, sim, shift);
+            }
+            if sim.frame() == ReferenceFrame::Scenario {
+                camera.translate(-shift);
+                if let Some(path) = &mut camera_path {
+                    path.translate(-shift);
+                }
+            }
+        }
+        if sim.backend == Backend::Cpu {
+            upload_cpu_state(&gpu, sim);
+        }
+    }
+    sink.finish()?;
+    log::info!("Exported {} frames in {:.1}s", frames, started.elapsed().as_secs_f32());
+
+    if cli.snapshot.is_some() && sim.backend == Backend::Gpu {
+        read_back_state(&gpu, sim);
+    }
+    Ok(())
+}
+
+/// Write the event log and state snapshot requested on the command line
+fn write_outputs(cli: &Cli, sim: &Simulation) {
+    if let Some(path) = &cli.record {
+        match sim.log.save(path) {
+            Ok(()) => log::info!("Saved {} events to {}", sim.log.events.len(), path.display()),
+            Err(e) => log::error!("Failed to save event log: {}", e),
+        }
+    }
+    if let Some(path) = &cli.snapshot {
+        match std::fs::write(path, sim.snapshot_csv()) {
+            Ok(()) => log::info!("Saved snapshot to {}", path.display()),
+            Err(e) => log::error!("Failed to save snapshot: {}", e),
+        }
+    }
 }
 
 fn main() {
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+    let cli = Cli::parse();
+    let mut logger = env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info"));
+    if let Some(level) = cli.log_level {
+        logger.filter_level(level);
+    }
+    logger.init();
+
+    let replay_log = cli.replay.as_ref().map(|path| {
+        EventLog::load(path).unwrap_or_else(|e| {
+            log::error!("Failed to load replay: {}", e);
+            std::process::exit(1);
+        })
+    });
+
+    // A replay reuses the scenario it was recorded with unless overridden
+    let scenario_name = cli
+        .scenario
+        .clone()
+        .or_else(|| replay_log.as_ref().and_then(|log| log.scenario.clone()))
+        .unwrap_or_else(|| presets::DEFAULT_PRESET.to_string());
+    let scenario = presets::resolve(&scenario_name).unwrap_or_else(|e| {
+        log::error!("Failed to load scenario: {}", e);
+        std::process::exit(1);
+    });
+    log::info!("Scenario: {}", scenario.name);
+
+    let capacity = cli.capacity();
+    if let Err(e) = scenario.check_capacity(&capacity) {
+        log::error!("{}", e);
+        std::process::exit(1);
+    }
+
+    let seed = match &replay_log {
+        Some(log) => log.seed,
+        None => cli.seed.or(scenario.seed).unwrap_or_else(rand::random),
+    };
+    log::info!("Seed: {}", seed);
+
+    // Initialize bodies, swarms and parameters from the scenario
+    let mut sim = scenario.build(seed, Some(scenario_name.clone()), &capacity);
+    sim.backend = if cli.headless { Backend::Cpu } else { cli.backend };
+    log::info!("Physics backend: {:?}", sim.backend);
+    if let Some(scale) = cli.time_scale {
+        sim.set_time_scale(scale);
+    }
+    if let Some(integrator) = cli.integrator {
+        sim.set_integrator(integrator);
+    }
+    log::info!("Integrator: {:?}", sim.integrator());
+    if let Some(precision) = cli.precision {
+        sim.set_precision(precision);
+    }
+    log::info!("Precision: {:?}", sim.precision());
+    if let Some(name) = &cli.floating_origin {
+        let Some(index) = sim.find_body(name) else {
+            log::error!("No body named '{}' to keep the origin on", name);
+            std::process::exit(1);
+        };
+        sim.floating_origin = Some(index);
+    }
+    if let Some(text) = &cli.frame {
+        match ReferenceFrame::parse(text, &sim) {
+            Ok(frame) => sim.set_frame(frame),
+            Err(e) => {
+                log::error!("Bad --frame: {}", e);
+                std::process::exit(1);
+            }
+        }
+        log::info!("Reference frame: {}", sim.frame().describe(&sim));
+    }
+    if let Some(log) = replay_log {
+        log::info!("Replaying {} events", log.events.len());
+        sim.load_replay(log);
+    }
+
+    if cli.exporting() {
+        if let Err(e) = run_export(&cli, &mut sim, &scenario, cli.duration.expect("required by --export")) {
+            log::error!("Export failed: {}", e);
+            std::process::exit(1);
+        }
+        write_outputs(&cli, &sim);
+        return;
+    }
+
+    if cli.headless {
+        run_headless(&mut sim, cli.duration.expect("required by --headless"));
+        write_outputs(&cli, &sim);
+        return;
+    }
 
     let event_loop = EventLoop::new().unwrap();
     event_loop.set_control_flow(ControlFlow::Poll);
@@ -77,446 +444,697 @@ fn main() {
     let window = Arc::new(
         WindowBuilder::new()
             .with_title("⭐ Star System Simulator — GPU Accelerated")
-            .with_inner_size(PhysicalSize::new(1600u32, 900u32))
+            .with_inner_size(PhysicalSize::new(cli.width, cli.height))
             .build(&event_loop)
             .unwrap(),
     );
 
-    let mut gpu = pollster::block_on(GpuState::new(window.clone()));
-    let mut camera = Camera::new();
     let size = window.inner_size();
+    let mut gpu = pollster::block_on(GpuState::new(
+        Some(window.clone()),
+        (size.width, size.height),
+        capacity,
+        cli.present_mode.into(),
+        cli.render_settings(),
+        cli.msaa,
+    ));
+    let mut camera = Camera::new();
     camera.resize(size.width, size.height);
+    scenario.camera.apply(&mut camera);
 
-    // Initialize solar system
-    let bodies = create_solar_system();
-    let mut sim = Simulation::new(bodies.clone());
-
-    // Initialize Dynamic Orbit Trails
-    // Instead of pre-calculating the orbit lines, we fill the buffer with
-    // the planets' starting positions repeated TRAIL_LENGTH times.
-    let trail_length = 512;
-    let mut grid_vertices = Vec::with_capacity(bodies.len() * trail_length);
-
-    for body in &bodies {
-        let pos = [body.position[0], body.position[1], body.position[2]];
-        let color = body.color;
-
-        // Fill the entire trail with the starting position
-        for _ in 0..trail_length {
-            grid_vertices.push(GridVertex {
-                position: pos,
-                _pad: 0.0,
-                color,
-            });
-        }
-    }
+    upload_world(&mut gpu, &mut sim);
+    let mut panel = ui::Panel::new(&window, &gpu);
+    let mut scenario = scenario;
+    let mut preset_key = presets::PRESETS
+        .iter()
+        .map(|(k, _)| *k)
+        .find(|k| *k == scenario_name);
 
-    gpu.queue.write_buffer(
-        &gpu.orbit_vertex_buffer,
-        0,
-        bytemuck::cast_slice(&grid_vertices),
-    );
-    gpu.orbit_vertex_count = grid_vertices.len() as u32;
-
-    // Upload initial celestial bodies to both ping-pong buffers
-    gpu.queue.write_buffer(&gpu.body_buffers[0], 0, bytemuck::cast_slice(&sim.bodies));
-    gpu.queue.write_buffer(&gpu.body_buffers[1], 0, bytemuck::cast_slice(&sim.bodies));
-
-    // Spawn initial swarm near the Earth-like planet
-    sim.spawn_swarm(Vec3::new(1.0, 0.0, 0.2), 500);
-    upload_particles(&gpu, &sim);
+    sim.apply_due_events();
+    upload_particles(&gpu, &mut sim);
 
     let mut last_frame = Instant::now();
+    let mut step_accumulator = 0.0_f32;
     let mut mouse_pos: (f32, f32) = (0.0, 0.0);
     let mut frame_count: u64 = 0;
     let mut fps_timer = Instant::now();
+    let mut fps = 0.0_f32;
+    let mut overlay = hud::Overlay::default();
+    let mut modifiers = ModifiersState::default();
+    let mut screenshot: Option<(u32, u32)> = None;
+    let mut selection: Option<Selection> = None;
+    // Where the left button went down, to tell clicks from drags
+    let mut press_pos: Option<(f32, f32)> = None;
+    // Ping-pong buffer the last frame was drawn from, for picking
+    let mut shown_buffer = 1;
+    let mut follow = Follow::new();
+    // Sim time of the host copy of the bodies, behind the drawn state on
+    // the GPU backend
+    let mut bodies_time = sim.time;
+    // Physical keys held down, for flying
+    let mut held_keys: HashSet<KeyCode> = HashSet::new();
+    let mut views = Viewports::new();
+    views.reset(&camera);
+    panel.screenshot_size = cli.screenshot_size;
+    panel.camera_path = scenario.camera_path.clone().unwrap_or_default();
 
-    print_controls();
+    hud::print_controls();
 
     event_loop
         .run(move |event, elwt| {
             match event {
-                Event::WindowEvent { event, .. } => match event {
-                    WindowEvent::CloseRequested => elwt.exit(),
-
-                    WindowEvent::Resized(size) => {
-                        gpu.resize(size.width, size.height);
-                        camera.resize(size.width, size.height);
+                Event::WindowEvent { event, .. } => {
+                    let ui_consumed = panel.on_window_event(&window, &event);
+                    if let WindowEvent::KeyboardInput { event: KeyEvent { physical_key: PhysicalKey::Code(code), state, .. }, .. } = &event {
+                        if *state == ElementState::Pressed && !ui_consumed && !panel.wants_keyboard() {
+                            held_keys.insert(*code);
+                        } else {
+                            held_keys.remove(code);
+                        }
                     }
+                    match event {
+                        WindowEvent::CloseRequested => elwt.exit(),
 
-                    WindowEvent::MouseInput {
-                        state: btn_state,
-                        button,
-                        ..
-                    } => {
-                        let pressed = btn_state == ElementState::Pressed;
-                        match button {
-                            MouseButton::Left => {
-                                camera.handle_mouse_button(CamButton::Left, pressed);
-                            }
-                            MouseButton::Right => {
-                                if pressed {
-                                    let (origin, dir) = camera.screen_to_world_ray(
-                                        mouse_pos.0,
-                                        mouse_pos.1,
-                                        gpu.config.width as f32,
-                                        gpu.config.height as f32,
-                                    );
-                                    if let Some(world_pos) =
-                                        camera.ray_plane_intersection(origin, dir, 0.0)
-                                    {
-                                        sim.set_target(world_pos);
-                                        log::info!(
-                                            "Target set at ({:.1}, {:.1}, {:.1})",
-                                            world_pos.x,
-                                            world_pos.y,
-                                            world_pos.z
+                        WindowEvent::Focused(false) => held_keys.clear(),
+
+                        WindowEvent::Resized(size) => {
+                            gpu.resize(size.width, size.height);
+                            camera.resize(size.width, size.height);
+                        }
+
+                        WindowEvent::MouseInput {
+                            state: btn_state,
+                            button,
+                            ..
+                        } if !ui_consumed => {
+                            let pressed = btn_state == ElementState::Pressed;
+                            let viewport = views.viewport_at((gpu.config.width, gpu.config.height), mouse_pos);
+                            match button {
+                                MouseButton::Left => {
+                                    // A click without dragging selects what is under the cursor
+                                    if pressed {
+                                        press_pos = Some(mouse_pos);
+                                    } else if press_pos.take().is_some_and(|(x, y)| {
+                                        (x - mouse_pos.0).hypot(y - mouse_pos.1) < CLICK_SLOP
+                                    }) {
+                                        selection = picking::pick(
+                                            &gpu,
+                                            views.camera(&camera, &viewport),
+                                            &viewport,
+                                            &mut sim,
+                                            mouse_pos,
+                                            window.scale_factor() as f32,
+                                            shown_buffer,
                                         );
+                                        if let Some(info) = selection.and_then(|s| picking::inspect(&sim, s)) {
+                                            log::info!("Selected {}", info.name);
+                                        }
                                     }
+                                    views.mouse_button(&mut camera, &viewport, CamButton::Left, pressed);
                                 }
-                                camera.handle_mouse_button(CamButton::Right, pressed);
-                            }
-                            MouseButton::Middle => {
-                                if pressed {
-                                    let (origin, dir) = camera.screen_to_world_ray(
-                                        mouse_pos.0,
-                                        mouse_pos.1,
-                                        gpu.config.width as f32,
-                                        gpu.config.height as f32,
-                                    );
-                                    if let Some(world_pos) =
-                                        camera.ray_plane_intersection(origin, dir, 0.0)
-                                    {
-                                        sim.spawn_burst(world_pos, 100);
-                                        upload_particles(&gpu, &sim);
-                                        log::info!("Spawned 100 particles");
+                                MouseButton::Right => {
+                                    if pressed {
+                                        if let Some(world_pos) =
+                                            viewport.on_ecliptic(views.camera(&camera, &viewport), mouse_pos)
+                                        {
+                                            let world_pos = sim.frame_at(sim.time - bodies_time).point_in_sim(world_pos);
+                                            sim.set_target(world_pos);
+                                            log::info!(
+                                                "Target set at ({:.1}, {:.1}, {:.1})",
+                                                world_pos.x,
+                                                world_pos.y,
+                                                world_pos.z
+                                            );
+                                        }
                                     }
+                                    views.mouse_button(&mut camera, &viewport, CamButton::Right, pressed);
                                 }
-                                camera.handle_mouse_button(CamButton::Middle, pressed);
+                                MouseButton::Middle => {
+                                    if pressed {
+                                        if let Some(world_pos) =
+                                            viewport.on_ecliptic(views.camera(&camera, &viewport), mouse_pos)
+                                        {
+                                            let world_pos = sim.frame_at(sim.time - bodies_time).point_in_sim(world_pos);
+                                            sim.spawn_burst(world_pos, 100);
+                                            upload_particles(&gpu, &mut sim);
+                                            log::info!("Spawned 100 particles");
+                                        }
+                                    }
+                                    views.mouse_button(&mut camera, &viewport, CamButton::Middle, pressed);
+                                }
+                                _ => {}
                             }
-                            _ => {}
                         }
-                    }
 
-                    WindowEvent::CursorMoved { position, .. } => {
-                        mouse_pos = (position.x as f32, position.y as f32);
-                        camera.handle_mouse_move(position.x as f32, position.y as f32);
-                    }
+                        WindowEvent::ModifiersChanged(state) => modifiers = state.state(),
 
-                    WindowEvent::MouseWheel { delta, .. } => {
-                        let scroll = match delta {
-                            MouseScrollDelta::LineDelta(_, y) => y,
-                            MouseScrollDelta::PixelDelta(pos) => pos.y as f32 * 0.1,
-                        };
-                        camera.handle_scroll(scroll);
-                    }
+                        WindowEvent::CursorMoved { position, .. } => {
+                            mouse_pos = (position.x as f32, position.y as f32);
+                            views.mouse_move(&mut camera, position.x as f32, position.y as f32);
+                        }
+
+                        WindowEvent::MouseWheel { delta, .. } if !ui_consumed => {
+                            let scroll = match delta {
+                                MouseScrollDelta::LineDelta(_, y) => y,
+                                MouseScrollDelta::PixelDelta(pos) => pos.y as f32 * 0.1,
+                            };
+                            let viewport = views.viewport_at((gpu.config.width, gpu.config.height), mouse_pos);
+                            views.camera_mut(&mut camera, &viewport).handle_scroll(scroll);
+                        }
 
-                    WindowEvent::KeyboardInput {
-                        event:
-                        KeyEvent {
-                            logical_key,
-                            state: key_state,
+                        WindowEvent::KeyboardInput {
+                            event:
+                            KeyEvent {
+                                logical_key,
+                                state: ElementState::Pressed,
+                                ..
+                            },
                             ..
-                        },
-                        ..
-                    } => {
-                        if key_state == ElementState::Pressed {
-                            match logical_key.as_ref() {
-                                // Spawn mode selection
-                                Key::Character("1") => {
-                                    sim.spawn_mode = SpawnMode::Swarm;
-                                    log::info!("Mode: Swarm");
-                                }
-                                Key::Character("2") => {
-                                    sim.spawn_mode = SpawnMode::Free;
-                                    log::info!("Mode: Free");
-                                }
-                                Key::Character("3") => {
-                                    sim.spawn_mode = SpawnMode::Burst;
-                                    log::info!("Mode: Burst");
+                        } if !ui_consumed && !panel.wants_keyboard() => match logical_key.as_ref() {
+                            // Flying, handled each frame from the held keys
+                            Key::Character(key) if camera.mode == CameraMode::Fly && FLY_KEYS.contains(&key) => {}
+
+                            // Orbit / fly camera
+                            Key::Character("v") => {
+                                let mode = match camera.mode {
+                                    CameraMode::Orbit => CameraMode::Fly,
+                                    CameraMode::Fly => CameraMode::Orbit,
+                                };
+                                camera.set_mode(mode);
+                                log::info!("Camera: {:?}", mode);
+                            }
+                            Key::Character("u") => {
+                                camera.projection = match camera.projection {
+                                    Projection::Perspective => Projection::Orthographic,
+                                    Projection::Orthographic => Projection::Perspective,
+                                };
+                                log::info!("Projection: {:?}", camera.projection);
+                            }
+                            Key::Character("m") => {
+                                views.split = !views.split;
+                                log::info!("Top and side views: {}", if views.split { "on" } else { "off" });
+                            }
+
+                            // Spawn mode selection
+                            Key::Character("1") => {
+                                sim.set_spawn_mode(SpawnMode::Swarm);
+                                log::info!("Mode: Swarm");
+                            }
+                            Key::Character("2") => {
+                                sim.set_spawn_mode(SpawnMode::Free);
+                                log::info!("Mode: Free");
+                            }
+                            Key::Character("3") => {
+                                sim.set_spawn_mode(SpawnMode::Burst);
+                                log::info!("Mode: Burst");
+                            }
+
+                            // Spawn swarm at cursor
+                            Key::Character("s") => {
+                                let viewport = views.viewport_at((gpu.config.width, gpu.config.height), mouse_pos);
+                                if let Some(pos) = viewport.on_ecliptic(views.camera(&camera, &viewport), mouse_pos) {
+                                    let pos = sim.frame_at(sim.time - bodies_time).point_in_sim(pos);
+                                    sim.spawn_swarm(pos, 200);
+                                    upload_particles(&gpu, &mut sim);
+                                    log::info!("Spawned 200 swarm particles");
                                 }
+                            }
 
-                                // Spawn swarm at cursor
-                                Key::Character("s") => {
-                                    let (origin, dir) = camera.screen_to_world_ray(
-                                        mouse_pos.0,
-                                        mouse_pos.1,
-                                        gpu.config.width as f32,
-                                        gpu.config.height as f32,
-                                    );
-                                    if let Some(pos) =
-                                        camera.ray_plane_intersection(origin, dir, 0.0)
-                                    {
-                                        sim.spawn_swarm(pos, 200);
-                                        upload_particles(&gpu, &sim);
-                                        log::info!("Spawned 200 swarm particles");
+                            // Spawn orbital swarm around Earth (or the first planet)
+                            Key::Character("o") => {
+                                let body = sim.find_body("Earth").unwrap_or(1);
+                                sim.spawn_orbital_swarm(body, 300, 0.1);
+                                upload_particles(&gpu, &mut sim);
+                                log::info!("Spawned orbital swarm");
+                            }
+
+                            // Cycle through the built-in presets
+                            Key::Character("p") => {
+                                let key = presets::next_key(preset_key);
+                                match presets::preset(key)
+                                    .and_then(|s| s.check_capacity(&gpu.capacity).map(|_| s))
+                                {
+                                    Ok(next) => {
+                                        scenario = next;
+                                        let seed = scenario.seed.unwrap_or(sim.seed);
+                                        let (backend, integrator, precision) = (sim.backend, sim.integrator(), sim.precision());
+                                        sim = scenario.build(seed, Some(key.to_string()), &gpu.capacity);
+                                        sim.backend = backend;
+                                        sim.set_integrator(integrator);
+                                        sim.set_precision(precision);
+                                        step_accumulator = 0.0;
+                                        scenario.camera.apply(&mut camera);
+                                        views.reset(&camera);
+                                        upload_world(&mut gpu, &mut sim);
+                                        selection = None;
+                                        follow.mode = FollowMode::Off;
+                                        panel.camera_path = scenario.camera_path.clone().unwrap_or_default();
+                                        panel.path_playing = false;
+                                        panel.lagrange_pair = None;
+                                        preset_key = Some(key);
+                                        log::info!("Scenario: {}", scenario.name);
                                     }
+                                    Err(e) => log::error!("{}", e),
                                 }
+                            }
 
-                                // Spawn orbital swarm around Earth
-                                Key::Character("o") => {
-                                    sim.spawn_orbital_swarm(3, 300, 0.1);
-                                    upload_particles(&gpu, &sim);
-                                    log::info!("Spawned orbital swarm around Earth");
-                                }
+                            // Clear target / particles
+                            Key::Character("t") => {
+                                sim.clear_target();
+                                log::info!("Target cleared");
+                            }
+                            Key::Character("c") => {
+                                sim.clear_particles();
+                                upload_particles(&gpu, &mut sim);
+                                log::info!("Particles cleared");
+                            }
 
-                                // Clear target / particles
-                                Key::Character("t") => {
-                                    sim.clear_target();
-                                    log::info!("Target cleared");
-                                }
-                                Key::Character("c") => {
-                                    sim.clear_particles();
-                                    upload_particles(&gpu, &sim);
-                                    log::info!("Particles cleared");
-                                }
+                            // Pause
+                            Key::Named(NamedKey::Space) => {
+                                sim.set_paused(!sim.paused);
+                                log::info!(
+                                    "{}",
+                                    if sim.paused { "⏸ Paused" } else { "▶ Resumed" }
+                                );
+                            }
 
-                                // Pause
-                                Key::Named(NamedKey::Space) => {
-                                    sim.paused = !sim.paused;
-                                    log::info!(
-                                        "{}",
-                                        if sim.paused { "⏸ Paused" } else { "▶ Resumed" }
-                                    );
-                                }
+                            // Time scale
+                            Key::Character("+") | Key::Character("=") => {
+                                sim.set_time_scale((sim.time_scale * 1.5).min(1000.0));
+                                log::info!("Time scale: {:.4}x", sim.time_scale);
+                            }
+                            Key::Character("-") => {
+                                sim.set_time_scale((sim.time_scale / 1.5).max(1e-4));
+                                log::info!("Time scale: {:.4}x", sim.time_scale);
+                            }
 
-                                // Time scale
-                                Key::Character("+") | Key::Character("=") => {
-                                    sim.time_scale = (sim.time_scale * 1.5).min(10.0);
-                                    log::info!("Time scale: {:.2}x", sim.time_scale);
-                                }
-                                Key::Character("-") => {
-                                    sim.time_scale = (sim.time_scale / 1.5).max(0.1);
-                                    log::info!("Time scale: {:.2}x", sim.time_scale);
-                                }
+                            // Physical vs exaggerated body sizes
+                            Key::Character("r") => {
+                                let settings = &mut gpu.post.settings;
+                                settings.true_scale = !settings.true_scale;
+                                log::info!(
+                                    "Body radii: {}",
+                                    if settings.true_scale { "true scale" } else { "visual" }
+                                );
+                            }
+
+                            // Camera follow modes
+                            Key::Character(key @ ("f" | "k")) => {
+                                let mode = match selection {
+                                    Some(s) if key == "f" => FollowMode::Centre(s),
+                                    Some(s) => FollowMode::OrbitLock(s),
+                                    None => FollowMode::Off,
+                                };
+                                // The same key again lets go
+                                let mode = if mode == follow.mode { FollowMode::Off } else { mode };
+                                log::info!("Camera: {}", mode.describe(&sim));
+                                follow.set(mode, &camera, &sim, (sim.time - bodies_time).max(0.0));
+                            }
+                            Key::Character("n") => {
+                                panel.camera_path.insert(CameraKey::from_camera(&camera, sim.time));
+                                log::info!("Camera key at t = {:.4} yr ({} keys)", sim.time, panel.camera_path.keys.len());
+                            }
+                            Key::Character("b") => {
+                                let bodies = (0..sim.bodies.len()).map(Selection::Body).collect();
+                                follow.set(FollowMode::Frame(bodies), &camera, &sim, (sim.time - bodies_time).max(0.0));
+                                log::info!("Camera: {}", follow.mode.describe(&sim));
+                            }
 
-                                // Help
-                                Key::Character("h") => print_controls(),
+                            // Overlays and parameter panel
+                            Key::Character("h") => overlay.help = !overlay.help,
+                            Key::Character("l") => overlay.labels = !overlay.labels,
+                            Key::Named(NamedKey::F1) => panel.visible = !panel.visible,
+                            Key::Named(NamedKey::F2) => overlay.hud = !overlay.hud,
 
-                                // Swarm tuning
-                                Key::Character("q") => {
-                                    sim.params.separation_weight += 0.2;
-                                    log::info!("Separation: {:.1}", sim.params.separation_weight);
+                            // Screenshot at the window size, or the high-resolution size with Shift
+                            Key::Named(NamedKey::F12) => {
+                                screenshot = Some(if modifiers.shift_key() {
+                                    panel.screenshot_size
+                                } else {
+                                    (gpu.config.width, gpu.config.height)
+                                });
+                            }
+
+                            // Swarm tuning
+                            Key::Character("q") => {
+                                sim.set_param("separation_weight", sim.params.separation_weight + 0.2);
+                                log::info!("Separation: {:.1}", sim.params.separation_weight);
+                            }
+                            Key::Character("w") => {
+                                sim.set_param("alignment_weight", sim.params.alignment_weight + 0.2);
+                                log::info!("Alignment: {:.1}", sim.params.alignment_weight);
+                            }
+                            Key::Character("e") => {
+                                sim.set_param("cohesion_weight", sim.params.cohesion_weight + 0.2);
+                                log::info!("Cohesion: {:.1}", sim.params.cohesion_weight);
+                            }
+                            Key::Character("g") => {
+                                sim.set_param(
+                                    "swarm_gravity_weight",
+                                    (sim.params.swarm_gravity_weight + 0.1).min(2.0),
+                                );
+                                log::info!(
+                                    "Gravity weight: {:.1}",
+                                    sim.params.swarm_gravity_weight
+                                );
+                            }
+
+                            Key::Named(NamedKey::Escape) => elwt.exit(),
+                            _ => {}
+                        },
+
+                        // ========================================================
+                        // RENDER FRAME
+                        // ========================================================
+                        WindowEvent::RedrawRequested => {
+                            let now = Instant::now();
+                            let dt = now.duration_since(last_frame).as_secs_f32().min(0.05);
+                            last_frame = now;
+
+                            // FPS counter
+                            frame_count += 1;
+                            let fps_elapsed = now.duration_since(fps_timer).as_secs_f32();
+                            if fps_elapsed >= 1.0 {
+                                fps = frame_count as f32 / fps_elapsed;
+                                frame_count = 0;
+                                fps_timer = now;
+                                window.set_title(&format!(
+                                    "⭐ Star System Sim | {:.0} FPS | {} particles | {:?} | seed {}",
+                                    fps, sim.num_alive_particles, sim.spawn_mode, sim.seed
+                                ));
+                            }
+
+                            // Update simulation state. The GPU backend takes one
+                            // variable step per frame; the CPU backend takes fixed
+                            // steps so a replay lands on exactly the same states.
+                            sim.apply_due_events();
+                            let gpu_step = sim.backend == Backend::Gpu && !sim.paused;
+                            match sim.backend {
+                                Backend::Gpu => {
+                                    // Keep the host bodies current for labels and lookups
+                                    if let Some(state) = gpu.poll_body_readback() {
+                                        bodies_time = state.time;
+                                        sim.bodies = state.bodies;
+                                        if let Some((index, particle)) = state.particle {
+                                            sim.particles[index] = particle;
+                                        }
+                                    }
+                                    if !sim.paused {
+                                        sim.update_params(dt);
+                                    }
+                                    upload_particles(&gpu, &mut sim);
                                 }
-                                Key::Character("w") => {
-                                    sim.params.alignment_weight += 0.2;
-                                    log::info!("Alignment: {:.1}", sim.params.alignment_weight);
+                                Backend::Cpu => {
+                                    if !sim.paused {
+                                        step_accumulator += dt;
+                                        while step_accumulator >= CPU_STEP {
+                                            step_accumulator -= CPU_STEP;
+                                            sim.step_cpu(CPU_STEP);
+                                            sim.apply_due_events();
+                                        }
+                                    }
+                                    upload_cpu_state(&gpu, &mut sim);
+                                    bodies_time = sim.time;
                                 }
-                                Key::Character("e") => {
-                                    sim.params.cohesion_weight += 0.2;
-                                    log::info!("Cohesion: {:.1}", sim.params.cohesion_weight);
+                            }
+
+                            // Floating origin: everything holding positions moves with it
+                            sim.rebase_if_due();
+                            if let Some(shift) = sim.take_rebase() {
+                                match sim.backend {
+                                    Backend::Gpu => {
+                                        rebase_gpu_state(&mut gpu, &mut sim, shift);
+                                        bodies_time = sim.time;
+                                    }
+                                    Backend::Cpu => upload_cpu_state(&gpu, &mut sim),
                                 }
-                                Key::Character("g") => {
-                                    sim.params.swarm_gravity_weight =
-                                        (sim.params.swarm_gravity_weight + 0.1).min(2.0);
-                                    log::info!(
-                                        "Gravity weight: {:.1}",
-                                        sim.params.swarm_gravity_weight
-                                    );
+                                // Other frames are body-relative and don't move
+                                if sim.frame() == ReferenceFrame::Scenario {
+                                    camera.translate(-shift);
+                                    follow.translate(-shift);
+                                    panel.camera_path.translate(-shift);
                                 }
-
-                                Key::Named(NamedKey::Escape) => elwt.exit(),
-                                _ => {}
+                                log::debug!("Origin moved to {:?}", sim.origin);
+                            }
+                            let lead = (sim.time - bodies_time).max(0.0);
+                            if panel.path_playing {
+                                panel.camera_path.steer(&mut camera, sim.time);
+                            }
+                            follow.update(&mut camera, &sim, lead, dt);
+                            if camera.mode == CameraMode::Fly {
+                                // Pace set by the distance to the nearest body's surface
+                                let eye = sim.frame_at(lead).point_in_sim(camera.eye_position());
+                                let true_scale = gpu.post.settings.true_scale;
+                                let reference = sim
+                                    .bodies
+                                    .iter()
+                                    .map(|b| {
+                                        let radius = if true_scale { b.data[3] } else { b.velocity[3] };
+                                        (glam::Vec3::from_slice(&b.position) - eye).length() - radius
+                                    })
+                                    .fold(f32::INFINITY, f32::min)
+                                    .clamp(1e-5, camera.max_distance);
+                                let boost = if modifiers.shift_key() { FLY_BOOST } else { 1.0 };
+                                let (movement, roll) = fly_input(&held_keys);
+                                camera.fly(movement, roll, reference * boost, dt);
+                            }
+                            camera.update(dt);
+                            views.update(&mut camera, (gpu.config.width, gpu.config.height), dt);
+                            if cli.duration.is_some_and(|d| sim.time >= d) {
+                                elwt.exit();
                             }
-                        }
-                    }
 
-                    // ========================================================
-                    // RENDER FRAME
-                    // ========================================================
-                    WindowEvent::RedrawRequested => {
-                        let now = Instant::now();
-                        let dt = now.duration_since(last_frame).as_secs_f32().min(0.05);
-                        last_frame = now;
-
-                        // FPS counter
-                        frame_count += 1;
-                        let fps_elapsed = now.duration_since(fps_timer).as_secs_f32();
-                        if fps_elapsed >= 1.0 {
-                            let fps = frame_count as f32 / fps_elapsed;
-                            frame_count = 0;
-                            fps_timer = now;
-                            window.set_title(&format!(
-                                "⭐ Star System Sim | {:.0} FPS | {} particles | {:?}",
-                                fps, sim.num_alive_particles, sim.spawn_mode
-                            ));
-                        }
+                            // Get surface texture
+                            let frame_idx = gpu.frame_index;
+                            let surface = gpu.surface.as_ref().expect("windowed GPU state has a surface");
+                            let output = match surface.get_current_texture() {
+                                Ok(t) => t,
+                                Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                                    let size = window.inner_size();
+                                    gpu.resize(size.width, size.height);
+                                    return;
+                                }
+                                Err(e) => {
+                                    log::error!("Surface error: {:?}", e);
+                                    return;
+                                }
+                            };
 
-                        // Update simulation state
-                        sim.update_params(dt);
-                        camera.update(dt);
-
-                        // Upload simulation parameters
-                        gpu.queue.write_buffer(
-                            &gpu.sim_params_buffer,
-                            0,
-                            bytemuck::bytes_of(&sim.params),
-                        );
-
-                        // Upload camera uniform
-                        let eye = camera.eye_position();
-                        let cam_uniform = CameraUniform {
-                            view_proj: camera.view_proj_matrix().to_cols_array_2d(),
-                            view: camera.view_matrix().to_cols_array_2d(),
-                            proj: camera.proj_matrix().to_cols_array_2d(),
-                            eye_pos: [eye.x, eye.y, eye.z, 1.0],
-                            screen_size: [
-                                gpu.config.width as f32,
-                                gpu.config.height as f32,
-                                sim.time,
-                                0.0,
-                            ],
-                        };
-                        gpu.queue.write_buffer(
-                            &gpu.camera_buffer,
-                            0,
-                            bytemuck::bytes_of(&cam_uniform),
-                        );
-
-                        // Get surface texture
-                        let frame_idx = gpu.frame_index;
-                        let output = match gpu.surface.get_current_texture() {
-                            Ok(t) => t,
-                            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
-                                let size = window.inner_size();
-                                gpu.resize(size.width, size.height);
-                                return;
-                            }
-                            Err(e) => {
-                                log::error!("Surface error: {:?}", e);
-                                return;
-                            }
-                        };
-
-                        let view = output
-                            .texture
-                            .create_view(&wgpu::TextureViewDescriptor::default());
-
-                        let mut encoder =
-                            gpu.device
-                                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
-                                    label: Some("Frame"),
-                                });
+                            let view = output
+                                .texture
+                                .create_view(&wgpu::TextureViewDescriptor::default());
 
-                        // === COMPUTE PASS 1: Update celestial body orbits ===
-                        {
-                            let mut pass =
-                                encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                                    label: Some("Orbit Compute"),
-                                    timestamp_writes: None,
-                                });
-                            pass.set_pipeline(&gpu.orbit_compute_pipeline);
-                            pass.set_bind_group(
-                                0,
-                                &gpu.orbit_compute_bind_groups[frame_idx % 2],
-                                &[],
+                            let mut encoder =
+                                gpu.device
+                                    .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                                        label: Some("Frame"),
+                                    });
+
+                            // Parameter panel (applies its changes before the uploads below)
+                            let panel_frame = panel.run(
+                                &window,
+                                &mut gpu,
+                                &mut encoder,
+                                &mut sim,
+                                &mut camera,
+                                &mut views,
+                                &scenario,
+                                &mut selection,
+                                &follow.mode,
                             );
-                            pass.dispatch_workgroups(1, 1, 1);
-                        }
+                            if let Some(mode) = &panel_frame.actions.follow {
+                                log::info!("Camera: {}", mode.describe(&sim));
+                                follow.set(mode.clone(), &camera, &sim, lead);
+                            }
+                            if let Some(length) = panel_frame.actions.trail_length {
+                                if sim.backend == Backend::Gpu {
+                                    read_back_state(&gpu, &mut sim);
+                                }
+                                sim.set_trail_length(length);
+                                gpu.queue.write_buffer(&gpu.orbit_vertex_buffer, 0, bytemuck::cast_slice(&sim.trails));
+                                gpu.orbit_vertex_count = sim.trails.len() as u32;
+                            }
+                            if let Some(frame) = panel_frame.actions.frame {
+                                let delta = change_frame(&mut gpu, &mut sim, &mut camera, frame);
+                                follow.translate(delta);
+                                bodies_time = sim.time;
+                                log::info!("Reference frame: {}", sim.frame().describe(&sim));
+                            }
+                            screenshot = screenshot.or(panel_frame.actions.screenshot);
 
-                        // Sync: copy updated bodies so particle compute can read them
-                        encoder.copy_buffer_to_buffer(
-                            &gpu.body_buffers[(frame_idx + 1) % 2],
-                            0,
-                            &gpu.body_buffers[frame_idx % 2],
-                            0,
-                            (MAX_BODIES * std::mem::size_of::<GpuCelestialBody>()) as u64,
-                        );
-
-                        // === COMPUTE PASS 2: Update particles (gravity + swarm) ===
-                        {
-                            let mut pass =
-                                encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
-                                    label: Some("Particle Compute"),
-                                    timestamp_writes: None,
-                                });
-                            pass.set_pipeline(&gpu.particle_compute_pipeline);
-                            pass.set_bind_group(
+                            // Upload simulation parameters
+                            gpu.queue.write_buffer(
+                                &gpu.sim_params_buffer,
                                 0,
-                                &gpu.particle_compute_bind_groups[frame_idx % 2],
-                                &[],
+                                bytemuck::bytes_of(&sim.params),
                             );
-                            let workgroups = (MAX_PARTICLES as u32 + 255) / 256;
-                            pass.dispatch_workgroups(workgroups, 1, 1);
-                        }
 
-                        // === RENDER PASS ===
-                        {
-                            let mut rp =
-                                encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                                    label: Some("Main Render"),
-                                    color_attachments: &[Some(
-                                        wgpu::RenderPassColorAttachment {
-                                            view: &view,
-                                            resolve_target: None,
-                                            ops: wgpu::Operations {
-                                                load: wgpu::LoadOp::Clear(wgpu::Color {
-                                                    r: 0.005,
-                                                    g: 0.005,
-                                                    b: 0.02,
-                                                    a: 1.0,
-                                                }),
-                                                store: wgpu::StoreOp::Store,
-                                            },
-                                        },
-                                    )],
-                                    depth_stencil_attachment: Some(
-                                        wgpu::RenderPassDepthStencilAttachment {
-                                            view: &gpu.depth_texture,
-                                            depth_ops: Some(wgpu::Operations {
-                                                load: wgpu::LoadOp::Clear(1.0),
-                                                store: wgpu::StoreOp::Store,
-                                            }),
-                                            stencil_ops: None,
-                                        },
-                                    ),
-                                    timestamp_writes: None,
-                                    occlusion_query_set: None,
-                                });
+                            // Upload a camera uniform per view, dropping a selection that has died or gone
+                            selection = selection.filter(|&s| picking::inspect(&sim, s).is_some());
+                            let viewports = views.viewports((gpu.config.width, gpu.config.height));
+                            for viewport in &viewports {
+                                let mut cam_uniform = gpu.camera_uniform(
+                                    views.camera(&camera, viewport),
+                                    &sim,
+                                    (viewport.rect[2], viewport.rect[3]),
+                                    window.scale_factor() as f32,
+                                    1.0,
+                                );
+                                cam_uniform.selection = selection.map_or([0.0; 4], |s| s.uniform());
+                                gpu.queue.write_buffer(
+                                    gpu.camera_buffer_for(viewport),
+                                    0,
+                                    bytemuck::bytes_of(&cam_uniform),
+                                );
+                            }
+                            let surfaces: Vec<GpuSurface> = sim.surfaces.iter().map(|s| s.spun(sim.time)).collect();
+                            gpu.queue.write_buffer(&gpu.surface_buffer, 0, bytemuck::cast_slice(&surfaces));
 
-                            // 1. Draw orbit path lines (Dynamic Trails)
-                            if gpu.orbit_vertex_count > 0 {
-                                rp.set_pipeline(&gpu.orbit_render_pipeline);
-                                rp.set_bind_group(0, &gpu.render_bind_group, &[]);
-                                rp.set_vertex_buffer(0, gpu.orbit_vertex_buffer.slice(..));
 
-                                let trail_len = 512u32;
-                                let num_bodies = gpu.orbit_vertex_count / trail_len;
-                                for i in 0..num_bodies {
-                                    rp.draw(i * trail_len .. (i + 1) * trail_len, 0..1);
-                                }
+                            // === COMPUTE: body orbits, then particles (gravity + swarm) ===
+                            if gpu_step {
+                                gpu.step_physics(&mut encoder, sim.bodies.len());
+                            }
+
+                            if sim.backend == Backend::Gpu {
+                                let particle = match selection {
+                                    Some(Selection::Particle(index)) => Some(index),
+                                    _ => None,
+                                };
+                                gpu.copy_bodies_for_readback(&mut encoder, sim.bodies.len(), particle, sim.time);
                             }
 
-                            // 2. Draw celestial bodies (from updated buffer)
+                            // === RENDER PASS: multisampled, resolved into the HDR target ===
                             let body_buf_idx = (frame_idx + 1) % 2;
-                            rp.set_pipeline(&gpu.body_render_pipeline);
-                            rp.set_bind_group(0, &gpu.render_bind_group, &[]);
-                            rp.set_vertex_buffer(0, gpu.body_buffers[body_buf_idx].slice(..));
-                            rp.draw(0..6, 0..sim.bodies.len() as u32);
-
-                            // 3. Draw all particles (from updated buffer)
-                            let particle_buf_idx = (frame_idx + 1) % 2;
-                            rp.set_pipeline(&gpu.particle_render_pipeline);
-                            rp.set_bind_group(0, &gpu.render_bind_group, &[]);
-                            rp.set_vertex_buffer(
-                                0,
-                                gpu.particle_buffers[particle_buf_idx].slice(..),
+                            gpu.draw_scene(
+                                &mut encoder,
+                                gpu.post.scene_target(),
+                                &gpu.depth_texture,
+                                &viewports,
+                                body_buf_idx,
+                                sim.bodies.len() as u32,
+                                sim.params.trail_length,
                             );
-                            rp.draw(0..6, 0..MAX_PARTICLES as u32);
+
+                            // === POST-PROCESS: bloom + tone mapping into the swapchain ===
+                            gpu.post.update(&gpu.queue);
+                            gpu.post.run(&mut encoder, &view);
+
+                            // === OVERLAYS: labels, HUD and help ===
+                            let (width, height) = (gpu.config.width as f32, gpu.config.height as f32);
+                            let shown_frame = sim.frame_at(0.0);
+                            let shown_bodies = shown_frame.bodies_in_frame(&sim.bodies);
+                            let markers: Vec<(&str, glam::Vec3)> = panel
+                                .lagrange_pair
+                                .map(|(primary, secondary)| {
+                                    LagrangePoint::ALL
+                                        .iter()
+                                        .filter_map(|point| {
+                                            let at = point.locate(&sim.bodies, primary, secondary)?;
+                                            Some((point.name(), shown_frame.point_in_frame(at.position)))
+                                        })
+                                        .collect()
+                                })
+                                .unwrap_or_default();
+                            for viewport in &viewports {
+                                if overlay.labels {
+                                    hud::draw_labels(
+                                        &mut gpu.text,
+                                        views.camera(&camera, viewport),
+                                        viewport,
+                                        &shown_bodies,
+                                        &sim.body_info,
+                                        gpu.post.settings.true_scale,
+                                    );
+                                }
+                                hud::draw_markers(&mut gpu.text, views.camera(&camera, viewport), viewport, &markers);
+                                if let Some(i) = viewport.inset {
+                                    hud::draw_view_title(&mut gpu.text, viewport, &views.insets[i].caption(viewport));
+                                }
+                            }
+                            if overlay.hud {
+                                hud::draw_hud(&mut gpu.text, &sim, &scenario.name, fps, viewports[0].rect[2] as f32);
+                            }
+                            if overlay.help {
+                                hud::draw_help(&mut gpu.text, width, height);
+                            }
+                            gpu.text.prepare(&gpu.device, &gpu.queue, gpu.config.width, gpu.config.height);
+
+                            // === UI PASS: overlays and parameter panel, after tone mapping ===
+                            {
+                                let mut rp =
+                                    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                                        label: Some("UI"),
+                                        color_attachments: &[Some(
+                                            wgpu::RenderPassColorAttachment {
+                                                view: &view,
+                                                resolve_target: None,
+                                                ops: wgpu::Operations {
+                                                    load: wgpu::LoadOp::Load,
+                                                    store: wgpu::StoreOp::Store,
+                                                },
+                                            },
+                                        )],
+                                        depth_stencil_attachment: None,
+                                        timestamp_writes: None,
+                                        occlusion_query_set: None,
+                                    });
+                                gpu.text.draw(&mut rp);
+                                panel.paint(&mut rp, &panel_frame);
+                            }
+
+                            gpu.queue.submit(std::iter::once(encoder.finish()));
+                            gpu.map_body_readback();
+                            output.present();
+
+                            // === SCREENSHOT: the same state again, offscreen ===
+                            if let Some(size) = screenshot.take() {
+                                let path = capture::screenshot_path(&cli.screenshot_dir, &scenario.name, &sim, size);
+                                let result = capture::capture(
+                                    &gpu,
+                                    &camera,
+                                    &sim,
+                                    size,
+                                    window.scale_factor() as f32,
+                                    body_buf_idx,
+                                )
+                                .and_then(|image| {
+                                    capture::save_png(&path, &image, &capture::metadata(&sim, &scenario.name))
+                                });
+                                panel.screenshot_status = match result {
+                                    Ok(()) => {
+                                        log::info!("Saved {}", path.display());
+                                        format!("Saved {}", path.display())
+                                    }
+                                    Err(e) => {
+                                        log::error!("Screenshot failed: {}", e);
+                                        e
+                                    }
+                                };
+                            }
+                            panel.finish(panel_frame);
+                            shown_buffer = body_buf_idx;
+                            // Ping-pong only flips when a compute step wrote the other buffer
+                            if gpu_step {
+                                gpu.frame_index += 1;
+                            }
                         }
 
-                        gpu.queue.submit(std::iter::once(encoder.finish()));
-                        output.present();
-                        gpu.frame_index += 1;
+                        _ => {}
                     }
-
-                    _ => {}
-                },
+                }
 
                 Event::AboutToWait => {
                     window.request_redraw();
                 }
 
+                Event::LoopExiting => {
+                    if cli.snapshot.is_some() && sim.backend == Backend::Gpu {
+                        read_back_state(&gpu, &mut sim);
+                    }
+                    write_outputs(&cli, &sim);
+                }
+
                 _ => {}
             }
         })
@@ -0,0 +1,179 @@
+use glam::{DVec3, Vec3};
+
+// ============================================================================
+// Two-body orbital mechanics helpers.
+// Elements are given in the usual ecliptic convention (z = north); the
+// simulation is y-up with orbits in the x-z plane, so the ecliptic
+// (x, y, z) maps onto simulation (x, z, y).
+// ============================================================================
+
+/// Classical Keplerian elements. Angles in radians.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrbitalElements {
+    pub semi_major_axis: f32,
+    pub eccentricity: f32,
+    pub inclination: f32,
+    pub ascending_node: f32,
+    pub argument_of_periapsis: f32,
+    pub mean_anomaly: f32,
+}
+
+fn to_sim(v: Vec3) -> Vec3 {
+    Vec3::new(v.x, v.z, v.y)
+}
+
+/// Solve Kepler's equation M = E - e sin E for the eccentric anomaly
+fn eccentric_anomaly(mean_anomaly: f32, e: f32) -> f32 {
+    let mut ecc = if e < 0.8 { mean_anomaly } else { std::f32::consts::PI };
+    for _ in 0..32 {
+        let f = ecc - e * ecc.sin() - mean_anomaly;
+        let step = f / (1.0 - e * ecc.cos());
+        ecc -= step;
+        if step.abs() < 1e-7 {
+            break;
+        }
+    }
+    ecc
+}
+
+/// Position and velocity relative to the primary, for gravitational
+/// parameter `mu` = G * (M_primary + m).
+pub fn elements_to_state(el: &OrbitalElements, mu: f32) -> (Vec3, Vec3) {
+    let a = el.semi_major_axis;
+    let e = el.eccentricity;
+    let ecc = eccentric_anomaly(el.mean_anomaly, e);
+
+    // Perifocal frame: x towards periapsis
+    let b = a * (1.0 - e * e).sqrt();
+    let r = a * (1.0 - e * ecc.cos());
+    let pos_pf = Vec3::new(a * (ecc.cos() - e), b * ecc.sin(), 0.0);
+    let vel_pf = Vec3::new(-ecc.sin(), (1.0 - e * e).sqrt() * ecc.cos(), 0.0) * ((mu * a).sqrt() / r);
+
+    // Rotate perifocal -> ecliptic: Rz(node) * Rx(inclination) * Rz(periapsis)
+    let (so, co) = el.ascending_node.sin_cos();
+    let (si, ci) = el.inclination.sin_cos();
+    let (sw, cw) = el.argument_of_periapsis.sin_cos();
+    let p = Vec3::new(co * cw - so * sw * ci, so * cw + co * sw * ci, sw * si);
+    let q = Vec3::new(-co * sw - so * cw * ci, -so * sw + co * cw * ci, cw * si);
+
+    let pos = p * pos_pf.x + q * pos_pf.y;
+    let vel = p * vel_pf.x + q * vel_pf.y;
+    (to_sim(pos), to_sim(vel))
+}
+
+/// Osculating elements of a position and velocity relative to the
+/// primary, the inverse of `elements_to_state`. Worked in f64, since
+/// nearly circular or flat orbits lose their angles in f32. Hyperbolic
+/// orbits get a negative semi-major axis and the hyperbolic mean anomaly;
+/// undefined angles (node of a flat orbit, periapsis of a circular one)
+/// are zero.
+pub fn state_to_elements(pos: Vec3, vel: Vec3, mu: f32) -> OrbitalElements {
+    const EPS: f64 = 1e-9;
+    // The y-z swap is its own inverse, so to_sim also maps back
+    let r = to_sim(pos).as_dvec3();
+    let v = to_sim(vel).as_dvec3();
+    let mu = mu as f64;
+
+    let h = r.cross(v);
+    let node = DVec3::new(-h.y, h.x, 0.0);
+    let e_vec = ((v.length_squared() - mu / r.length()) * r - r.dot(v) * v) / mu;
+    let e = e_vec.length();
+    let energy = 0.5 * v.length_squared() - mu / r.length();
+    let a = -mu / (2.0 * energy);
+
+    let h_hat = h.normalize_or_zero();
+    let inclination = (h_hat.z).clamp(-1.0, 1.0).acos();
+    // Reference direction in the orbit plane: the ascending node, or x
+    // for an orbit in the ecliptic
+    let (ascending_node, node_hat) = if node.length() > EPS * h.length() {
+        (node.y.atan2(node.x), node.normalize())
+    } else {
+        (0.0, DVec3::X)
+    };
+    let angle_from = |from: DVec3, to: DVec3| to.dot(h_hat.cross(from)).atan2(to.dot(from));
+    let (argument_of_periapsis, true_anomaly) = if e > EPS {
+        let e_hat = e_vec / e;
+        (angle_from(node_hat, e_hat), angle_from(e_hat, r))
+    } else {
+        (0.0, angle_from(node_hat, r))
+    };
+
+    let half = (0.5 * true_anomaly).tan();
+    let mean_anomaly = if e < 1.0 {
+        let ecc = 2.0 * (((1.0 - e) / (1.0 + e)).sqrt() * half).atan();
+        ecc - e * ecc.sin()
+    } else {
+        let hyp = 2.0 * (((e - 1.0) / (e + 1.0)).sqrt() * half).atanh();
+        e * hyp.sinh() - hyp
+    };
+
+    OrbitalElements {
+        semi_major_axis: a as f32,
+        eccentricity: e as f32,
+        inclination: inclination as f32,
+        ascending_node: ascending_node.rem_euclid(std::f64::consts::TAU) as f32,
+        argument_of_periapsis: argument_of_periapsis.rem_euclid(std::f64::consts::TAU) as f32,
+        mean_anomaly: if e < 1.0 { mean_anomaly.rem_euclid(std::f64::consts::TAU) } else { mean_anomaly } as f32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MU: f32 = 39.478_416;
+
+    /// Difference of two angles, wrapped into [-pi, pi]
+    fn angle_diff(a: f32, b: f32) -> f32 {
+        (a - b + std::f32::consts::PI).rem_euclid(std::f32::consts::TAU) - std::f32::consts::PI
+    }
+
+    #[test]
+    fn inclined_eccentric_round_trip() {
+        let el = OrbitalElements {
+            semi_major_axis: 2.7,
+            eccentricity: 0.35,
+            inclination: 0.4,
+            ascending_node: 1.2,
+            argument_of_periapsis: 4.1,
+            mean_anomaly: 2.3,
+        };
+        let (pos, vel) = elements_to_state(&el, MU);
+        let back = state_to_elements(pos, vel, MU);
+
+        assert!((back.semi_major_axis - el.semi_major_axis).abs() < 1e-4, "{:?}", back);
+        assert!((back.eccentricity - el.eccentricity).abs() < 1e-5, "{:?}", back);
+        assert!(angle_diff(back.inclination, el.inclination).abs() < 1e-5, "{:?}", back);
+        assert!(angle_diff(back.ascending_node, el.ascending_node).abs() < 1e-5, "{:?}", back);
+        assert!(angle_diff(back.argument_of_periapsis, el.argument_of_periapsis).abs() < 1e-4, "{:?}", back);
+        assert!(angle_diff(back.mean_anomaly, el.mean_anomaly).abs() < 1e-4, "{:?}", back);
+    }
+
+    #[test]
+    fn near_circular_round_trip() {
+        let el = OrbitalElements {
+            semi_major_axis: 1.0,
+            eccentricity: 1e-6,
+            inclination: 0.02,
+            ascending_node: 0.7,
+            argument_of_periapsis: 1.9,
+            mean_anomaly: 0.5,
+        };
+        let (pos, vel) = elements_to_state(&el, MU);
+        let back = state_to_elements(pos, vel, MU);
+
+        assert!((back.semi_major_axis - el.semi_major_axis).abs() < 1e-4, "{:?}", back);
+        assert!(back.eccentricity < 1e-4, "{:?}", back);
+        assert!(angle_diff(back.inclination, el.inclination).abs() < 1e-5, "{:?}", back);
+        assert!(angle_diff(back.ascending_node, el.ascending_node).abs() < 1e-3, "{:?}", back);
+        // Periapsis is barely defined, but where along the orbit the body
+        // sits is
+        let longitude = |e: &OrbitalElements| e.argument_of_periapsis + e.mean_anomaly;
+        assert!(angle_diff(longitude(&back), longitude(&el)).abs() < 1e-3, "{:?}", back);
+
+        // And the elements give the state back
+        let (pos2, vel2) = elements_to_state(&back, MU);
+        assert!(pos2.distance(pos) < 1e-4, "{} vs {}", pos2, pos);
+        assert!(vel2.distance(vel) < 1e-3, "{} vs {}", vel2, vel);
+    }
+}
@@ -0,0 +1,194 @@
+use glam::{Mat4, Vec3};
+
+use crate::camera::Camera;
+use crate::capture;
+use crate::cpu_physics;
+use crate::gpu::{GpuState, PICK_SIZE};
+use crate::orbit::{self, OrbitalElements};
+use crate::simulation::{Backend, Simulation};
+use crate::viewports::Viewport;
+
+// ============================================================================
+// Picking: a click selects the body or particle under the cursor. Bodies
+// are few and their spheres are known on the host, so they are ray traced
+// here; particles are rendered as ids into a small target around the cursor
+// on the GPU. The selection is outlined in the scene and described in the
+// inspector with its osculating orbit about its primary.
+// ============================================================================
+
+/// Smallest radius, in pixels, a body can be clicked at
+const BODY_PICK_PIXELS: f32 = 6.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Selection {
+    Body(usize),
+    Particle(usize),
+}
+
+impl Selection {
+    /// The `selection` field of the camera uniform
+    pub fn uniform(&self) -> [f32; 4] {
+        match *self {
+            Selection::Body(i) => [1.0, i as f32, 0.0, 0.0],
+            Selection::Particle(i) => [2.0, i as f32, 0.0, 0.0],
+        }
+    }
+
+    /// Position, velocity and mass in the host copy of the simulation, or
+    /// `None` if the object no longer exists
+    pub fn state(&self, sim: &Simulation) -> Option<(Vec3, Vec3, f32)> {
+        let (position, velocity) = match *self {
+            Selection::Body(i) => {
+                let b = sim.bodies.get(i)?;
+                (b.position, b.velocity)
+            }
+            Selection::Particle(i) => {
+                let p = sim.particles.get(i).filter(|p| p.data[3] >= 0.5)?;
+                (p.position, p.velocity)
+            }
+        };
+        Some((Vec3::from_slice(&position), Vec3::from_slice(&velocity), position[3]))
+    }
+
+    pub fn name(&self, sim: &Simulation) -> String {
+        match *self {
+            Selection::Body(i) => sim.body_info.get(i).map_or_else(|| format!("Body {}", i), |b| b.name.clone()),
+            Selection::Particle(i) => format!("Particle {}", i),
+        }
+    }
+}
+
+/// The nearest body or live particle under `cursor` (window pixels) in
+/// `viewport`, seen through `camera` and drawn from ping-pong buffer
+/// `buffer`. On the GPU backend a picked particle is read back into
+/// `sim.particles` so it can be inspected at once.
+pub fn pick(
+    gpu: &GpuState,
+    camera: &Camera,
+    viewport: &Viewport,
+    sim: &mut Simulation,
+    cursor: (f32, f32),
+    dpi: f32,
+    buffer: usize,
+) -> Option<Selection> {
+    let [_, _, width, height] = viewport.rect;
+    // The camera sits in the reference frame; the bodies are in simulation
+    // coordinates
+    let (origin, dir) = viewport.ray(camera, cursor);
+    let frame = sim.frame_at(0.0);
+    let (origin, dir) = (frame.point_in_sim(origin), frame.direction_in_sim(dir));
+    let true_scale = gpu.post.settings.true_scale;
+
+    // Bodies: distance along the ray to the nearest sphere, clickable at
+    // no smaller than a few pixels
+    let mut best: Option<(f32, Selection)> = None;
+    for (i, body) in sim.bodies.iter().enumerate() {
+        let center = Vec3::from_slice(&body.position);
+        let along = (center - origin).dot(dir);
+        if along <= 0.0 {
+            continue;
+        }
+        let radius = if true_scale { body.data[3] } else { body.velocity[3] };
+        let radius = radius.max(camera.pixel_size(along, height as f32) * BODY_PICK_PIXELS);
+        let miss = (center - origin).length_squared() - along * along;
+        if miss <= radius * radius {
+            let hit = along - (radius * radius - miss).sqrt();
+            if best.is_none_or(|(t, _)| hit < t) {
+                best = Some((hit, Selection::Body(i)));
+            }
+        }
+    }
+
+    // Particles: the id buffer, zoomed onto the pixels around the cursor
+    let mut uniform = gpu.camera_uniform(camera, sim, (PICK_SIZE, PICK_SIZE), dpi, 1.0);
+    let half = (PICK_SIZE / 2) as i64;
+    let cursor = viewport.local(cursor);
+    let origin_px = (cursor.0 as i64 - half, cursor.1 as i64 - half);
+    let tile = capture::tile_matrix((width, height), origin_px, (PICK_SIZE, PICK_SIZE));
+    uniform.view_proj = (tile * Mat4::from_cols_array_2d(&uniform.view_proj)).to_cols_array_2d();
+    uniform.proj = (tile * Mat4::from_cols_array_2d(&uniform.proj)).to_cols_array_2d();
+    if let Some(index) = gpu.pick_particle(&uniform, buffer) {
+        if sim.backend == Backend::Gpu {
+            sim.particles[index] = gpu.read_element(&gpu.particle_buffers[buffer], index);
+        }
+        let along = (Vec3::from_slice(&sim.particles[index].position) - origin).dot(dir);
+        if best.is_none_or(|(t, _)| along < t) {
+            best = Some((along, Selection::Particle(index)));
+        }
+    }
+    best.map(|(_, selection)| selection)
+}
+
+/// What the inspector shows about the selection
+pub struct Inspection {
+    pub name: String,
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub mass: f32,
+    /// Nearest star and the distance to it
+    pub star: Option<(String, f32)>,
+    /// Body the orbit is taken about, and the osculating elements and
+    /// period (years, bound orbits only) around it
+    pub orbit: Option<(String, OrbitalElements, Option<f32>)>,
+}
+
+/// The body `selection` orbits: the lightest body whose sphere of
+/// influence, taken about the most massive body, holds it; else the most
+/// massive body. Only bodies heavier than the object count.
+pub fn primary(sim: &Simulation, selection: Selection) -> Option<usize> {
+    let (position, _, mass) = selection.state(sim)?;
+    let skip = match selection {
+        Selection::Body(i) => Some(i),
+        Selection::Particle(_) => None,
+    };
+    let others = || sim.bodies.iter().enumerate().filter(move |(j, b)| Some(*j) != skip && b.position[3] > mass);
+    let (h, heavy) = others().max_by(|a, b| a.1.position[3].total_cmp(&b.1.position[3]))?;
+    let heavy_pos = Vec3::from_slice(&heavy.position);
+    let within = others()
+        .filter(|(j, b)| {
+            let b_pos = Vec3::from_slice(&b.position);
+            *j != h
+                && (b_pos - position).length()
+                    < (b_pos - heavy_pos).length() * (b.position[3] / heavy.position[3]).powf(0.4)
+        })
+        .min_by(|a, b| a.1.position[3].total_cmp(&b.1.position[3]));
+    Some(within.map_or(h, |(j, _)| j))
+}
+
+/// Describe the selection, or `None` if it no longer exists
+pub fn inspect(sim: &Simulation, selection: Selection) -> Option<Inspection> {
+    let (position, velocity, mass) = selection.state(sim)?;
+    let skip = match selection {
+        Selection::Body(i) => Some(i),
+        Selection::Particle(_) => None,
+    };
+
+    let star = sim
+        .bodies
+        .iter()
+        .enumerate()
+        .filter(|(j, b)| Some(*j) != skip && b.data[0] > 0.5)
+        .map(|(j, b)| (j, (Vec3::from_slice(&b.position) - position).length()))
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(j, d)| (sim.body_info[j].name.clone(), d));
+
+    let orbit = primary(sim, selection).map(|j| {
+        let body = &sim.bodies[j];
+        // Bodies move under the fixed G; only particles feel the tunable one
+        let g = match selection {
+            Selection::Body(_) => cpu_physics::ORBIT_G,
+            Selection::Particle(_) => sim.params.gravitational_constant,
+        };
+        let mu = g * (body.position[3] + mass);
+        let elements = orbit::state_to_elements(
+            position - Vec3::from_slice(&body.position),
+            velocity - Vec3::from_slice(&body.velocity),
+            mu,
+        );
+        let a = elements.semi_major_axis;
+        let period = (elements.eccentricity < 1.0 && a > 0.0).then(|| std::f32::consts::TAU * (a * a * a / mu).sqrt());
+        (sim.body_info[j].name.clone(), elements, period)
+    });
+
+    Some(Inspection { name: selection.name(sim), position, velocity, mass, star, orbit })
+}
@@ -0,0 +1,473 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::util::DeviceExt;
+
+// ============================================================================
+// HDR post-processing: the scene renders into an Rgba16Float target, then a
+// downsample/upsample bloom chain and a tone mapping pass resolve it into
+// the swapchain.
+// ============================================================================
+
+/// Format of the scene colour target
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Bloom levels, each half the size of the previous one
+const BLOOM_LEVELS: u32 = 6;
+
+/// Tone mapping curve applied after exposure
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum ToneMap {
+    Aces,       // Filmic, rolls off highlights and desaturates them
+    Reinhard,   // Gentler, keeps bright colours saturated
+    Linear,     // Plain clamp, for comparison
+}
+
+/// User-facing rendering controls
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderSettings {
+    pub exposure: f32,
+    pub bloom_intensity: f32,
+    pub bloom_threshold: f32,
+    pub bloom_radius: f32,
+    pub tonemap: ToneMap,
+    /// Draw bodies at their physical radius instead of the visual one
+    pub true_scale: bool,
+    /// Background starfield brightness, 0 to hide it
+    pub starfield: f32,
+    /// Trail ribbon width in logical pixels
+    pub trail_width: f32,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            exposure: 1.0,
+            bloom_intensity: 0.6,
+            bloom_threshold: 1.0,
+            bloom_radius: 1.0,
+            tonemap: ToneMap::Aces,
+            true_scale: false,
+            starfield: 1.0,
+            trail_width: 1.5,
+        }
+    }
+}
+
+/// Post-process uniform - must match WGSL
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct PostParams {
+    exposure: f32,
+    bloom_intensity: f32,
+    bloom_threshold: f32,
+    bloom_knee: f32,
+    bloom_radius: f32,
+    tonemap: u32,
+    reduction: u32,
+    _pad0: u32,
+    bloom_rect: [f32; 4],
+    tile_origin: [f32; 2],
+    _pad1: [f32; 2],
+}
+
+/// Where the image being post-processed sits in a larger tiled capture
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tiling {
+    /// Scene pixels averaged into each bloom source pixel, each way
+    pub reduction: u32,
+    /// The part of the bloom texture under this image: uv offset, uv size
+    pub bloom_rect: [f32; 4],
+    /// This image's top-left pixel in the full capture
+    pub origin: [f32; 2],
+}
+
+impl Tiling {
+    /// The whole image, blooming itself
+    pub const NONE: Self = Self { reduction: 1, bloom_rect: [0.0, 0.0, 1.0, 1.0], origin: [0.0, 0.0] };
+}
+
+/// Size-dependent resources, rebuilt on resize
+struct Targets {
+    hdr_view: wgpu::TextureView,
+    bloom_views: Vec<wgpu::TextureView>,
+    /// Sources for the downsample passes: the scene, then each level but the last
+    down_bind_groups: Vec<wgpu::BindGroup>,
+    /// Sources for the upsample passes: every level but the first
+    up_bind_groups: Vec<wgpu::BindGroup>,
+    composite_bind_group: wgpu::BindGroup,
+}
+
+pub struct PostProcess {
+    pub settings: RenderSettings,
+    pub tiling: Tiling,
+    /// MSAA samples of the scene pass, resolved into the HDR target
+    pub sample_count: u32,
+    msaa_view: Option<wgpu::TextureView>,
+    params_buffer: wgpu::Buffer,
+    sampler: wgpu::Sampler,
+    sample_layout: wgpu::BindGroupLayout,
+    composite_layout: wgpu::BindGroupLayout,
+    prefilter_pipeline: wgpu::RenderPipeline,
+    downsample_pipeline: wgpu::RenderPipeline,
+    upsample_pipeline: wgpu::RenderPipeline,
+    reduce_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+    targets: Targets,
+}
+
+fn texture_entry(binding: u32) -> wgpu::BindGroupLayoutEntry {
+    wgpu::BindGroupLayoutEntry {
+        binding,
+        visibility: wgpu::ShaderStages::FRAGMENT,
+        ty: wgpu::BindingType::Texture {
+            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: false,
+        },
+        count: None,
+    }
+}
+
+fn color_texture(device: &wgpu::Device, label: &str, width: u32, height: u32) -> wgpu::TextureView {
+    device
+        .create_texture(&wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d { width: width.max(1), height: height.max(1), depth_or_array_layers: 1 },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: HDR_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        })
+        .create_view(&wgpu::TextureViewDescriptor::default())
+}
+
+/// Multisampled scene colour, or none without MSAA
+fn msaa_texture(device: &wgpu::Device, sample_count: u32, width: u32, height: u32) -> Option<wgpu::TextureView> {
+    (sample_count > 1).then(|| {
+        device
+            .create_texture(&wgpu::TextureDescriptor {
+                label: Some("HDR Scene MSAA"),
+                size: wgpu::Extent3d { width: width.max(1), height: height.max(1), depth_or_array_layers: 1 },
+                mip_level_count: 1,
+                sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format: HDR_FORMAT,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            })
+            .create_view(&wgpu::TextureViewDescriptor::default())
+    })
+}
+
+impl PostProcess {
+    pub fn new(
+        device: &wgpu::Device,
+        output_format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+        settings: RenderSettings,
+        sample_count: u32,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Post Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/post.wgsl").into()),
+        });
+
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Post Params"),
+            contents: bytemuck::bytes_of(&PostParams::zeroed()),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Post Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let common = [
+            texture_entry(0),
+            wgpu::BindGroupLayoutEntry {
+                binding: 1,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                count: None,
+            },
+            wgpu::BindGroupLayoutEntry {
+                binding: 2,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            },
+        ];
+        let sample_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Post Sample BGL"),
+            entries: &common,
+        });
+        let composite_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Post Composite BGL"),
+            entries: &[common[0], common[1], common[2], texture_entry(3)],
+        });
+
+        let pipeline = |label: &str, layout: &wgpu::BindGroupLayout, entry: &str, format, blend| {
+            let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some(label),
+                bind_group_layouts: &[layout],
+                push_constant_ranges: &[],
+            });
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_fullscreen",
+                    buffers: &[],
+                    compilation_options: Default::default(),
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: entry,
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: Default::default(),
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        };
+
+        let additive = wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::One,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent::REPLACE,
+        };
+        let prefilter_pipeline = pipeline("Bloom Prefilter", &sample_layout, "fs_prefilter", HDR_FORMAT, None);
+        let downsample_pipeline = pipeline("Bloom Downsample", &sample_layout, "fs_downsample", HDR_FORMAT, None);
+        let upsample_pipeline = pipeline("Bloom Upsample", &sample_layout, "fs_upsample", HDR_FORMAT, Some(additive));
+        let reduce_pipeline = pipeline("Bloom Reduce", &sample_layout, "fs_reduce", HDR_FORMAT, None);
+        let composite_pipeline = pipeline("Tone Map", &composite_layout, "fs_composite", output_format, None);
+
+        let targets = Self::create_targets(
+            device, &sample_layout, &composite_layout, &sampler, &params_buffer, width, height,
+        );
+
+        Self {
+            settings,
+            tiling: Tiling::NONE,
+            sample_count,
+            msaa_view: msaa_texture(device, sample_count, width, height),
+            params_buffer,
+            sampler,
+            sample_layout,
+            composite_layout,
+            prefilter_pipeline,
+            downsample_pipeline,
+            upsample_pipeline,
+            reduce_pipeline,
+            composite_pipeline,
+            targets,
+        }
+    }
+
+    fn create_targets(
+        device: &wgpu::Device,
+        sample_layout: &wgpu::BindGroupLayout,
+        composite_layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        params_buffer: &wgpu::Buffer,
+        width: u32,
+        height: u32,
+    ) -> Targets {
+        let hdr_view = color_texture(device, "HDR Scene", width, height);
+
+        // Stop early on tiny windows so no level collapses below a pixel
+        let levels = BLOOM_LEVELS.min(width.min(height).max(2).ilog2());
+        let bloom_views: Vec<wgpu::TextureView> = (1..=levels)
+            .map(|i| color_texture(device, "Bloom Level", width >> i, height >> i))
+            .collect();
+
+        let bind = |layout: &wgpu::BindGroupLayout, source: &wgpu::TextureView, bloom: Option<&wgpu::TextureView>| {
+            let mut entries = vec![
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(source) },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(sampler) },
+                wgpu::BindGroupEntry { binding: 2, resource: params_buffer.as_entire_binding() },
+            ];
+            if let Some(bloom) = bloom {
+                entries.push(wgpu::BindGroupEntry { binding: 3, resource: wgpu::BindingResource::TextureView(bloom) });
+            }
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Post BG"),
+                layout,
+                entries: &entries,
+            })
+        };
+
+        let down_bind_groups = std::iter::once(&hdr_view)
+            .chain(&bloom_views[..bloom_views.len() - 1])
+            .map(|source| bind(sample_layout, source, None))
+            .collect();
+        let up_bind_groups = bloom_views[1..].iter().map(|source| bind(sample_layout, source, None)).collect();
+        let composite_bind_group = bind(composite_layout, &hdr_view, Some(&bloom_views[0]));
+
+        Targets { hdr_view, bloom_views, down_bind_groups, up_bind_groups, composite_bind_group }
+    }
+
+    /// Colour attachment and resolve target for the main render pass
+    pub fn scene_target(&self) -> (&wgpu::TextureView, Option<&wgpu::TextureView>) {
+        match &self.msaa_view {
+            Some(msaa) => (msaa, Some(&self.targets.hdr_view)),
+            None => (&self.targets.hdr_view, None),
+        }
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, width: u32, height: u32) {
+        self.msaa_view = msaa_texture(device, self.sample_count, width, height);
+        self.targets = Self::create_targets(
+            device,
+            &self.sample_layout,
+            &self.composite_layout,
+            &self.sampler,
+            &self.params_buffer,
+            width,
+            height,
+        );
+    }
+
+    pub fn update(&self, queue: &wgpu::Queue) {
+        let settings = &self.settings;
+        let params = PostParams {
+            exposure: settings.exposure,
+            bloom_intensity: settings.bloom_intensity,
+            bloom_threshold: settings.bloom_threshold,
+            bloom_knee: settings.bloom_threshold * 0.5,
+            bloom_radius: settings.bloom_radius,
+            tonemap: match settings.tonemap {
+                ToneMap::Aces => 0,
+                ToneMap::Reinhard => 1,
+                ToneMap::Linear => 2,
+            },
+            reduction: self.tiling.reduction,
+            _pad0: 0,
+            bloom_rect: self.tiling.bloom_rect,
+            tile_origin: self.tiling.origin,
+            _pad1: [0.0; 2],
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(&params));
+    }
+
+    fn fullscreen_pass(
+        encoder: &mut wgpu::CommandEncoder,
+        label: &str,
+        target: &wgpu::TextureView,
+        load: wgpu::LoadOp<wgpu::Color>,
+        pipeline: &wgpu::RenderPipeline,
+        bind_group: &wgpu::BindGroup,
+    ) {
+        Self::scissored_pass(encoder, label, target, load, pipeline, bind_group, None);
+    }
+
+    fn scissored_pass(
+        encoder: &mut wgpu::CommandEncoder,
+        label: &str,
+        target: &wgpu::TextureView,
+        load: wgpu::LoadOp<wgpu::Color>,
+        pipeline: &wgpu::RenderPipeline,
+        bind_group: &wgpu::BindGroup,
+        scissor: Option<[u32; 4]>,
+    ) {
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations { load, store: wgpu::StoreOp::Store },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, bind_group, &[]);
+        if let Some([x, y, w, h]) = scissor {
+            pass.set_scissor_rect(x, y, w, h);
+        }
+        pass.draw(0..3, 0..1);
+    }
+
+    /// Bloom the HDR scene and tone map it into `output`
+    pub fn run(&self, encoder: &mut wgpu::CommandEncoder, output: &wgpu::TextureView) {
+        self.bloom(encoder);
+        self.composite(encoder, output, &self.targets.composite_bind_group);
+    }
+
+    /// Blur the bright parts of the HDR scene down and back up the chain
+    pub fn bloom(&self, encoder: &mut wgpu::CommandEncoder) {
+        let t = &self.targets;
+        let clear = wgpu::LoadOp::Clear(wgpu::Color::BLACK);
+
+        // Downsample: scene -> level 0 (with threshold) -> level 1 -> ...
+        for (i, (target, source)) in t.bloom_views.iter().zip(&t.down_bind_groups).enumerate() {
+            let pipeline = if i == 0 { &self.prefilter_pipeline } else { &self.downsample_pipeline };
+            Self::fullscreen_pass(encoder, "Bloom Down", target, clear, pipeline, source);
+        }
+
+        // Upsample: add each level onto the next larger one
+        for (target, source) in t.bloom_views.iter().zip(&t.up_bind_groups).rev() {
+            Self::fullscreen_pass(encoder, "Bloom Up", target, wgpu::LoadOp::Load, &self.upsample_pipeline, source);
+        }
+    }
+
+    /// Tone map the HDR scene into `output` with the glow bound in `bind_group`
+    pub fn composite(&self, encoder: &mut wgpu::CommandEncoder, output: &wgpu::TextureView, bind_group: &wgpu::BindGroup) {
+        let clear = wgpu::LoadOp::Clear(wgpu::Color::BLACK);
+        Self::fullscreen_pass(encoder, "Tone Map", output, clear, &self.composite_pipeline, bind_group);
+    }
+
+    /// Bind this scene for tone mapping with the glow of `other`, whose
+    /// scene covers a larger image this one is a tile of
+    pub fn composite_with(&self, device: &wgpu::Device, other: &PostProcess) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Post BG"),
+            layout: &self.composite_layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: wgpu::BindingResource::TextureView(&self.targets.hdr_view) },
+                wgpu::BindGroupEntry { binding: 1, resource: wgpu::BindingResource::Sampler(&self.sampler) },
+                wgpu::BindGroupEntry { binding: 2, resource: self.params_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry {
+                    binding: 3,
+                    resource: wgpu::BindingResource::TextureView(&other.targets.bloom_views[0]),
+                },
+            ],
+        })
+    }
+
+    /// Average this scene, a tile at `tiling.origin`, into `scissor` of
+    /// `other`'s smaller scene, `tiling.reduction` pixels to one each way
+    pub fn reduce_into(&self, encoder: &mut wgpu::CommandEncoder, other: &PostProcess, scissor: [u32; 4]) {
+        Self::scissored_pass(
+            encoder,
+            "Bloom Reduce",
+            &other.targets.hdr_view,
+            wgpu::LoadOp::Load,
+            &self.reduce_pipeline,
+            &self.targets.down_bind_groups[0],
+            Some(scissor),
+        );
+    }
+}
@@ -0,0 +1,54 @@
+use std::path::Path;
+
+use crate::scenario::Scenario;
+
+// ============================================================================
+// Built-in scenario presets, compiled into the binary from scenarios/*.toml.
+// A preset is referred to by its key, e.g. `--scenario trappist1`.
+// ============================================================================
+
+pub const PRESETS: &[(&str, &str)] = &[
+    ("solar_system", include_str!("../scenarios/solar_system.toml")),
+    ("trappist1", include_str!("../scenarios/trappist1.toml")),
+    ("alpha_centauri", include_str!("../scenarios/alpha_centauri.toml")),
+    ("figure_eight", include_str!("../scenarios/figure_eight.toml")),
+    ("pythagorean", include_str!("../scenarios/pythagorean.toml")),
+    ("earth_moon", include_str!("../scenarios/earth_moon.toml")),
+    ("asteroid_belt", include_str!("../scenarios/asteroid_belt.toml")),
+];
+
+pub const DEFAULT_PRESET: &str = "solar_system";
+
+/// Parse the preset with the given key
+pub fn preset(key: &str) -> Result<Scenario, String> {
+    let (_, source) = PRESETS
+        .iter()
+        .find(|(k, _)| *k == key)
+        .ok_or_else(|| format!("unknown preset '{}'", key))?;
+    Scenario::parse(source).map_err(|e| format!("preset '{}': {}", key, e))
+}
+
+/// Load a scenario by preset key, falling back to a TOML file path
+pub fn resolve(name: &str) -> Result<Scenario, String> {
+    if PRESETS.iter().any(|(k, _)| *k == name) {
+        return preset(name);
+    }
+    let path = Path::new(name);
+    if !path.exists() {
+        let keys: Vec<&str> = PRESETS.iter().map(|(k, _)| *k).collect();
+        return Err(format!(
+            "'{}' is neither a preset nor a scenario file (presets: {})",
+            name,
+            keys.join(", ")
+        ));
+    }
+    Scenario::load(path)
+}
+
+/// Key of the preset after `key` in the cycle order
+pub fn next_key(key: Option<&str>) -> &'static str {
+    let index = key
+        .and_then(|k| PRESETS.iter().position(|(p, _)| *p == k))
+        .map_or(0, |i| (i + 1) % PRESETS.len());
+    PRESETS[index].0
+}
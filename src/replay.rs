@@ -0,0 +1,238 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::path::Path;
+
+use glam::Vec3;
+
+use crate::lagrange::{LagrangePoint, LagrangeSite};
+use crate::simulation::{Integrator, Precision, SpawnMode};
+use crate::types::SimParams;
+
+/// Every input that changes the simulation state. Replaying the same
+/// sequence with the same seed reproduces a run exactly on the CPU backend.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimEvent {
+    SpawnBurst { center: Vec3, count: usize },
+    SpawnSwarm { center: Vec3, count: usize },
+    SpawnOrbitalSwarm { body: usize, count: usize, radius: f32 },
+    SpawnRing { body: usize, count: usize },
+    SpawnLagrange { site: LagrangeSite, count: usize },
+    SetTarget(Vec3),
+    /// Keep the swarm target on a Lagrange point
+    SetLagrangeTarget(LagrangeSite),
+    ClearTarget,
+    ClearParticles,
+    SetSpawnMode(SpawnMode),
+    SetPaused(bool),
+    SetTimeScale(f32),
+    SetIntegrator(Integrator),
+    SetPrecision(Precision),
+    SetParam { name: String, value: f32 },
+    /// Move the coordinate origin to this point
+    Rebase(Vec3),
+}
+
+/// An event stamped with the simulation step and sim time it was applied at
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoggedEvent {
+    pub step: u64,
+    pub time: f32,
+    pub event: SimEvent,
+}
+
+/// Recorded run: the seed and scenario plus all events in application order.
+///
+/// Stored as plain text, one event per line:
+/// `<step> <time> <kind> <args...>`. Floats use Rust's shortest
+/// round-trip formatting so a reload is bit-exact.
+#[derive(Debug, Clone, Default)]
+pub struct EventLog {
+    pub seed: u64,
+    pub scenario: Option<String>,
+    pub events: Vec<LoggedEvent>,
+}
+
+fn mode_name(mode: SpawnMode) -> &'static str {
+    match mode {
+        SpawnMode::Swarm => "swarm",
+        SpawnMode::Free => "free",
+        SpawnMode::Burst => "burst",
+    }
+}
+
+fn integrator_name(integrator: Integrator) -> &'static str {
+    match integrator {
+        Integrator::Euler => "euler",
+        Integrator::Leapfrog => "leapfrog",
+    }
+}
+
+fn precision_name(precision: Precision) -> &'static str {
+    match precision {
+        Precision::F32 => "f32",
+        Precision::Df64 => "df64",
+    }
+}
+
+impl fmt::Display for SimEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimEvent::SpawnBurst { center: c, count } => {
+                write!(f, "spawn_burst {} {} {} {}", c.x, c.y, c.z, count)
+            }
+            SimEvent::SpawnSwarm { center: c, count } => {
+                write!(f, "spawn_swarm {} {} {} {}", c.x, c.y, c.z, count)
+            }
+            SimEvent::SpawnOrbitalSwarm { body, count, radius } => {
+                write!(f, "spawn_orbital_swarm {} {} {}", body, count, radius)
+            }
+            SimEvent::SpawnRing { body, count } => write!(f, "spawn_ring {} {}", body, count),
+            SimEvent::SpawnLagrange { site: s, count } => {
+                write!(f, "spawn_lagrange {} {} {} {}", s.primary, s.secondary, s.point.name(), count)
+            }
+            SimEvent::SetTarget(p) => write!(f, "set_target {} {} {}", p.x, p.y, p.z),
+            SimEvent::SetLagrangeTarget(s) => {
+                write!(f, "lagrange_target {} {} {}", s.primary, s.secondary, s.point.name())
+            }
+            SimEvent::ClearTarget => write!(f, "clear_target"),
+            SimEvent::ClearParticles => write!(f, "clear_particles"),
+            SimEvent::SetSpawnMode(mode) => write!(f, "spawn_mode {}", mode_name(*mode)),
+            SimEvent::SetPaused(paused) => write!(f, "paused {}", paused),
+            SimEvent::SetTimeScale(scale) => write!(f, "time_scale {}", scale),
+            SimEvent::SetIntegrator(integrator) => {
+                write!(f, "integrator {}", integrator_name(*integrator))
+            }
+            SimEvent::SetPrecision(precision) => write!(f, "precision {}", precision_name(*precision)),
+            SimEvent::SetParam { name, value } => write!(f, "param {} {}", name, value),
+            SimEvent::Rebase(p) => write!(f, "rebase {} {} {}", p.x, p.y, p.z),
+        }
+    }
+}
+
+impl SimEvent {
+    fn parse(kind: &str, args: &[&str]) -> Result<Self, String> {
+        fn num<T: std::str::FromStr>(args: &[&str], i: usize) -> Result<T, String> {
+            let raw = args.get(i).ok_or_else(|| format!("missing argument {}", i + 1))?;
+            raw.parse().map_err(|_| format!("invalid argument '{}'", raw))
+        }
+        fn vec3(args: &[&str]) -> Result<Vec3, String> {
+            Ok(Vec3::new(num(args, 0)?, num(args, 1)?, num(args, 2)?))
+        }
+        fn site(args: &[&str]) -> Result<LagrangeSite, String> {
+            let point = args.get(2).ok_or("missing argument 3")?;
+            Ok(LagrangeSite { primary: num(args, 0)?, secondary: num(args, 1)?, point: LagrangePoint::parse(point)? })
+        }
+
+        Ok(match kind {
+            "spawn_burst" => SimEvent::SpawnBurst { center: vec3(args)?, count: num(args, 3)? },
+            "spawn_swarm" => SimEvent::SpawnSwarm { center: vec3(args)?, count: num(args, 3)? },
+            "spawn_orbital_swarm" => SimEvent::SpawnOrbitalSwarm {
+                body: num(args, 0)?,
+                count: num(args, 1)?,
+                radius: num(args, 2)?,
+            },
+            "spawn_ring" => SimEvent::SpawnRing { body: num(args, 0)?, count: num(args, 1)? },
+            "spawn_lagrange" => SimEvent::SpawnLagrange { site: site(args)?, count: num(args, 3)? },
+            "set_target" => SimEvent::SetTarget(vec3(args)?),
+            "lagrange_target" => SimEvent::SetLagrangeTarget(site(args)?),
+            "clear_target" => SimEvent::ClearTarget,
+            "rebase" => SimEvent::Rebase(vec3(args)?),
+            "clear_particles" => SimEvent::ClearParticles,
+            "spawn_mode" => SimEvent::SetSpawnMode(match args.first().copied() {
+                Some("swarm") => SpawnMode::Swarm,
+                Some("free") => SpawnMode::Free,
+                Some("burst") => SpawnMode::Burst,
+                other => return Err(format!("unknown spawn mode {:?}", other)),
+            }),
+            "paused" => SimEvent::SetPaused(num(args, 0)?),
+            "time_scale" => SimEvent::SetTimeScale(num(args, 0)?),
+            "integrator" => SimEvent::SetIntegrator(match args.first().copied() {
+                Some("euler") => Integrator::Euler,
+                Some("leapfrog") => Integrator::Leapfrog,
+                other => return Err(format!("unknown integrator {:?}", other)),
+            }),
+            "precision" => SimEvent::SetPrecision(match args.first().copied() {
+                Some("f32") => Precision::F32,
+                Some("df64") => Precision::Df64,
+                other => return Err(format!("unknown precision {:?}", other)),
+            }),
+            "param" => {
+                let name = *args.first().ok_or("missing parameter name")?;
+                if !SimParams::TUNABLE.contains(&name) {
+                    return Err(format!("unknown parameter '{}'", name));
+                }
+                SimEvent::SetParam { name: name.to_string(), value: num(args, 1)? }
+            }
+            _ => return Err(format!("unknown event '{}'", kind)),
+        })
+    }
+}
+
+impl EventLog {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, scenario: None, events: Vec::new() }
+    }
+
+    pub fn push(&mut self, step: u64, time: f32, event: SimEvent) {
+        self.events.push(LoggedEvent { step, time, event });
+    }
+
+    pub fn to_text(&self) -> String {
+        let mut out = String::from("# starsystem-sim event log\n");
+        out.push_str(&format!("seed {}\n", self.seed));
+        if let Some(scenario) = &self.scenario {
+            out.push_str(&format!("scenario {}\n", scenario));
+        }
+        for e in &self.events {
+            out.push_str(&format!("{} {} {}\n", e.step, e.time, e.event));
+        }
+        out
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut log = EventLog::default();
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let err = |msg: String| format!("line {}: {}", line_no + 1, msg);
+
+            if fields[0] == "seed" {
+                log.seed = fields
+                    .get(1)
+                    .and_then(|s| s.parse().ok())
+                    .ok_or_else(|| err("invalid seed".into()))?;
+                continue;
+            }
+            if fields[0] == "scenario" {
+                log.scenario = Some(line["scenario".len()..].trim().to_string());
+                continue;
+            }
+            if fields.len() < 3 {
+                return Err(err("expected '<step> <time> <event>'".into()));
+            }
+            let step = fields[0].parse().map_err(|_| err("invalid step".into()))?;
+            let time = fields[1].parse().map_err(|_| err("invalid time".into()))?;
+            let event = SimEvent::parse(fields[2], &fields[3..]).map_err(err)?;
+            log.events.push(LoggedEvent { step, time, event });
+        }
+        Ok(log)
+    }
+
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::write(path, self.to_text())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        Self::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Queue of events still to be applied, in order
+    pub fn into_queue(self) -> VecDeque<LoggedEvent> {
+        self.events.into()
+    }
+}
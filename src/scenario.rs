@@ -0,0 +1,883 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use glam::Vec3;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::camera::{Camera, Projection};
+use crate::camera_path::CameraPath;
+use crate::cpu_physics;
+use crate::lagrange::{self, LagrangeSite};
+use crate::orbit::{self, OrbitalElements};
+use crate::simulation::{BodyInfo, Emitter, Simulation, SpawnMode};
+use crate::surface::SurfaceStyle;
+use crate::types::*;
+
+// ============================================================================
+// Declarative scenario files (TOML)
+//
+//   name = "Inner planets"
+//   time_scale = 0.5
+//
+//   [params]
+//   separation_weight = 2.0
+//
+//   [[bodies]]
+//   name = "Sun"
+//   mass = 1.0
+//   radius = 0.15
+//   color = [1.0, 0.95, 0.7]
+//   star = true
+//
+//   [[bodies]]
+//   name = "Earth"
+//   mass = 3.003e-6
+//   radius = 0.03
+//   color = [0.2, 0.5, 0.9]
+//   orbit = { primary = "Sun", semi_major_axis = 1.0, eccentricity = 0.0167 }
+//
+//   [[swarms]]
+//   center = [1.0, 0.0, 0.2]
+//   count = 500
+//
+//   [[swarms]]
+//   lagrange = "Sun:Earth:L4"
+//   count = 300
+//
+//   [[disks]]
+//   around = ["Sun"]
+//   count = 5000
+//   inner_radius = 2.0
+//   outer_radius = 3.5
+// ============================================================================
+
+/// A complete starting setup: bodies, particles, parameters and view
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    #[serde(default = "default_time_scale")]
+    pub time_scale: f32,
+    #[serde(default = "default_trail_length")]
+    pub trail_length: usize,
+    /// Multiplier on particle radii and default swarm extents
+    #[serde(default = "default_particle_scale")]
+    pub particle_scale: f32,
+    /// Shift the bodies so the centre of mass starts at rest at the origin
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub barycentric: bool,
+    #[serde(default)]
+    pub camera: CameraSpec,
+    /// Keyframed camera for playback and export
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub camera_path: Option<CameraPath>,
+    /// Overrides for tunable `SimParams` fields, by field name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, f32>,
+    pub bodies: Vec<BodySpec>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub swarms: Vec<SwarmSpec>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disks: Vec<DiskSpec>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub emitters: Vec<EmitterSpec>,
+    /// Directory that relative texture paths resolve against: the
+    /// scenario file's own, or the working directory for presets
+    #[serde(skip)]
+    pub dir: PathBuf,
+}
+
+/// A body, given either by state vector or by orbital elements
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BodySpec {
+    pub name: String,
+    /// Solar masses
+    pub mass: f32,
+    /// Visual radius (AU), also used for particle collisions
+    pub radius: f32,
+    /// True radius (AU) for the true-scale view; defaults to `radius`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub physical_radius: Option<f32>,
+    /// RGB or RGBA
+    pub color: Vec<f32>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub star: bool,
+    /// Solar luminosities, for stars; defaults to the main-sequence mass^3.5
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub luminosity: Option<f32>,
+    /// Pinned in place; defaults to true for stars
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fixed: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<[f32; 3]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub velocity: Option<[f32; 3]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub orbit: Option<OrbitSpec>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rings: Option<RingSpec>,
+    /// Sidereal rotation period in days; no spin when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotation_period: Option<f32>,
+    /// Tilt of the spin axis from +y in degrees, towards -z like a ring
+    /// with node 0. Above 90 the body spins retrograde.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub axial_tilt: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub surface: Option<SurfaceSpec>,
+}
+
+/// How a planet's surface looks. Without a texture, or if it fails to
+/// load, the procedural style is drawn instead.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SurfaceSpec {
+    /// Equirectangular image, relative to the scenario file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub texture: Option<PathBuf>,
+    /// Defaults to banded for gas giants and rocky otherwise
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub style: Option<SurfaceStyle>,
+    /// RGB; defaults to a darker shade of the body colour
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secondary: Option<Vec<f32>>,
+    /// Varies the procedural pattern between bodies of the same style
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u32>,
+}
+
+/// A planetary ring system. Radii are in body radii, so the rings follow
+/// the body in both the visual and the true-scale view.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RingSpec {
+    pub inner: f32,
+    pub outer: f32,
+    /// Tilt of the ring plane from the x-z plane, in degrees
+    #[serde(default)]
+    pub inclination: f32,
+    /// Direction the tilt is about, in degrees around +y
+    #[serde(default)]
+    pub node: f32,
+    /// RGB or RGBA; alpha scales the whole profile
+    pub color: Vec<f32>,
+    /// Opacity from the inner to the outer edge, resampled to even steps
+    #[serde(default = "default_ring_profile")]
+    pub profile: Vec<f32>,
+    /// Free particles to seed on circular orbits in the ring. Orbits this
+    /// close to a moving planet hold together under the leapfrog integrator.
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub particles: usize,
+}
+
+/// Keplerian elements relative to an earlier body. Angles in degrees.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OrbitSpec {
+    pub primary: String,
+    pub semi_major_axis: f32,
+    #[serde(default)]
+    pub eccentricity: f32,
+    #[serde(default)]
+    pub inclination: f32,
+    #[serde(default)]
+    pub ascending_node: f32,
+    #[serde(default)]
+    pub argument_of_periapsis: f32,
+    #[serde(default)]
+    pub mean_anomaly: f32,
+}
+
+/// A swarm spawned at start: around a point, in orbit of a body, or at a
+/// Lagrange point of a pair ("Sun:Jupiter:L4")
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SwarmSpec {
+    pub count: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub center: Option<[f32; 3]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub around: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub lagrange: Option<String>,
+    /// Orbit radius (AU) when `around` is set
+    #[serde(default = "default_orbit_radius")]
+    pub radius: f32,
+    /// Bulk velocity of a `center` swarm
+    #[serde(default)]
+    pub velocity: [f32; 3],
+    /// Scales the extent and velocity jitter of a `center` or `lagrange`
+    /// swarm
+    #[serde(default = "default_one")]
+    pub spread: f32,
+    #[serde(default = "default_swarm_mode")]
+    pub mode: SpawnMode,
+}
+
+/// A ring of particles on randomised Keplerian orbits about the
+/// barycentre of one or more bodies
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DiskSpec {
+    pub around: Vec<String>,
+    pub count: usize,
+    pub inner_radius: f32,
+    pub outer_radius: f32,
+    /// Upper bound of the per-particle eccentricity
+    #[serde(default)]
+    pub eccentricity: f32,
+    /// Upper bound of the per-particle inclination, in degrees
+    #[serde(default)]
+    pub inclination: f32,
+    #[serde(default = "default_emit_mode")]
+    pub mode: SpawnMode,
+}
+
+/// A continuous particle source
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EmitterSpec {
+    pub position: [f32; 3],
+    #[serde(default)]
+    pub velocity: [f32; 3],
+    /// Particles per sim year
+    pub rate: f32,
+    #[serde(default = "default_spread")]
+    pub spread: f32,
+    #[serde(default = "default_emit_mode")]
+    pub mode: SpawnMode,
+    #[serde(default)]
+    pub start: f32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stop: Option<f32>,
+}
+
+/// Initial orbit camera. Angles in degrees.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CameraSpec {
+    #[serde(default)]
+    pub target: [f32; 3],
+    #[serde(default = "default_camera_distance")]
+    pub distance: f32,
+    #[serde(default = "default_camera_azimuth")]
+    pub azimuth: f32,
+    #[serde(default = "default_camera_elevation")]
+    pub elevation: f32,
+    #[serde(default = "default_camera_fov")]
+    pub fov: f32,
+    #[serde(default)]
+    pub projection: Projection,
+    #[serde(default = "default_camera_min_distance")]
+    pub min_distance: f32,
+    #[serde(default = "default_camera_max_distance")]
+    pub max_distance: f32,
+}
+
+fn default_time_scale() -> f32 { 1.0 }
+fn default_trail_length() -> usize { DEFAULT_TRAIL_LENGTH }
+fn default_particle_scale() -> f32 { 1.0 }
+fn default_one() -> f32 { 1.0 }
+fn default_orbit_radius() -> f32 { 0.1 }
+fn default_swarm_mode() -> SpawnMode { SpawnMode::Swarm }
+fn default_spread() -> f32 { 0.05 }
+fn default_emit_mode() -> SpawnMode { SpawnMode::Free }
+fn default_camera_distance() -> f32 { 5.0 }
+fn default_camera_azimuth() -> f32 { 45.0 }
+fn default_camera_elevation() -> f32 { 28.65 }
+fn default_camera_fov() -> f32 { 60.0 }
+fn default_camera_min_distance() -> f32 { 0.1 }
+fn default_camera_max_distance() -> f32 { 500.0 }
+fn is_false(b: &bool) -> bool { !*b }
+fn is_zero(n: &usize) -> bool { *n == 0 }
+fn default_ring_profile() -> Vec<f32> { vec![0.6] }
+
+impl Default for CameraSpec {
+    fn default() -> Self {
+        Self {
+            target: [0.0; 3],
+            distance: default_camera_distance(),
+            azimuth: default_camera_azimuth(),
+            elevation: default_camera_elevation(),
+            fov: default_camera_fov(),
+            projection: Projection::Perspective,
+            min_distance: default_camera_min_distance(),
+            max_distance: default_camera_max_distance(),
+        }
+    }
+}
+
+impl CameraSpec {
+    /// Capture the camera's current view
+    pub fn from_camera(camera: &Camera) -> Self {
+        Self {
+            target: camera.target.to_array(),
+            distance: camera.distance,
+            azimuth: camera.theta.to_degrees(),
+            elevation: camera.phi.to_degrees(),
+            fov: camera.fov.to_degrees(),
+            projection: camera.projection,
+            min_distance: camera.min_distance,
+            max_distance: camera.max_distance,
+        }
+    }
+
+    pub fn apply(&self, camera: &mut Camera) {
+        camera.fov = self.fov.to_radians();
+        camera.projection = self.projection;
+        camera.min_distance = self.min_distance;
+        camera.max_distance = self.max_distance;
+        // Keep the clip planes around the zoom range
+        camera.near = (self.min_distance * 0.01).min(0.001);
+        camera.set_orbit(
+            Vec3::from(self.target),
+            self.distance,
+            self.azimuth.to_radians(),
+            self.elevation.to_radians(),
+        );
+    }
+}
+
+fn color4(c: &[f32]) -> [f32; 4] {
+    [c[0], c[1], c[2], c.get(3).copied().unwrap_or(1.0)]
+}
+
+fn finite(v: &[f32]) -> bool {
+    v.iter().all(|x| x.is_finite())
+}
+
+impl Scenario {
+    /// Parse and validate TOML text
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let scenario: Scenario = toml::from_str(text).map_err(|e| e.to_string())?;
+        scenario.validate()?;
+        Ok(scenario)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("{}: {}", path.display(), e))?;
+        let mut scenario = Self::parse(&text).map_err(|e| format!("{}: {}", path.display(), e))?;
+        scenario.dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Ok(scenario)
+    }
+
+    /// Serialize back to TOML
+    pub fn to_toml(&self) -> Result<String, String> {
+        let mut value = toml::Value::try_from(self).map_err(|e| e.to_string())?;
+        shorten_floats(&mut value);
+        toml::to_string(&value).map_err(|e| e.to_string())
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let text = self.to_toml()?;
+        std::fs::write(path, text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Default parameters with this scenario's overrides applied
+    pub fn params(&self) -> SimParams {
+        let mut params = SimParams::default();
+        for (name, value) in &self.params {
+            params.set(name, *value);
+        }
+        params
+    }
+
+    /// Check every field, reporting all problems at once
+    pub fn validate(&self) -> Result<(), String> {
+        let mut errors = Vec::new();
+
+        if self.bodies.is_empty() {
+            errors.push("at least one body is required".to_string());
+        }
+        if self.bodies.len() > MAX_BODIES {
+            errors.push(format!("{} bodies given, at most {} are supported", self.bodies.len(), MAX_BODIES));
+        }
+        if !(self.time_scale.is_finite() && self.time_scale > 0.0) {
+            errors.push(format!("time_scale must be positive, got {}", self.time_scale));
+        }
+        if !(2..=MAX_TRAIL_LENGTH).contains(&self.trail_length) {
+            errors.push(format!(
+                "trail_length must be between 2 and {}, got {}",
+                MAX_TRAIL_LENGTH, self.trail_length
+            ));
+        }
+
+        for (name, value) in &self.params {
+            if !SimParams::TUNABLE.contains(&name.as_str()) {
+                errors.push(format!(
+                    "params.{}: unknown parameter (expected one of: {})",
+                    name,
+                    SimParams::TUNABLE.join(", ")
+                ));
+            } else if !(value.is_finite() && *value >= 0.0) {
+                errors.push(format!("params.{}: must be a non-negative number, got {}", name, value));
+            } else if name == "damping" && *value > 1.0 {
+                errors.push(format!("params.damping: must be at most 1.0, got {}", value));
+            }
+        }
+
+        if !(self.particle_scale.is_finite() && self.particle_scale > 0.0) {
+            errors.push(format!("particle_scale must be positive, got {}", self.particle_scale));
+        }
+
+        let c = &self.camera;
+        if !(finite(&c.target) && c.distance > 0.0 && (1.0..179.0).contains(&c.fov)) {
+            errors.push("camera: distance must be positive and fov between 1 and 179 degrees".into());
+        }
+        if !(c.min_distance > 0.0 && c.min_distance <= c.distance && c.distance <= c.max_distance) {
+            errors.push(format!(
+                "camera: need 0 < min_distance <= distance <= max_distance, got {} / {} / {}",
+                c.min_distance, c.distance, c.max_distance
+            ));
+        }
+
+        if let Err(e) = self.camera_path.as_ref().map_or(Ok(()), CameraPath::validate) {
+            errors.extend(e.lines().map(|line| format!("camera_path: {}", line)));
+        }
+
+        for (i, body) in self.bodies.iter().enumerate() {
+            let at = format!("bodies[{}] '{}'", i, body.name);
+            if self.bodies[..i].iter().any(|b| b.name == body.name) {
+                errors.push(format!("{}: duplicate body name", at));
+            }
+            if !(body.mass.is_finite() && body.mass > 0.0) {
+                errors.push(format!("{}: mass must be positive, got {}", at, body.mass));
+            }
+            if !(body.radius.is_finite() && body.radius > 0.0) {
+                errors.push(format!("{}: radius must be positive, got {}", at, body.radius));
+            }
+            if let Some(r) = body.physical_radius {
+                if !(r.is_finite() && r > 0.0) {
+                    errors.push(format!("{}: physical_radius must be positive, got {}", at, r));
+                }
+            }
+            if !(body.color.len() == 3 || body.color.len() == 4) || !finite(&body.color) {
+                errors.push(format!("{}: color needs 3 (RGB) or 4 (RGBA) numbers", at));
+            }
+            if let Some(rings) = &body.rings {
+                if !(rings.inner >= 1.0 && rings.inner < rings.outer && rings.outer.is_finite()) {
+                    errors.push(format!("{}: rings need 1 <= inner < outer (body radii)", at));
+                }
+                if !(rings.color.len() == 3 || rings.color.len() == 4) || !finite(&rings.color) {
+                    errors.push(format!("{}: rings.color needs 3 (RGB) or 4 (RGBA) numbers", at));
+                }
+                if rings.profile.is_empty() || !rings.profile.iter().all(|o| (0.0..=1.0).contains(o)) {
+                    errors.push(format!("{}: rings.profile needs opacities between 0 and 1", at));
+                }
+                if !(finite(&[rings.inclination, rings.node])) {
+                    errors.push(format!("{}: rings inclination/node must be finite", at));
+                }
+            }
+            if let Some(l) = body.luminosity {
+                if !(l.is_finite() && l > 0.0) {
+                    errors.push(format!("{}: luminosity must be positive, got {}", at, l));
+                }
+            }
+            if let Some(period) = body.rotation_period {
+                if !(period.is_finite() && period > 0.0) {
+                    errors.push(format!("{}: rotation_period must be positive days, got {}", at, period));
+                }
+            }
+            if body.axial_tilt.is_some_and(|t| !(0.0..=180.0).contains(&t)) {
+                errors.push(format!("{}: axial_tilt must be in [0, 180] degrees", at));
+            }
+            if let Some(secondary) = body.surface.as_ref().and_then(|s| s.secondary.as_ref()) {
+                if secondary.len() != 3 || !finite(secondary) {
+                    errors.push(format!("{}: surface.secondary needs 3 (RGB) numbers", at));
+                }
+            }
+            match &body.orbit {
+                Some(orbit) => {
+                    if body.position.is_some() || body.velocity.is_some() {
+                        errors.push(format!("{}: give either orbit or position/velocity, not both", at));
+                    }
+                    if !self.bodies[..i].iter().any(|b| b.name == orbit.primary) {
+                        errors.push(format!(
+                            "{}: orbit.primary '{}' must name a body listed before it",
+                            at, orbit.primary
+                        ));
+                    }
+                    if !(orbit.semi_major_axis.is_finite() && orbit.semi_major_axis > 0.0) {
+                        errors.push(format!("{}: orbit.semi_major_axis must be positive", at));
+                    }
+                    if !(0.0..1.0).contains(&orbit.eccentricity) {
+                        errors.push(format!(
+                            "{}: orbit.eccentricity must be in [0, 1), got {}",
+                            at, orbit.eccentricity
+                        ));
+                    }
+                }
+                None => {
+                    let state = body.position.iter().chain(body.velocity.iter()).flatten();
+                    if !state.copied().all(f32::is_finite) {
+                        errors.push(format!("{}: position/velocity must be finite", at));
+                    }
+                }
+            }
+        }
+
+        let mut total_particles: usize =
+            self.bodies.iter().filter_map(|b| b.rings.as_ref()).map(|r| r.particles).sum();
+        for (i, swarm) in self.swarms.iter().enumerate() {
+            let at = format!("swarms[{}]", i);
+            total_particles += swarm.count;
+            match (&swarm.center, &swarm.around, &swarm.lagrange) {
+                (Some(center), None, None) => {
+                    if !(finite(center) && finite(&swarm.velocity)) {
+                        errors.push(format!("{}: center/velocity must be finite", at));
+                    }
+                    if !(swarm.spread.is_finite() && swarm.spread >= 0.0) {
+                        errors.push(format!("{}: spread must be non-negative", at));
+                    }
+                }
+                (None, Some(around), None) => {
+                    if !self.bodies.iter().any(|b| &b.name == around) {
+                        errors.push(format!("{}: around '{}' is not a body in this scenario", at, around));
+                    }
+                    if !(swarm.radius.is_finite() && swarm.radius > 0.0) {
+                        errors.push(format!("{}: radius must be positive", at));
+                    }
+                }
+                (None, None, Some(site)) => {
+                    match lagrange::split(site) {
+                        Ok((primary, secondary, _)) => {
+                            for name in [primary, secondary] {
+                                if !self.bodies.iter().any(|b| b.name == name) {
+                                    errors.push(format!("{}: lagrange '{}' is not a body in this scenario", at, name));
+                                }
+                            }
+                        }
+                        Err(e) => errors.push(format!("{}: {}", at, e)),
+                    }
+                    if !(swarm.spread.is_finite() && swarm.spread >= 0.0) {
+                        errors.push(format!("{}: spread must be non-negative", at));
+                    }
+                }
+                _ => errors.push(format!("{}: set exactly one of 'center', 'around' or 'lagrange'", at)),
+            }
+        }
+        for (i, disk) in self.disks.iter().enumerate() {
+            let at = format!("disks[{}]", i);
+            total_particles += disk.count;
+            if disk.around.is_empty() {
+                errors.push(format!("{}: around needs at least one body", at));
+            }
+            for name in &disk.around {
+                if !self.bodies.iter().any(|b| &b.name == name) {
+                    errors.push(format!("{}: around '{}' is not a body in this scenario", at, name));
+                }
+            }
+            if !(disk.inner_radius > 0.0 && disk.inner_radius <= disk.outer_radius && disk.outer_radius.is_finite()) {
+                errors.push(format!("{}: need 0 < inner_radius <= outer_radius", at));
+            }
+            if !(0.0..1.0).contains(&disk.eccentricity) {
+                errors.push(format!("{}: eccentricity must be in [0, 1), got {}", at, disk.eccentricity));
+            }
+            if !(0.0..=180.0).contains(&disk.inclination) {
+                errors.push(format!("{}: inclination must be in [0, 180] degrees", at));
+            }
+        }
+        if total_particles > MAX_PARTICLES {
+            errors.push(format!(
+                "swarms, disks and rings request {} particles, at most {} are supported",
+                total_particles, MAX_PARTICLES
+            ));
+        }
+
+        for (i, e) in self.emitters.iter().enumerate() {
+            let at = format!("emitters[{}]", i);
+            if !(finite(&e.position) && finite(&e.velocity)) {
+                errors.push(format!("{}: position/velocity must be finite", at));
+            }
+            if !(e.rate.is_finite() && e.rate >= 0.0 && e.spread.is_finite() && e.spread >= 0.0) {
+                errors.push(format!("{}: rate and spread must be non-negative", at));
+            }
+            if e.stop.is_some_and(|stop| stop < e.start) {
+                errors.push(format!("{}: stop must not be before start", at));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("invalid scenario '{}':\n  - {}", self.name, errors.join("\n  - ")))
+        }
+    }
+
+    /// Resolve orbital elements into absolute state vectors
+    pub fn bodies(&self, g: f32) -> (Vec<GpuCelestialBody>, Vec<BodyInfo>) {
+        let mut bodies: Vec<GpuCelestialBody> = Vec::with_capacity(self.bodies.len());
+        let mut info = Vec::with_capacity(self.bodies.len());
+
+        for spec in &self.bodies {
+            let (pos, vel) = match &spec.orbit {
+                Some(o) => {
+                    let primary_idx = info
+                        .iter()
+                        .position(|b: &BodyInfo| b.name == o.primary)
+                        .expect("validated");
+                    let primary: &GpuCelestialBody = &bodies[primary_idx];
+                    let mu = g * (primary.position[3] + spec.mass);
+                    let elements = OrbitalElements {
+                        semi_major_axis: o.semi_major_axis,
+                        eccentricity: o.eccentricity,
+                        inclination: o.inclination.to_radians(),
+                        ascending_node: o.ascending_node.to_radians(),
+                        argument_of_periapsis: o.argument_of_periapsis.to_radians(),
+                        mean_anomaly: o.mean_anomaly.to_radians(),
+                    };
+                    let (rel_pos, rel_vel) = orbit::elements_to_state(&elements, mu);
+                    let primary_pos = Vec3::new(primary.position[0], primary.position[1], primary.position[2]);
+                    let primary_vel = Vec3::new(primary.velocity[0], primary.velocity[1], primary.velocity[2]);
+                    (primary_pos + rel_pos, primary_vel + rel_vel)
+                }
+                None => (
+                    Vec3::from(spec.position.unwrap_or_default()),
+                    Vec3::from(spec.velocity.unwrap_or_default()),
+                ),
+            };
+
+            let fixed = spec.fixed.unwrap_or(spec.star);
+            bodies.push(GpuCelestialBody::new(
+                [pos.x, pos.y, pos.z, spec.mass],
+                [vel.x, vel.y, vel.z, spec.radius],
+                color4(&spec.color),
+                [
+                    if spec.star { 1.0 } else { 0.0 },
+                    vel.length(),
+                    if fixed { 1.0 } else { 0.0 },
+                    spec.physical_radius.unwrap_or(spec.radius),
+                ],
+            ));
+            info.push(BodyInfo {
+                name: spec.name.clone(),
+                texture: spec
+                    .surface
+                    .as_ref()
+                    .and_then(|s| s.texture.as_ref())
+                    .map(|t| self.dir.join(t)),
+            });
+        }
+
+        if self.barycentric {
+            let (com_pos, com_vel) = barycenter(&bodies);
+            for b in &mut bodies {
+                for k in 0..3 {
+                    b.position[k] -= com_pos[k];
+                    b.velocity[k] -= com_vel[k];
+                }
+            }
+        }
+
+        (bodies, info)
+    }
+
+    /// Ring geometry for every body that has rings
+    pub fn rings(&self) -> Vec<GpuRing> {
+        self.bodies
+            .iter()
+            .enumerate()
+            .filter_map(|(i, body)| {
+                let spec = body.rings.as_ref()?;
+                // Tilt +y about the node line, which lies in the x-z plane
+                let (si, ci) = spec.inclination.to_radians().sin_cos();
+                let (sn, cn) = spec.node.to_radians().sin_cos();
+                let normal = Vec3::new(si * sn, ci, -si * cn);
+                let mut profile = [0.0; RING_PROFILE_SAMPLES];
+                let last = (spec.profile.len() - 1) as f32;
+                for (k, out) in profile.iter_mut().enumerate() {
+                    let x = k as f32 / (RING_PROFILE_SAMPLES - 1) as f32 * last;
+                    let (a, b) = (x.floor() as usize, (x.ceil() as usize).min(spec.profile.len() - 1));
+                    *out = spec.profile[a] + (spec.profile[b] - spec.profile[a]) * x.fract();
+                }
+                Some(GpuRing {
+                    normal: [normal.x, normal.y, normal.z, i as f32],
+                    radii: [spec.inner, spec.outer, 0.0, 0.0],
+                    color: color4(&spec.color),
+                    profile,
+                })
+            })
+            .collect()
+    }
+
+    /// Spin axis, surface appearance and luminosity of every body
+    pub fn surfaces(&self) -> Vec<GpuSurface> {
+        self.bodies
+            .iter()
+            .enumerate()
+            .map(|(i, body)| {
+                let (st, ct) = body.axial_tilt.unwrap_or(0.0).to_radians().sin_cos();
+                let axis = Vec3::new(0.0, ct, -st);
+                // The meridian starts at +x, which is perpendicular to any tilt
+                let meridian = Vec3::X;
+                let period = body.rotation_period.map_or(0.0, |days| days / 365.25);
+                let surface = body.surface.as_ref();
+                let style = surface.and_then(|s| s.style).unwrap_or(SurfaceStyle::for_mass(body.mass));
+                let secondary = match surface.and_then(|s| s.secondary.as_ref()) {
+                    Some(c) => [c[0], c[1], c[2], 1.0],
+                    None => {
+                        let c = color4(&body.color);
+                        [c[0] * 0.55, c[1] * 0.5, c[2] * 0.45, 1.0]
+                    }
+                };
+                let seed = surface.and_then(|s| s.seed).unwrap_or(i as u32);
+                let luminosity = match body.star {
+                    true => body.luminosity.unwrap_or(body.mass.powf(3.5)),
+                    false => 0.0,
+                };
+                GpuSurface {
+                    axis: [axis.x, axis.y, axis.z, period],
+                    meridian: [meridian.x, meridian.y, meridian.z, -1.0],
+                    secondary,
+                    params: [style.index(), seed as f32, 0.0, luminosity],
+                }
+            })
+            .collect()
+    }
+
+    /// Check that the scenario fits in buffers of the given size
+    pub fn check_capacity(&self, capacity: &Capacity) -> Result<(), String> {
+        let particles = self.swarms.iter().map(|s| s.count).sum::<usize>()
+            + self.disks.iter().map(|d| d.count).sum::<usize>()
+            + self.bodies.iter().filter_map(|b| b.rings.as_ref()).map(|r| r.particles).sum::<usize>();
+        if self.bodies.len() > capacity.bodies {
+            return Err(format!(
+                "scenario '{}' has {} bodies but the body capacity is {}",
+                self.name, self.bodies.len(), capacity.bodies
+            ));
+        }
+        if particles > capacity.particles {
+            return Err(format!(
+                "scenario '{}' spawns {} particles but the particle capacity is {}",
+                self.name, particles, capacity.particles
+            ));
+        }
+        Ok(())
+    }
+
+    /// Build a simulation in this scenario's initial state.
+    /// `source` is how a replay can find the scenario again.
+    pub fn build(&self, seed: u64, source: Option<String>, capacity: &Capacity) -> Simulation {
+        let params = self.params();
+        // Bodies always move under the fixed G; the tunable one is only
+        // for particles
+        let (bodies, info) = self.bodies(cpu_physics::ORBIT_G);
+        let mut sim = Simulation::new(bodies, info, seed, capacity.particles);
+        sim.params = SimParams {
+            num_bodies: sim.params.num_bodies,
+            num_particles: sim.params.num_particles,
+            ..params
+        };
+        sim.time_scale = self.time_scale;
+        sim.set_trail_length(self.trail_length);
+
+        sim.particle_scale = self.particle_scale;
+        for swarm in &self.swarms {
+            match (&swarm.center, &swarm.around, &swarm.lagrange) {
+                (Some(center), _, _) => sim.spawn_cloud(
+                    Vec3::from(*center),
+                    Vec3::from(swarm.velocity),
+                    swarm.count,
+                    swarm.spread * self.particle_scale,
+                    swarm.mode,
+                ),
+                (None, Some(around), _) => {
+                    let idx = sim.body_info.iter().position(|b| &b.name == around).expect("validated");
+                    sim.spawn_orbital_swarm(idx, swarm.count, swarm.radius);
+                }
+                (None, None, Some(site)) => {
+                    let site = LagrangeSite::parse(site, &sim).expect("validated");
+                    sim.spawn_near_lagrange(site, swarm.count, swarm.spread, swarm.mode);
+                }
+                (None, None, None) => unreachable!("validated"),
+            }
+        }
+        for disk in &self.disks {
+            spawn_disk(&mut sim, disk);
+        }
+        sim.rings = self.rings();
+        sim.surfaces = self.surfaces();
+        for (i, body) in self.bodies.iter().enumerate() {
+            if let Some(rings) = body.rings.as_ref().filter(|r| r.particles > 0) {
+                sim.spawn_ring_particles(i, rings.particles);
+            }
+        }
+
+        sim.emitters = self
+            .emitters
+            .iter()
+            .map(|e| Emitter {
+                position: Vec3::from(e.position),
+                velocity: Vec3::from(e.velocity),
+                rate: e.rate,
+                spread: e.spread,
+                mode: e.mode,
+                start: e.start,
+                stop: e.stop,
+                pending: 0.0,
+            })
+            .collect();
+
+        sim.start_recording(source);
+        sim
+    }
+}
+
+/// Every float in a scenario is an f32 widened to f64 on the way out;
+/// print them with the f32's shortest representation (0.1, not 0.10000000149).
+fn shorten_floats(value: &mut toml::Value) {
+    match value {
+        toml::Value::Float(f) => {
+            *f = (*f as f32).to_string().parse().unwrap_or(*f);
+        }
+        toml::Value::Array(items) => items.iter_mut().for_each(shorten_floats),
+        toml::Value::Table(table) => table.iter_mut().for_each(|(_, v)| shorten_floats(v)),
+        _ => {}
+    }
+}
+
+/// Mass-weighted centre position and velocity
+fn barycenter(bodies: &[GpuCelestialBody]) -> (Vec3, Vec3) {
+    let mut total = 0.0;
+    let (mut pos, mut vel) = (Vec3::ZERO, Vec3::ZERO);
+    for b in bodies {
+        let m = b.position[3];
+        total += m;
+        pos += Vec3::new(b.position[0], b.position[1], b.position[2]) * m;
+        vel += Vec3::new(b.velocity[0], b.velocity[1], b.velocity[2]) * m;
+    }
+    (pos / total, vel / total)
+}
+
+/// Seed a disk with particles on randomised orbits, uniform in area
+fn spawn_disk(sim: &mut Simulation, disk: &DiskSpec) {
+    let members: Vec<GpuCelestialBody> = disk
+        .around
+        .iter()
+        .map(|name| sim.bodies[sim.find_body(name).expect("validated")])
+        .collect();
+    let (center, center_vel) = barycenter(&members);
+    let central_mass: f32 = members.iter().map(|b| b.position[3]).sum();
+    let mu = sim.params.gravitational_constant * central_mass;
+
+    let (r2_min, r2_max) = (disk.inner_radius.powi(2), disk.outer_radius.powi(2));
+    let tau = std::f32::consts::TAU;
+    for _ in 0..disk.count {
+        let rng = sim.rng();
+        let elements = OrbitalElements {
+            semi_major_axis: rng.gen_range(r2_min..=r2_max).sqrt(),
+            eccentricity: rng.gen_range(0.0..=disk.eccentricity),
+            inclination: rng.gen_range(0.0..=disk.inclination).to_radians(),
+            ascending_node: rng.gen_range(0.0..tau),
+            argument_of_periapsis: rng.gen_range(0.0..tau),
+            mean_anomaly: rng.gen_range(0.0..tau),
+        };
+        let (pos, vel) = orbit::elements_to_state(&elements, mu);
+        sim.spawn_with_mode(center + pos, center_vel + vel, disk.mode);
+    }
+}
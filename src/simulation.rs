@@ -1,12 +1,29 @@
-use glam::Vec3;
-use rand::Rng;
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::path::PathBuf;
+
+use glam::{DVec3, Vec3};
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::cpu_physics;
+use crate::frames::{Frame, ReferenceFrame};
+use crate::lagrange::LagrangeSite;
+use crate::replay::{EventLog, LoggedEvent, SimEvent};
 use crate::types::*;
 
 /// High-level simulation state
 pub struct Simulation {
     pub params: SimParams,
     pub bodies: Vec<GpuCelestialBody>,
+    pub body_info: Vec<BodyInfo>,
+    /// Ring systems, at most one per body
+    pub rings: Vec<GpuRing>,
+    /// Spin and surface of each body, parallel to `bodies`
+    pub surfaces: Vec<GpuSurface>,
     pub particles: Vec<GpuParticle>,
+    pub trails: Vec<GridVertex>,
     pub num_alive_particles: u32,
     pub time: f32,
     pub paused: bool,
@@ -14,33 +31,223 @@ pub struct Simulation {
 
     // Interaction
     pub target_pos: Option<Vec3>,
+    /// Lagrange point the swarm target follows
+    target_site: Option<LagrangeSite>,
     pub spawn_mode: SpawnMode,
+    pub emitters: Vec<Emitter>,
+    /// Multiplier on spawned particle radii, for systems far from 1 AU scale
+    pub particle_scale: f32,
+
+    // Floating origin
+    /// Body the origin moves to whenever it strays past `REBASE_DISTANCE`
+    pub floating_origin: Option<usize>,
+    /// Where the coordinate origin sits in the scenario's starting frame.
+    /// Every position in the state is relative to it.
+    pub origin: DVec3,
+    /// Origin shift not yet taken by the view and the GPU buffers
+    rebased: Option<Vec3>,
+
+    /// Frame the scene, trails and snapshots are given in
+    frame: ReferenceFrame,
+
+    // Determinism
+    pub seed: u64,
+    pub step: u64,
+    pub backend: Backend,
+    pub log: EventLog,
+    rng: ChaCha8Rng,
+    pending: VecDeque<LoggedEvent>,
+    /// Rebases still queued by a replay. Until they have run, origin moves
+    /// come only from the log.
+    queued_rebases: usize,
+    /// Particle slots changed on the host since the last upload
+    dirty_particles: Vec<Range<usize>>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum SpawnMode {
     Swarm,      // Spawn swarm particles with boids behavior
     Free,       // Spawn free particles (just gravity)
     Burst,      // Spawn a burst of particles
 }
 
+/// Host-side metadata for a body, parallel to `bodies`
+#[derive(Debug, Clone, PartialEq)]
+pub struct BodyInfo {
+    pub name: String,
+    /// Surface texture to load, already resolved against the scenario
+    pub texture: Option<PathBuf>,
+}
+
+/// Continuous particle source, driven by sim time so it replays exactly
+#[derive(Debug, Clone, PartialEq)]
+pub struct Emitter {
+    pub position: Vec3,
+    pub velocity: Vec3,
+    pub rate: f32,          // particles per sim year
+    pub spread: f32,        // position jitter (AU)
+    pub mode: SpawnMode,
+    pub start: f32,
+    pub stop: Option<f32>,
+    pub pending: f32,       // fractional particles carried between steps
+}
+
+/// Where the physics step runs
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Backend {
+    Gpu,        // Compute shaders, one step per frame
+    Cpu,        // Reference port, fixed steps, bit-for-bit reproducible
+}
+
+/// Time integration scheme, shared by bodies and particles
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Integrator {
+    Euler,      // Symplectic Euler: kick then drift
+    Leapfrog,   // Drift-kick-drift, second order, time-reversible
+}
+
+/// Arithmetic for body positions and velocities. Particles stay in f32.
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Precision {
+    F32,        // Single precision, ~1e-7 relative
+    Df64,       // Pairs of f32 (double-single), ~1e-14 relative, several times slower
+}
+
 impl Simulation {
-    pub fn new(bodies: Vec<GpuCelestialBody>) -> Self {
-        let num_bodies = bodies.len();
-        let mut params = SimParams::default();
-        params.num_bodies = num_bodies as u32;
-        params.num_particles = MAX_PARTICLES as u32;
+    pub fn new(bodies: Vec<GpuCelestialBody>, body_info: Vec<BodyInfo>, seed: u64, particle_capacity: usize) -> Self {
+        let params = SimParams {
+            num_bodies: bodies.len() as u32,
+            num_particles: particle_capacity as u32,
+            ..Default::default()
+        };
 
-        Self {
+        let mut sim = Self {
             params,
             bodies,
-            particles: vec![GpuParticle::dead(); MAX_PARTICLES],
+            body_info,
+            rings: Vec::new(),
+            surfaces: Vec::new(),
+            particles: vec![GpuParticle::dead(); particle_capacity],
+            trails: Vec::new(),
             num_alive_particles: 0,
             time: 0.0,
             paused: false,
             time_scale: 1.0,
             target_pos: None,
+            target_site: None,
             spawn_mode: SpawnMode::Swarm,
+            emitters: Vec::new(),
+            particle_scale: 1.0,
+            floating_origin: None,
+            origin: DVec3::ZERO,
+            rebased: None,
+            frame: ReferenceFrame::Scenario,
+            seed,
+            step: 0,
+            backend: Backend::Gpu,
+            log: EventLog::new(seed),
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            pending: VecDeque::new(),
+            queued_rebases: 0,
+            dirty_particles: Vec::new(),
+        };
+        sim.set_trail_length(DEFAULT_TRAIL_LENGTH);
+        sim
+    }
+
+    /// Resize the trails and fill each with its body's current position
+    pub fn set_trail_length(&mut self, length: usize) {
+        self.params.trail_length = length as u32;
+        self.trails.clear();
+        let frame = self.frame_at(0.0);
+        for body in &self.bodies {
+            let vertex = GridVertex {
+                position: frame.frame_position(body.position_f64()).as_vec3().into(),
+                _pad: 0.0,
+                color: body.color,
+            };
+            self.trails.extend(std::iter::repeat_n(vertex, length));
+        }
+    }
+
+    /// Everything applied so far becomes the initial state; the event log
+    /// only records what happens from here on.
+    pub fn start_recording(&mut self, scenario: Option<String>) {
+        self.log = EventLog::new(self.seed);
+        self.log.scenario = scenario;
+    }
+
+    fn record(&mut self, event: SimEvent) {
+        self.log.push(self.step, self.time, event);
+    }
+
+    /// Queue a recorded log for replay. Events fire at their recorded step.
+    pub fn load_replay(&mut self, log: EventLog) {
+        self.pending = log.into_queue();
+        self.queued_rebases = self.pending.iter().filter(|e| matches!(e.event, SimEvent::Rebase(_))).count();
+    }
+
+    /// Whether a queued replay event's step has been reached
+    pub fn events_due(&self) -> bool {
+        self.pending.front().is_some_and(|e| e.step <= self.step)
+    }
+
+    /// Apply every queued replay event whose step has been reached
+    pub fn apply_due_events(&mut self) {
+        while self.events_due() {
+            let logged = self.pending.pop_front().unwrap();
+            if matches!(logged.event, SimEvent::Rebase(_)) {
+                self.queued_rebases -= 1;
+            }
+            self.apply(logged.event);
+        }
+    }
+
+    /// Apply an event through the same path live input takes
+    pub fn apply(&mut self, event: SimEvent) {
+        match event {
+            SimEvent::SpawnBurst { center, count } => self.spawn_burst(center, count),
+            SimEvent::SpawnSwarm { center, count } => self.spawn_swarm(center, count),
+            SimEvent::SpawnOrbitalSwarm { body, count, radius } => {
+                self.spawn_orbital_swarm(body, count, radius)
+            }
+            SimEvent::SpawnRing { body, count } => self.spawn_ring_particles(body, count),
+            SimEvent::SpawnLagrange { site, count } => self.spawn_at_lagrange(site, count),
+            SimEvent::SetTarget(pos) => self.set_target(pos),
+            SimEvent::SetLagrangeTarget(site) => self.set_lagrange_target(site),
+            SimEvent::ClearTarget => self.clear_target(),
+            SimEvent::ClearParticles => self.clear_particles(),
+            SimEvent::SetSpawnMode(mode) => self.set_spawn_mode(mode),
+            SimEvent::SetPaused(paused) => self.set_paused(paused),
+            SimEvent::SetTimeScale(scale) => self.set_time_scale(scale),
+            SimEvent::SetIntegrator(integrator) => self.set_integrator(integrator),
+            SimEvent::SetPrecision(precision) => self.set_precision(precision),
+            SimEvent::SetParam { name, value } => self.set_param(&name, value),
+            SimEvent::Rebase(shift) => self.rebase(shift),
+        }
+    }
+
+    /// Take the particle slots changed since the last upload, as sorted
+    /// runs of neighbouring slots. Only these may be written: on the GPU
+    /// backend the slots between them hold stale host copies.
+    pub fn take_dirty_particles(&mut self) -> Vec<Range<usize>> {
+        let mut ranges = std::mem::take(&mut self.dirty_particles);
+        ranges.sort_by_key(|r| r.start);
+        let mut runs: Vec<Range<usize>> = Vec::with_capacity(ranges.len());
+        for r in ranges {
+            match runs.last_mut() {
+                Some(last) if r.start <= last.end => last.end = last.end.max(r.end),
+                _ => runs.push(r),
+            }
+        }
+        runs
+    }
+
+    fn mark_dirty(&mut self, idx: usize) {
+        match self.dirty_particles.last_mut() {
+            Some(last) if last.end == idx => last.end += 1,
+            _ => self.dirty_particles.push(idx..idx + 1),
         }
     }
 
@@ -49,32 +256,39 @@ impl Simulation {
         self.particles.iter().position(|p| p.data[3] < 0.5)
     }
 
+    /// The simulation's seeded RNG, for scenario generators
+    pub fn rng(&mut self) -> &mut ChaCha8Rng {
+        &mut self.rng
+    }
+
     /// Spawn a single particle
-    pub fn spawn_particle(&mut self, pos: Vec3, vel: Vec3) {
+    fn spawn_particle(&mut self, pos: Vec3, vel: Vec3) {
         if let Some(idx) = self.find_dead_slot() {
-            let particle = match self.spawn_mode {
+            let mut particle = match self.spawn_mode {
                 SpawnMode::Swarm => GpuParticle::new_swarm(pos, vel, 0.1),
                 SpawnMode::Free => GpuParticle::new_free(pos, vel, 0.1),
                 SpawnMode::Burst => GpuParticle::new_free(pos, vel, 0.05),
             };
+            particle.data[0] *= self.particle_scale;
             self.particles[idx] = particle;
             self.num_alive_particles += 1;
+            self.mark_dirty(idx);
         }
     }
 
     /// Spawn a burst of particles around a position
     pub fn spawn_burst(&mut self, center: Vec3, count: usize) {
-        let mut rng = rand::thread_rng();
+        self.record(SimEvent::SpawnBurst { center, count });
         for _ in 0..count {
             let offset = Vec3::new(
-                rng.gen_range(-0.1..0.1),
-                rng.gen_range(-0.1..0.1),
-                rng.gen_range(-0.1..0.1),
+                self.rng.gen_range(-0.1..0.1),
+                self.rng.gen_range(-0.1..0.1),
+                self.rng.gen_range(-0.1..0.1),
             );
             let vel = Vec3::new(
-                rng.gen_range(-0.5..0.5),
-                rng.gen_range(-0.5..0.5),
-                rng.gen_range(-0.5..0.5),
+                self.rng.gen_range(-0.5..0.5),
+                self.rng.gen_range(-0.5..0.5),
+                self.rng.gen_range(-0.5..0.5),
             );
             self.spawn_particle(center + offset, vel);
         }
@@ -82,22 +296,75 @@ impl Simulation {
 
     /// Spawn a swarm formation around a position
     pub fn spawn_swarm(&mut self, center: Vec3, count: usize) {
-        let mut rng = rand::thread_rng();
+        self.record(SimEvent::SpawnSwarm { center, count });
+        self.spawn_cloud(center, Vec3::ZERO, count, 1.0, SpawnMode::Swarm);
+    }
+
+    /// Spawn a flattened cloud moving with `velocity`. `spread` scales both
+    /// the extent and the velocity jitter. Not logged: used for setup.
+    pub fn spawn_cloud(&mut self, center: Vec3, velocity: Vec3, count: usize, spread: f32, mode: SpawnMode) {
         let old_mode = self.spawn_mode;
-        self.spawn_mode = SpawnMode::Swarm;
+        self.spawn_mode = mode;
 
         for _ in 0..count {
             let offset = Vec3::new(
-                rng.gen_range(-0.15..0.15),
-                rng.gen_range(-0.05..0.05),
-                rng.gen_range(-0.15..0.15),
+                self.rng.gen_range(-0.15..0.15),
+                self.rng.gen_range(-0.05..0.05),
+                self.rng.gen_range(-0.15..0.15),
             );
             let vel = Vec3::new(
-                rng.gen_range(-0.3..0.3),
-                rng.gen_range(-0.15..0.15),
-                rng.gen_range(-0.3..0.3),
+                self.rng.gen_range(-0.3..0.3),
+                self.rng.gen_range(-0.15..0.15),
+                self.rng.gen_range(-0.3..0.3),
             );
-            self.spawn_particle(center + offset, vel);
+            self.spawn_particle(center + offset * spread, velocity + vel * spread);
+        }
+
+        self.spawn_mode = old_mode;
+    }
+
+    /// Spawn one particle of the given kind. Not logged: used for setup.
+    pub fn spawn_with_mode(&mut self, pos: Vec3, vel: Vec3, mode: SpawnMode) {
+        let old_mode = self.spawn_mode;
+        self.spawn_mode = mode;
+        self.spawn_particle(pos, vel);
+        self.spawn_mode = old_mode;
+    }
+
+    /// Index of the body with the given name
+    pub fn find_body(&self, name: &str) -> Option<usize> {
+        self.body_info.iter().position(|b| b.name == name)
+    }
+
+    /// Spawn a cloud at a Lagrange point, moving with it, in the current
+    /// spawn mode
+    pub fn spawn_at_lagrange(&mut self, site: LagrangeSite, count: usize) {
+        self.record(SimEvent::SpawnLagrange { site, count });
+        self.spawn_near_lagrange(site, count, 1.0, self.spawn_mode);
+    }
+
+    /// Spawn a cloud at a Lagrange point. The jitter scales with the pair's
+    /// separation and speed, times `spread`. Not logged: used for setup.
+    pub fn spawn_near_lagrange(&mut self, site: LagrangeSite, count: usize, spread: f32, mode: SpawnMode) {
+        let Some(at) = site.locate(&self.bodies) else {
+            return;
+        };
+        let old_mode = self.spawn_mode;
+        self.spawn_mode = mode;
+
+        let (extent, jitter) = (0.02 * at.separation * spread, 0.01 * at.speed * spread);
+        for _ in 0..count {
+            let offset = Vec3::new(
+                self.rng.gen_range(-1.0..1.0),
+                self.rng.gen_range(-1.0..1.0),
+                self.rng.gen_range(-1.0..1.0),
+            );
+            let vel = Vec3::new(
+                self.rng.gen_range(-1.0..1.0),
+                self.rng.gen_range(-1.0..1.0),
+                self.rng.gen_range(-1.0..1.0),
+            );
+            self.spawn_particle(at.position + offset * extent, at.velocity + vel * jitter);
         }
 
         self.spawn_mode = old_mode;
@@ -105,6 +372,31 @@ impl Simulation {
 
     /// Set the swarm target position
     pub fn set_target(&mut self, pos: Vec3) {
+        self.record(SimEvent::SetTarget(pos));
+        self.target_site = None;
+        self.place_target(pos);
+    }
+
+    /// Point the swarm target at a Lagrange point and keep it there as the
+    /// pair moves
+    pub fn set_lagrange_target(&mut self, site: LagrangeSite) {
+        self.record(SimEvent::SetLagrangeTarget(site));
+        self.target_site = Some(site);
+        self.track_target();
+    }
+
+    /// The Lagrange point the swarm target follows, if any
+    pub fn target_site(&self) -> Option<LagrangeSite> {
+        self.target_site
+    }
+
+    fn track_target(&mut self) {
+        if let Some(at) = self.target_site.and_then(|site| site.locate(&self.bodies)) {
+            self.place_target(at.position);
+        }
+    }
+
+    fn place_target(&mut self, pos: Vec3) {
         self.target_pos = Some(pos);
         self.params.target_x = pos.x;
         self.params.target_y = pos.y;
@@ -114,11 +406,86 @@ impl Simulation {
 
     /// Clear the swarm target
     pub fn clear_target(&mut self) {
+        self.record(SimEvent::ClearTarget);
         self.target_pos = None;
+        self.target_site = None;
         self.params.target_active = 0.0;
     }
 
-    /// Update simulation parameters for this frame
+    pub fn set_spawn_mode(&mut self, mode: SpawnMode) {
+        self.record(SimEvent::SetSpawnMode(mode));
+        self.spawn_mode = mode;
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.record(SimEvent::SetPaused(paused));
+        self.paused = paused;
+    }
+
+    pub fn set_time_scale(&mut self, scale: f32) {
+        self.record(SimEvent::SetTimeScale(scale));
+        self.time_scale = scale;
+    }
+
+    pub fn integrator(&self) -> Integrator {
+        match self.params.integrator {
+            1 => Integrator::Leapfrog,
+            _ => Integrator::Euler,
+        }
+    }
+
+    pub fn set_integrator(&mut self, integrator: Integrator) {
+        self.record(SimEvent::SetIntegrator(integrator));
+        self.params.integrator = match integrator {
+            Integrator::Euler => 0,
+            Integrator::Leapfrog => 1,
+        };
+    }
+
+    pub fn precision(&self) -> Precision {
+        match self.params.body_precision {
+            1 => Precision::Df64,
+            _ => Precision::F32,
+        }
+    }
+
+    pub fn set_precision(&mut self, precision: Precision) {
+        self.record(SimEvent::SetPrecision(precision));
+        self.params.body_precision = match precision {
+            Precision::F32 => 0,
+            Precision::Df64 => 1,
+        };
+    }
+
+    pub fn frame(&self) -> ReferenceFrame {
+        self.frame
+    }
+
+    /// View and output the simulation in another reference frame. Trails
+    /// restart from the bodies' current positions, seen in the new frame.
+    /// Frames naming bodies that don't exist fall back to the scenario's.
+    pub fn set_frame(&mut self, frame: ReferenceFrame) {
+        self.frame = if frame.fits(self.bodies.len()) { frame } else { ReferenceFrame::Scenario };
+        [self.params.frame_kind, self.params.frame_a, self.params.frame_b] = self.frame.params(&self.bodies);
+        self.set_trail_length(self.params.trail_length as usize);
+    }
+
+    /// The reference frame as it stands for the host bodies moved on by
+    /// `lead` years
+    pub fn frame_at(&self, lead: f32) -> Frame {
+        self.frame.resolve(&self.bodies, lead)
+    }
+
+    /// Set a tunable `SimParams` field by name
+    pub fn set_param(&mut self, name: &str, value: f32) {
+        if self.params.set(name, value) {
+            self.record(SimEvent::SetParam { name: name.to_string(), value });
+        } else {
+            log::warn!("Unknown parameter '{}'", name);
+        }
+    }
+
+    /// Update simulation parameters for this step
     pub fn update_params(&mut self, dt: f32) {
         if self.paused {
             self.params.dt = 0.0;
@@ -127,17 +494,209 @@ impl Simulation {
         }
         self.time += self.params.dt;
         self.params.time = self.time;
+        self.step += 1;
+        self.track_target();
+        self.run_emitters();
 
         // Count alive particles
         self.num_alive_particles = self.particles.iter().filter(|p| p.data[3] > 0.5).count() as u32;
     }
 
+    /// Advance one fixed step on the CPU backend
+    pub fn step_cpu(&mut self, dt: f32) {
+        self.update_params(dt);
+        cpu_physics::step_bodies(&mut self.bodies, &self.params);
+        let frame = self.frame_at(0.0);
+        cpu_physics::push_trails(&self.bodies, &mut self.trails, self.params.trail_length as usize, &frame);
+        cpu_physics::step_particles(&mut self.particles, &self.bodies, &self.params);
+    }
+
+    /// Emit this step's share of particles from every active emitter
+    fn run_emitters(&mut self) {
+        let dt = self.params.dt;
+        let time = self.time;
+        let old_mode = self.spawn_mode;
+
+        for i in 0..self.emitters.len() {
+            let e = &mut self.emitters[i];
+            if time < e.start || e.stop.is_some_and(|stop| time > stop) {
+                continue;
+            }
+            e.pending += e.rate * dt;
+            let count = e.pending.floor();
+            e.pending -= count;
+            let (position, velocity, spread) = (e.position, e.velocity, e.spread);
+            self.spawn_mode = e.mode;
+
+            for _ in 0..count as usize {
+                let offset = Vec3::new(
+                    self.rng.gen_range(-1.0..1.0),
+                    self.rng.gen_range(-1.0..1.0),
+                    self.rng.gen_range(-1.0..1.0),
+                ) * spread;
+                self.spawn_particle(position + offset, velocity);
+            }
+        }
+
+        self.spawn_mode = old_mode;
+    }
+
+    /// Move the coordinate origin to `shift`, given in current coordinates,
+    /// so the state near it keeps the finest f32 spacing. Bodies, particles,
+    /// trails, the swarm target and emitters all move by `-shift`. The
+    /// despawn sphere stays centred on the origin, so it moves with it.
+    pub fn rebase(&mut self, shift: Vec3) {
+        self.record(SimEvent::Rebase(shift));
+        self.origin += shift.as_dvec3();
+        self.shift_state(-shift);
+        if let Some(target) = self.target_pos {
+            self.place_target(target - shift);
+        }
+        for emitter in &mut self.emitters {
+            emitter.position -= shift;
+        }
+        self.rebased = Some(self.rebased.unwrap_or(Vec3::ZERO) + shift);
+    }
+
+    /// Move bodies, particles and trails by `delta`. Trails in any frame
+    /// but the scenario's are relative to bodies, so they stay put.
+    pub fn shift_state(&mut self, delta: Vec3) {
+        let trails = if self.frame == ReferenceFrame::Scenario { &mut self.trails[..] } else { &mut [] };
+        shift_positions(&mut self.bodies, &mut self.particles, trails, delta);
+    }
+
+    /// Take the origin shift since the last call, for whatever else holds
+    /// positions in simulation coordinates
+    pub fn take_rebase(&mut self) -> Option<Vec3> {
+        self.rebased.take()
+    }
+
+    /// Move the origin to the floating-origin body once it has strayed
+    /// further than `REBASE_DISTANCE` from it. A replay repeats the logged
+    /// rebases at their recorded steps instead, until the last has run.
+    pub fn rebase_if_due(&mut self) {
+        if self.queued_rebases > 0 {
+            return;
+        }
+        let Some(body) = self.floating_origin.and_then(|i| self.bodies.get(i)) else {
+            return;
+        };
+        let pos = Vec3::new(body.position[0], body.position[1], body.position[2]);
+        if pos.length() > REBASE_DISTANCE {
+            self.rebase(pos);
+        }
+    }
+
+    /// Body and live particle state as CSV, in the scenario's starting
+    /// frame, or in the reference frame if one is set
+    pub fn snapshot_csv(&self) -> String {
+        if self.frame != ReferenceFrame::Scenario {
+            return self.snapshot_csv_in(&self.frame_at(0.0));
+        }
+        let mut out = String::from("kind,index,name,x,y,z,vx,vy,vz,mass\n");
+        let o = self.origin;
+        for (i, (b, info)) in self.bodies.iter().zip(&self.body_info).enumerate() {
+            let (p, pl, v, vl) = (b.position, b.position_low, b.velocity, b.velocity_low);
+            out.push_str(&format!(
+                "body,{},{},{},{},{},{},{},{},{}\n",
+                i, info.name,
+                coordinate(p[0], pl[0], o.x), coordinate(p[1], pl[1], o.y), coordinate(p[2], pl[2], o.z),
+                coordinate(v[0], vl[0], 0.0), coordinate(v[1], vl[1], 0.0), coordinate(v[2], vl[2], 0.0),
+                p[3]
+            ));
+        }
+        for (i, p) in self.particles.iter().enumerate().filter(|(_, p)| p.data[3] > 0.5) {
+            let kind = if p.velocity[3] > 0.5 { "swarm" } else { "free" };
+            out.push_str(&format!(
+                "{},{},,{},{},{},{},{},{},{}\n",
+                kind, i,
+                coordinate(p.position[0], 0.0, o.x), coordinate(p.position[1], 0.0, o.y), coordinate(p.position[2], 0.0, o.z),
+                p.velocity[0], p.velocity[1], p.velocity[2], p.position[3]
+            ));
+        }
+        out
+    }
+
+    /// `snapshot_csv` with positions and velocities as seen in `frame`,
+    /// spin included, at f64
+    fn snapshot_csv_in(&self, frame: &Frame) -> String {
+        let mut out = String::from("kind,index,name,x,y,z,vx,vy,vz,mass\n");
+        let row = |p: DVec3, v: DVec3| {
+            let (p, v) = (frame.frame_position(p), frame.frame_velocity(p, v));
+            format!("{},{},{},{},{},{}", p.x, p.y, p.z, v.x, v.y, v.z)
+        };
+        for (i, (b, info)) in self.bodies.iter().zip(&self.body_info).enumerate() {
+            let state = row(b.position_f64(), b.velocity_f64());
+            out.push_str(&format!("body,{},{},{},{}\n", i, info.name, state, b.position[3]));
+        }
+        for (i, p) in self.particles.iter().enumerate().filter(|(_, p)| p.data[3] > 0.5) {
+            let kind = if p.velocity[3] > 0.5 { "swarm" } else { "free" };
+            let state = row(Vec3::from_slice(&p.position).as_dvec3(), Vec3::from_slice(&p.velocity).as_dvec3());
+            out.push_str(&format!("{},{},,{},{}\n", kind, i, state, p.position[3]));
+        }
+        out
+    }
+
     /// Kill all particles
     pub fn clear_particles(&mut self) {
+        self.record(SimEvent::ClearParticles);
         for p in &mut self.particles {
             p.data[3] = 0.0;
         }
         self.num_alive_particles = 0;
+        self.dirty_particles.clear();
+        self.dirty_particles.push(0..self.particles.len());
+    }
+
+    /// The ring system around a body, if it has one
+    pub fn ring(&self, body_index: usize) -> Option<&GpuRing> {
+        self.rings.iter().find(|r| r.body() == body_index)
+    }
+
+    /// Seed free particles on circular orbits in a body's ring, denser
+    /// where the ring is more opaque. Does nothing for ringless bodies.
+    pub fn spawn_ring_particles(&mut self, body_index: usize, count: usize) {
+        let Some(ring) = self.ring(body_index).copied() else {
+            return;
+        };
+        self.record(SimEvent::SpawnRing { body: body_index, count });
+
+        let body = &self.bodies[body_index];
+        let center = Vec3::new(body.position[0], body.position[1], body.position[2]);
+        let body_vel = Vec3::new(body.velocity[0], body.velocity[1], body.velocity[2]);
+        let gm = self.params.gravitational_constant * body.position[3];
+        // The visual radius is also the collision radius, so the ring is
+        // laid out in the same units the particles are absorbed at
+        let (inner, outer) = (ring.radii[0] * body.velocity[3], ring.radii[1] * body.velocity[3]);
+        let max_opacity = ring.profile.iter().copied().fold(0.0, f32::max);
+        if max_opacity <= 0.0 {
+            return;
+        }
+        let (u, v) = ring.basis();
+
+        let old_mode = self.spawn_mode;
+        self.spawn_mode = SpawnMode::Free;
+        for _ in 0..count {
+            // Uniform in area, thinned by the opacity profile
+            let r = loop {
+                let r = (self.rng.gen_range(inner * inner..=outer * outer)).sqrt();
+                let t = (r - inner) / (outer - inner).max(1e-12);
+                if self.rng.gen::<f32>() * max_opacity <= ring.opacity(t) {
+                    break r;
+                }
+            };
+            let angle = self.rng.gen_range(0.0..std::f32::consts::TAU);
+            let (s, c) = angle.sin_cos();
+            let pos = center + (u * c + v * s) * r;
+            let vel = body_vel + (v * c - u * s) * (gm / r).sqrt();
+            if let Some(idx) = self.find_dead_slot() {
+                self.spawn_particle(pos, vel);
+                let p = &mut self.particles[idx];
+                p.color = [ring.color[0], ring.color[1], ring.color[2], 0.8];
+                p.data[0] *= 0.5;
+            }
+        }
+        self.spawn_mode = old_mode;
     }
 
     /// Spawn particles in an orbit around a body
@@ -145,22 +704,23 @@ impl Simulation {
         if body_index >= self.bodies.len() {
             return;
         }
+        self.record(SimEvent::SpawnOrbitalSwarm { body: body_index, count, radius: orbit_radius });
 
         let body = &self.bodies[body_index];
         let body_pos = Vec3::new(body.position[0], body.position[1], body.position[2]);
         let body_mass = body.position[3];
         let g = self.params.gravitational_constant;
 
-        let mut rng = rand::thread_rng();
         let old_mode = self.spawn_mode;
         self.spawn_mode = SpawnMode::Swarm;
 
         for i in 0..count {
             let angle = (i as f32 / count as f32) * std::f32::consts::TAU
-                + rng.gen_range(-0.1..0.1);
-            let r = orbit_radius + rng.gen_range(-0.02..0.02);
+                + self.rng.gen_range(-0.1..0.1);
+            let r = orbit_radius + self.rng.gen_range(-0.02..0.02);
 
-            let pos = body_pos + Vec3::new(r * angle.cos(), rng.gen_range(-0.01..0.01), r * angle.sin());
+            let pos = body_pos
+                + Vec3::new(r * angle.cos(), self.rng.gen_range(-0.01..0.01), r * angle.sin());
 
             // Orbital velocity
             let orbital_speed = (g * body_mass / r).sqrt() * 0.3; // slower than full orbit
@@ -172,3 +732,82 @@ impl Simulation {
         self.spawn_mode = old_mode;
     }
 }
+
+/// Move every body, particle and trail point by `delta`
+pub fn shift_positions(bodies: &mut [GpuCelestialBody], particles: &mut [GpuParticle], trails: &mut [GridVertex], delta: Vec3) {
+    for b in bodies {
+        b.set_position_f64(b.position_f64() + delta.as_dvec3());
+    }
+    for p in particles {
+        p.position[0] += delta.x;
+        p.position[1] += delta.y;
+        p.position[2] += delta.z;
+    }
+    for v in trails {
+        v.position = (Vec3::from(v.position) + delta).into();
+    }
+}
+
+/// `hi + lo + offset`, printed at f64 only when the low part or offset
+/// carries something f32 cannot
+fn coordinate(hi: f32, lo: f32, offset: f64) -> String {
+    if lo == 0.0 && offset == 0.0 {
+        hi.to_string()
+    } else {
+        (hi as f64 + lo as f64 + offset).to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::presets;
+
+    const STEP: f32 = 1.0 / 60.0;
+
+    /// The solar system preset on the CPU backend, with the origin kept on
+    /// Jupiter so the log holds rebases too
+    fn build() -> Simulation {
+        let mut sim = presets::preset("solar_system").unwrap().build(7, None, &Capacity::default());
+        sim.backend = Backend::Cpu;
+        sim.floating_origin = sim.find_body("Jupiter");
+        sim.start_recording(None);
+        sim
+    }
+
+    /// One step as a headless run takes it
+    fn step(sim: &mut Simulation) {
+        sim.step_cpu(STEP);
+        sim.apply_due_events();
+        sim.rebase_if_due();
+    }
+
+    #[test]
+    fn replay_reproduces_the_recorded_run() {
+        let mut live = build();
+        let jupiter = live.find_body("Jupiter").unwrap();
+        for i in 0..120 {
+            match i {
+                10 => live.spawn_burst(Vec3::new(1.0, 0.0, 0.5), 200),
+                30 => live.set_param("separation_weight", 2.5),
+                50 => live.spawn_orbital_swarm(jupiter, 100, 0.2),
+                70 => live.set_target(Vec3::new(-2.0, 0.1, 1.0)),
+                90 => live.set_time_scale(2.0),
+                _ => {}
+            }
+            step(&mut live);
+        }
+        assert!(live.log.events.iter().any(|e| matches!(e.event, SimEvent::Rebase(_))));
+
+        // Through the saved text, as --replay reads it
+        let log = EventLog::parse(&live.log.to_text()).unwrap();
+        let mut replay = build();
+        replay.load_replay(log);
+        replay.apply_due_events();
+        for _ in 0..120 {
+            step(&mut replay);
+        }
+        assert_eq!(replay.snapshot_csv(), live.snapshot_csv());
+        assert_eq!(replay.log.to_text(), live.log.to_text());
+    }
+}
@@ -0,0 +1,97 @@
+use glam::Vec3;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::types::*;
+
+// ============================================================================
+// Procedural background starfield. Stars are fixed directions drawn at
+// infinity, with apparent magnitudes following the steep rise in star
+// counts towards faint magnitudes and colours from a blackbody fit.
+// About a third of them are crowded into a tilted galactic band.
+// ============================================================================
+
+/// Number of background stars
+pub const STAR_COUNT: usize = 12_000;
+/// Fixed so the sky looks the same in every run and scenario
+const STARFIELD_SEED: u64 = 0x5747_4152;
+
+const BRIGHTEST_MAG: f32 = -1.0;
+const FAINTEST_MAG: f32 = 6.5;
+/// log10 growth of star counts per magnitude
+const COUNT_SLOPE: f32 = 0.45;
+const GALACTIC_FRACTION: f32 = 0.35;
+const GALACTIC_TILT: f32 = 1.05; // radians between the band and the orbital plane
+
+/// Sample an apparent magnitude with density proportional to 10^(k m)
+fn magnitude(rng: &mut ChaCha8Rng) -> f32 {
+    let span = 10f32.powf(COUNT_SLOPE * (FAINTEST_MAG - BRIGHTEST_MAG)) - 1.0;
+    BRIGHTEST_MAG + (1.0 + rng.gen::<f32>() * span).log10() / COUNT_SLOPE
+}
+
+/// Approximate linear RGB colour of a blackbody, normalised to max 1
+fn blackbody(kelvin: f32) -> Vec3 {
+    let t = kelvin / 100.0;
+    let r = if t <= 66.0 { 255.0 } else { 329.7 * (t - 60.0).powf(-0.1332) };
+    let g = if t <= 66.0 {
+        99.47 * t.ln() - 161.12
+    } else {
+        288.12 * (t - 60.0).powf(-0.0755)
+    };
+    let b = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.52 * (t - 10.0).ln() - 305.04
+    };
+    let srgb = Vec3::new(r, g, b).clamp(Vec3::ZERO, Vec3::splat(255.0)) / 255.0;
+    let linear = srgb.powf(2.2);
+    linear / linear.max_element()
+}
+
+fn random_direction(rng: &mut ChaCha8Rng) -> Vec3 {
+    let z: f32 = rng.gen_range(-1.0..1.0);
+    let phi: f32 = rng.gen_range(0.0..std::f32::consts::TAU);
+    let r = (1.0 - z * z).sqrt();
+    Vec3::new(r * phi.cos(), z, r * phi.sin())
+}
+
+/// Direction near the galactic band: a great circle tilted about x
+fn galactic_direction(rng: &mut ChaCha8Rng) -> Vec3 {
+    let longitude: f32 = rng.gen_range(0.0..std::f32::consts::TAU);
+    // Latitude falls off roughly exponentially away from the band
+    let latitude = -rng.gen::<f32>().max(1e-6).ln() * 0.08 * if rng.gen() { 1.0 } else { -1.0 };
+    let band = Vec3::new(
+        latitude.cos() * longitude.cos(),
+        latitude.sin(),
+        latitude.cos() * longitude.sin(),
+    );
+    let (s, c) = GALACTIC_TILT.sin_cos();
+    Vec3::new(band.x, band.y * c - band.z * s, band.y * s + band.z * c)
+}
+
+pub fn generate() -> Vec<StarVertex> {
+    let mut rng = ChaCha8Rng::seed_from_u64(STARFIELD_SEED);
+    (0..STAR_COUNT)
+        .map(|_| {
+            let dir = if rng.gen::<f32>() < GALACTIC_FRACTION {
+                galactic_direction(&mut rng)
+            } else {
+                random_direction(&mut rng)
+            };
+            // Relative flux, compressed so the brightest stars bloom a little
+            // without drowning the faint ones
+            let flux = 10f32.powf(-0.4 * (magnitude(&mut rng) - FAINTEST_MAG));
+            let intensity = 0.04 * flux.powf(0.6);
+            // Cool stars are far more common than hot ones
+            let kelvin = 2800.0 + 9000.0 * rng.gen::<f32>().powi(3);
+            let color = blackbody(kelvin) * intensity;
+            let size = 1.2 + intensity.min(2.0) * 0.8;
+            StarVertex {
+                direction: [dir.x, dir.y, dir.z, size],
+                color: [color.x, color.y, color.z, 1.0],
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,58 @@
+use std::path::Path;
+
+use image::imageops::{self, FilterType};
+use serde::{Deserialize, Serialize};
+
+// ============================================================================
+// Planet surfaces: equirectangular textures loaded from image files, and
+// the procedural styles used when a body has no texture. Every texture is
+// resampled to one size so they can share a texture array.
+// ============================================================================
+
+pub const TEXTURE_WIDTH: u32 = 1024;
+pub const TEXTURE_HEIGHT: u32 = 512;
+/// Full mip chain of a TEXTURE_WIDTH x TEXTURE_HEIGHT image
+pub const TEXTURE_MIPS: u32 = TEXTURE_WIDTH.ilog2() + 1;
+
+/// Procedural surface generated in the fragment shader - must match the
+/// style constants in render.wgsl
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SurfaceStyle {
+    Rocky,      // Mottled terrain in the body colour, darker lowlands
+    Banded,     // Gas giant cloud bands with turbulent edges
+    Terran,     // Oceans in the body colour, land and polar caps
+    Icy,        // Bright ice crossed by dark fractures
+}
+
+impl SurfaceStyle {
+    /// Gas giants above about three Earth masses, rock below
+    pub fn for_mass(mass: f32) -> Self {
+        if mass > 1e-5 {
+            SurfaceStyle::Banded
+        } else {
+            SurfaceStyle::Rocky
+        }
+    }
+
+    pub fn index(self) -> f32 {
+        self as u32 as f32
+    }
+}
+
+/// RGBA8 pixels of one texture, each mip level half the size of the last
+pub type MipChain = Vec<Vec<u8>>;
+
+/// Load an equirectangular image and build its mip chain
+pub fn load_texture(path: &Path) -> Result<MipChain, String> {
+    let image = image::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mut level = imageops::resize(&image.to_rgba8(), TEXTURE_WIDTH, TEXTURE_HEIGHT, FilterType::CatmullRom);
+    let mut mips = Vec::with_capacity(TEXTURE_MIPS as usize);
+    for _ in 0..TEXTURE_MIPS {
+        let (w, h) = ((level.width() / 2).max(1), (level.height() / 2).max(1));
+        let next = imageops::resize(&level, w, h, FilterType::Triangle);
+        mips.push(level.into_raw());
+        level = next;
+    }
+    Ok(mips)
+}
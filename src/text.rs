@@ -0,0 +1,330 @@
+use ab_glyph::{Font, FontRef, PxScale, ScaleFont};
+use bytemuck::{Pod, Zeroable};
+
+// ============================================================================
+// Screen-space text. Printable ASCII is rasterised once into an R8 atlas
+// from egui's bundled monospace font; each frame the caller queues strings
+// and boxes in pixel coordinates, and they are drawn as instanced quads
+// after tone mapping so they stay crisp and unbloomed.
+// ============================================================================
+
+/// Glyph height in pixels
+const FONT_PX: f32 = 15.0;
+const ATLAS_SIZE: (u32, u32) = (256, 64);
+const FIRST_CHAR: char = ' ';
+const LAST_CHAR: char = '~';
+const INITIAL_INSTANCES: usize = 4096;
+
+/// One glyph or box - must match WGSL
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+struct GlyphInstance {
+    rect: [f32; 4], // xy = top-left, zw = size (pixels)
+    uv: [f32; 4],   // xy = top-left, zw = bottom-right
+    color: [f32; 4],
+}
+
+#[derive(Clone, Copy, Default)]
+struct Glyph {
+    uv: [f32; 4],
+    /// Offset of the bitmap from the pen position on the baseline
+    offset: [f32; 2],
+    size: [f32; 2],
+    advance: f32,
+}
+
+pub struct TextRenderer {
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+    screen_buffer: wgpu::Buffer,
+    instance_buffer: wgpu::Buffer,
+    instance_capacity: usize,
+    instance_count: u32,
+    instances: Vec<GlyphInstance>,
+    glyphs: Vec<Glyph>,
+    /// UV of a fully covered texel, for solid boxes
+    white_uv: [f32; 4],
+    ascent: f32,
+    pub line_height: f32,
+}
+
+/// Rasterise printable ASCII into a single-channel atlas. The top-left
+/// 2x2 texels are left solid white.
+fn build_atlas() -> (Vec<u8>, Vec<Glyph>, [f32; 4], f32, f32) {
+    let fonts = egui::FontDefinitions::default();
+    let font = FontRef::try_from_slice(&fonts.font_data["Hack"].font).expect("bundled font");
+    let scaled = font.as_scaled(PxScale::from(FONT_PX));
+
+    let (w, h) = ATLAS_SIZE;
+    let mut pixels = vec![0u8; (w * h) as usize];
+    for (x, y) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+        pixels[y * w as usize + x] = 255;
+    }
+    let texel = [1.0 / w as f32, 1.0 / h as f32];
+    let white_uv = [0.5 * texel[0], 0.5 * texel[1], 1.5 * texel[0], 1.5 * texel[1]];
+
+    let mut glyphs = Vec::new();
+    let (mut pen_x, mut pen_y, mut row_h) = (4u32, 0u32, 0u32);
+    for c in FIRST_CHAR..=LAST_CHAR {
+        let glyph = scaled.scaled_glyph(c);
+        let advance = scaled.h_advance(glyph.id);
+        let Some(outline) = font.outline_glyph(glyph) else {
+            glyphs.push(Glyph { advance, ..Default::default() });
+            continue;
+        };
+        let bounds = outline.px_bounds();
+        let (gw, gh) = (bounds.width() as u32, bounds.height() as u32);
+        if pen_x + gw + 1 > w {
+            pen_x = 0;
+            pen_y += row_h + 1;
+            row_h = 0;
+        }
+        assert!(pen_y + gh <= h, "glyph atlas too small");
+        outline.draw(|x, y, coverage| {
+            let i = (pen_y + y) * w + pen_x + x;
+            pixels[i as usize] = (coverage.clamp(0.0, 1.0) * 255.0) as u8;
+        });
+        glyphs.push(Glyph {
+            uv: [
+                pen_x as f32 * texel[0],
+                pen_y as f32 * texel[1],
+                (pen_x + gw) as f32 * texel[0],
+                (pen_y + gh) as f32 * texel[1],
+            ],
+            offset: [bounds.min.x, bounds.min.y],
+            size: [gw as f32, gh as f32],
+            advance,
+        });
+        pen_x += gw + 1;
+        row_h = row_h.max(gh);
+    }
+
+    let line_height = (scaled.height() + scaled.line_gap()).ceil() + 2.0;
+    (pixels, glyphs, white_uv, scaled.ascent(), line_height)
+}
+
+impl TextRenderer {
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue, output_format: wgpu::TextureFormat) -> Self {
+        let (pixels, glyphs, white_uv, ascent, line_height) = build_atlas();
+
+        let size = wgpu::Extent3d { width: ATLAS_SIZE.0, height: ATLAS_SIZE.1, depth_or_array_layers: 1 };
+        let atlas = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Glyph Atlas"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            atlas.as_image_copy(),
+            &pixels,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(ATLAS_SIZE.0),
+                rows_per_image: None,
+            },
+            size,
+        );
+        // Glyphs are drawn at whole-pixel positions, so no filtering is needed
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Glyph Sampler"),
+            ..Default::default()
+        });
+
+        let screen_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Text Screen"),
+            size: 16,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Text BGL"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Text BG"),
+            layout: &layout,
+            entries: &[
+                wgpu::BindGroupEntry { binding: 0, resource: screen_buffer.as_entire_binding() },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(
+                        &atlas.create_view(&wgpu::TextureViewDescriptor::default()),
+                    ),
+                },
+                wgpu::BindGroupEntry { binding: 2, resource: wgpu::BindingResource::Sampler(&sampler) },
+            ],
+        });
+
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("Text Shader"),
+            source: wgpu::ShaderSource::Wgsl(include_str!("../shaders/text.wgsl").into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Text Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Text Render"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_text",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<GlyphInstance>() as u64,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &wgpu::vertex_attr_array![
+                        0 => Float32x4,
+                        1 => Float32x4,
+                        2 => Float32x4,
+                    ],
+                }],
+                compilation_options: Default::default(),
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_text",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: output_format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+                compilation_options: Default::default(),
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let instance_buffer = Self::create_instance_buffer(device, INITIAL_INSTANCES);
+
+        Self {
+            pipeline,
+            bind_group,
+            screen_buffer,
+            instance_buffer,
+            instance_capacity: INITIAL_INSTANCES,
+            instance_count: 0,
+            instances: Vec::new(),
+            glyphs,
+            white_uv,
+            ascent,
+            line_height,
+        }
+    }
+
+    fn create_instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Text Instances"),
+            size: (capacity * std::mem::size_of::<GlyphInstance>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn glyph(&self, c: char) -> &Glyph {
+        let c = if (FIRST_CHAR..=LAST_CHAR).contains(&c) { c } else { '?' };
+        &self.glyphs[c as usize - FIRST_CHAR as usize]
+    }
+
+    /// Width and height in pixels of a possibly multi-line string
+    pub fn measure(&self, text: &str) -> (f32, f32) {
+        let mut width = 0.0_f32;
+        let mut lines = 0;
+        for line in text.lines() {
+            width = width.max(line.chars().map(|c| self.glyph(c).advance).sum());
+            lines += 1;
+        }
+        (width.ceil(), lines as f32 * self.line_height)
+    }
+
+    /// Queue a string with its top-left corner at (x, y)
+    pub fn text(&mut self, x: f32, y: f32, text: &str, color: [f32; 4]) {
+        let mut baseline = (y + self.ascent).round();
+        for line in text.lines() {
+            let mut pen = x.round();
+            for c in line.chars() {
+                let g = *self.glyph(c);
+                if g.size[0] > 0.0 {
+                    self.instances.push(GlyphInstance {
+                        rect: [pen + g.offset[0], baseline + g.offset[1], g.size[0], g.size[1]],
+                        uv: g.uv,
+                        color,
+                    });
+                }
+                pen += g.advance;
+            }
+            baseline += self.line_height;
+        }
+    }
+
+    /// Queue a solid box
+    pub fn rect(&mut self, x: f32, y: f32, width: f32, height: f32, color: [f32; 4]) {
+        self.instances.push(GlyphInstance {
+            rect: [x.round(), y.round(), width.round(), height.round()],
+            uv: self.white_uv,
+            color,
+        });
+    }
+
+    /// Upload everything queued since the last call
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, width: u32, height: u32) {
+        if self.instances.len() > self.instance_capacity {
+            self.instance_capacity = self.instances.len().next_power_of_two();
+            self.instance_buffer = Self::create_instance_buffer(device, self.instance_capacity);
+        }
+        queue.write_buffer(
+            &self.screen_buffer,
+            0,
+            bytemuck::cast_slice(&[width as f32, height as f32, 0.0, 0.0]),
+        );
+        if !self.instances.is_empty() {
+            queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&self.instances));
+        }
+        self.instance_count = self.instances.len() as u32;
+        self.instances.clear();
+    }
+
+    pub fn draw<'a>(&'a self, rp: &mut wgpu::RenderPass<'a>) {
+        if self.instance_count == 0 {
+            return;
+        }
+        rp.set_pipeline(&self.pipeline);
+        rp.set_bind_group(0, &self.bind_group, &[]);
+        rp.set_vertex_buffer(0, self.instance_buffer.slice(..));
+        rp.draw(0..6, 0..self.instance_count);
+    }
+}
@@ -1,5 +1,5 @@
 use bytemuck::{Pod, Zeroable};
-use glam::Vec3;
+use glam::{DVec3, Vec3};
 
 /// GPU particle data - must match WGSL struct layout exactly
 #[repr(C)]
@@ -47,7 +47,44 @@ pub struct GpuCelestialBody {
     pub position: [f32; 4], // xyz = position, w = mass
     pub velocity: [f32; 4], // xyz = velocity, w = radius
     pub color: [f32; 4],    // rgba
-    pub data: [f32; 4],     // x = is_star, y = orbital_speed
+    pub data: [f32; 4],     // x = is_star, y = orbital_speed, z = fixed, w = physical radius
+    pub position_low: [f32; 4], // xyz = df64 low part of the position
+    pub velocity_low: [f32; 4], // xyz = df64 low part of the velocity
+}
+
+/// A double split into the nearest f32 and the f32 nearest the remainder
+fn split(v: DVec3) -> (Vec3, Vec3) {
+    let hi = v.as_vec3();
+    (hi, (v - hi.as_dvec3()).as_vec3())
+}
+
+impl GpuCelestialBody {
+    pub fn new(position: [f32; 4], velocity: [f32; 4], color: [f32; 4], data: [f32; 4]) -> Self {
+        Self { position, velocity, color, data, position_low: [0.0; 4], velocity_low: [0.0; 4] }
+    }
+
+    /// Position with its df64 low part; just the f32 one in f32 precision
+    pub fn position_f64(&self) -> DVec3 {
+        let (p, l) = (self.position, self.position_low);
+        DVec3::new(p[0] as f64 + l[0] as f64, p[1] as f64 + l[1] as f64, p[2] as f64 + l[2] as f64)
+    }
+
+    pub fn velocity_f64(&self) -> DVec3 {
+        let (v, l) = (self.velocity, self.velocity_low);
+        DVec3::new(v[0] as f64 + l[0] as f64, v[1] as f64 + l[1] as f64, v[2] as f64 + l[2] as f64)
+    }
+
+    pub fn set_position_f64(&mut self, position: DVec3) {
+        let (hi, lo) = split(position);
+        self.position = [hi.x, hi.y, hi.z, self.position[3]];
+        self.position_low = [lo.x, lo.y, lo.z, 0.0];
+    }
+
+    pub fn set_velocity_f64(&mut self, velocity: DVec3) {
+        let (hi, lo) = split(velocity);
+        self.velocity = [hi.x, hi.y, hi.z, self.velocity[3]];
+        self.velocity_low = [lo.x, lo.y, lo.z, 0.0];
+    }
 }
 
 /// Simulation parameters uniform - must match WGSL
@@ -74,13 +111,21 @@ pub struct SimParams {
     pub damping: f32,
     pub swarm_gravity_weight: f32,
     pub time: f32,
+    pub trail_length: u32,
+    pub despawn_radius: f32,
+    pub integrator: u32,    // 0 = symplectic Euler, 1 = leapfrog (drift-kick-drift)
+    pub body_precision: u32, // 0 = f32, 1 = df64 positions and velocities
+    pub one: f32,           // always 1.0, hides df64 error terms from shader optimizers
+    pub frame_kind: u32,    // reference frame: 0 = scenario, 1 = body, 2 = barycentre, 3 = rotating pair
+    pub frame_a: u32,       // body the frame is centred on, or the pair's first
+    pub frame_b: u32,       // the rotating pair's second body
 }
 
 impl Default for SimParams {
     fn default() -> Self {
         Self {
             dt: 0.016,
-            gravitational_constant: 39.4784176, // 4 * pi^2 in AU^3/(M_sun * yr^2)
+            gravitational_constant: 39.478_416, // 4 * pi^2 in AU^3/(M_sun * yr^2)
             num_particles: 0,
             num_bodies: 0,
             separation_radius: 0.1,
@@ -99,19 +144,183 @@ impl Default for SimParams {
             damping: 1.0, // Changed from 0.999 to 1.0 to prevent energy loss
             swarm_gravity_weight: 0.3,
             time: 0.0,
+            trail_length: DEFAULT_TRAIL_LENGTH as u32,
+            despawn_radius: 100.0,
+            integrator: 0,
+            body_precision: 0,
+            one: 1.0,
+            frame_kind: 0,
+            frame_a: 0,
+            frame_b: 0,
         }
     }
 }
 
+impl SimParams {
+    /// Names of the user-tunable fields, in declaration order.
+    /// Bookkeeping fields (dt, counts, target, time) are driven by the simulation.
+    pub const TUNABLE: &'static [&'static str] = &[
+        "gravitational_constant",
+        "separation_radius",
+        "alignment_radius",
+        "cohesion_radius",
+        "separation_weight",
+        "alignment_weight",
+        "cohesion_weight",
+        "max_speed",
+        "max_force",
+        "softening",
+        "damping",
+        "swarm_gravity_weight",
+        "despawn_radius",
+    ];
+
+    /// Read a tunable field by name
+    pub fn get(&self, name: &str) -> Option<f32> {
+        Some(match name {
+            "gravitational_constant" => self.gravitational_constant,
+            "separation_radius" => self.separation_radius,
+            "alignment_radius" => self.alignment_radius,
+            "cohesion_radius" => self.cohesion_radius,
+            "separation_weight" => self.separation_weight,
+            "alignment_weight" => self.alignment_weight,
+            "cohesion_weight" => self.cohesion_weight,
+            "max_speed" => self.max_speed,
+            "max_force" => self.max_force,
+            "softening" => self.softening,
+            "damping" => self.damping,
+            "swarm_gravity_weight" => self.swarm_gravity_weight,
+            "despawn_radius" => self.despawn_radius,
+            _ => return None,
+        })
+    }
+
+    /// Write a tunable field by name. Returns false for unknown names.
+    pub fn set(&mut self, name: &str, value: f32) -> bool {
+        let field = match name {
+            "gravitational_constant" => &mut self.gravitational_constant,
+            "separation_radius" => &mut self.separation_radius,
+            "alignment_radius" => &mut self.alignment_radius,
+            "cohesion_radius" => &mut self.cohesion_radius,
+            "separation_weight" => &mut self.separation_weight,
+            "alignment_weight" => &mut self.alignment_weight,
+            "cohesion_weight" => &mut self.cohesion_weight,
+            "max_speed" => &mut self.max_speed,
+            "max_force" => &mut self.max_force,
+            "softening" => &mut self.softening,
+            "damping" => &mut self.damping,
+            "swarm_gravity_weight" => &mut self.swarm_gravity_weight,
+            "despawn_radius" => &mut self.despawn_radius,
+            _ => return false,
+        };
+        *field = value;
+        true
+    }
+}
+
 /// Camera uniform data
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Pod, Zeroable)]
 pub struct CameraUniform {
-    pub view_proj: [[f32; 4]; 4],
+    pub view_proj: [[f32; 4]; 4], // view and view_proj are relative to the eye
     pub view: [[f32; 4]; 4],
     pub proj: [[f32; 4]; 4],
-    pub eye_pos: [f32; 4],
-    pub screen_size: [f32; 4],
+    pub eye_pos: [f32; 4],     // eye = eye_pos + eye_low
+    pub eye_low: [f32; 4],
+    pub screen_size: [f32; 4], // xy = size, z = time, w = true scale (0/1)
+    pub background: [f32; 4],  // x = starfield brightness
+    pub scene: [f32; 4],       // x = body count, y = trail width (px), z = trail length, w = pixel scale
+    pub selection: [f32; 4],   // x = 0 none, 1 body, 2 particle; y = index
+}
+
+/// Reference frame the scene is drawn in, written by `cs_frame` - must
+/// match WGSL. A simulation point p sits at rows * (p - origin) in it.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct GpuFrame {
+    pub origin: [f32; 4],     // xyz = origin in simulation coordinates
+    pub origin_low: [f32; 4], // xyz = remainder of a df64 origin
+    pub x_axis: [f32; 4],     // frame axes in simulation coordinates
+    pub y_axis: [f32; 4],
+    pub z_axis: [f32; 4],
+}
+
+impl GpuFrame {
+    /// The scenario's own coordinates
+    pub const IDENTITY: Self = Self {
+        origin: [0.0; 4],
+        origin_low: [0.0; 4],
+        x_axis: [1.0, 0.0, 0.0, 0.0],
+        y_axis: [0.0, 1.0, 0.0, 0.0],
+        z_axis: [0.0, 0.0, 1.0, 0.0],
+    };
+}
+
+/// Samples in a ring's opacity profile
+pub const RING_PROFILE_SAMPLES: usize = 16;
+
+/// Planetary ring, drawn around its body - must match WGSL
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct GpuRing {
+    pub normal: [f32; 4],  // xyz = ring plane normal, w = body index
+    pub radii: [f32; 4],   // x = inner, y = outer edge, in body radii
+    pub color: [f32; 4],   // rgb, a = overall opacity
+    pub profile: [f32; RING_PROFILE_SAMPLES], // opacity from inner to outer edge
+}
+
+impl GpuRing {
+    pub fn body(&self) -> usize {
+        self.normal[3] as usize
+    }
+
+    /// Two unit vectors spanning the ring plane
+    pub fn basis(&self) -> (Vec3, Vec3) {
+        let n = Vec3::new(self.normal[0], self.normal[1], self.normal[2]).normalize();
+        let helper = if n.x.abs() > 0.9 { Vec3::Z } else { Vec3::X };
+        let u = n.cross(helper).normalize();
+        (u, n.cross(u))
+    }
+
+    /// Opacity at a fraction `t` of the way from the inner to the outer edge
+    pub fn opacity(&self, t: f32) -> f32 {
+        let x = t.clamp(0.0, 1.0) * (RING_PROFILE_SAMPLES - 1) as f32;
+        let i = (x as usize).min(RING_PROFILE_SAMPLES - 2);
+        let f = x - i as f32;
+        self.profile[i] * (1.0 - f) + self.profile[i + 1] * f
+    }
+}
+
+/// Surface appearance, spin and (for stars) luminosity of a body, parallel
+/// to the body buffer - must match WGSL
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct GpuSurface {
+    pub axis: [f32; 4],      // xyz = spin axis, w = rotation period (years, 0 = no spin)
+    pub meridian: [f32; 4],  // xyz = prime meridian at t = 0, w = texture layer (-1 = procedural)
+    pub secondary: [f32; 4], // rgb = second colour of the procedural surface
+    pub params: [f32; 4],    // x = procedural style, y = seed, z = rotation angle (radians), w = luminosity
+}
+
+impl GpuSurface {
+    /// Copy with the rotation angle at sim time `time`
+    pub fn spun(&self, time: f32) -> Self {
+        let period = self.axis[3];
+        let mut out = *self;
+        if period > 0.0 {
+            let turns = (time as f64 / period as f64).fract();
+            out.params[2] = (turns * std::f64::consts::TAU) as f32;
+        }
+        out
+    }
+}
+
+/// Background star at infinity
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+pub struct StarVertex {
+    pub direction: [f32; 4], // xyz = unit direction, w = radius in pixels
+    pub color: [f32; 4],     // rgb = HDR colour
 }
 
 /// Grid vertex for orbit lines
@@ -123,7 +332,33 @@ pub struct GridVertex {
     pub color: [f32; 4],
 }
 
-/// Maximum particles we can simulate
-pub const MAX_PARTICLES: usize = 65536;
-/// Maximum celestial bodies
-pub const MAX_BODIES: usize = 32;
\ No newline at end of file
+/// Default particle buffer capacity
+pub const DEFAULT_PARTICLE_CAPACITY: usize = 65536;
+/// Default celestial body buffer capacity
+pub const DEFAULT_BODY_CAPACITY: usize = 32;
+/// Largest particle buffer that fits in a default-sized storage binding (128 MiB)
+pub const MAX_PARTICLES: usize = 1 << 21;
+/// Largest body capacity; bounded by the trail buffer size
+pub const MAX_BODIES: usize = 1024;
+/// Default number of history points kept per body trail
+pub const DEFAULT_TRAIL_LENGTH: usize = 512;
+/// Longest trail the orbit buffer has room for
+pub const MAX_TRAIL_LENGTH: usize = 2048;
+/// How far a floating-origin focus body may drift before the origin moves to it (AU)
+pub const REBASE_DISTANCE: f32 = 1.0;
+
+/// Buffer sizes, fixed when the GPU state is created
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Capacity {
+    pub particles: usize,
+    pub bodies: usize,
+}
+
+impl Default for Capacity {
+    fn default() -> Self {
+        Self {
+            particles: DEFAULT_PARTICLE_CAPACITY,
+            bodies: DEFAULT_BODY_CAPACITY,
+        }
+    }
+}
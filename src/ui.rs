@@ -0,0 +1,683 @@
+use std::path::Path;
+
+use egui::{ClippedPrimitive, Slider, TextureId};
+use winit::event::WindowEvent;
+use winit::window::Window;
+
+use crate::camera::{Camera, CameraMode, Projection};
+use crate::camera_path::{CameraKey, CameraPath, Easing, Spline};
+use crate::capture::MAX_CAPTURE_SIZE;
+use crate::follow::FollowMode;
+use crate::frames::ReferenceFrame;
+use crate::gpu::GpuState;
+use crate::lagrange::{LagrangePoint, LagrangeSite};
+use crate::picking::{self, Selection};
+use crate::post::{RenderSettings, ToneMap};
+use crate::scenario::{CameraSpec, Scenario};
+use crate::simulation::{Integrator, Precision, Simulation};
+use crate::types::*;
+use crate::viewports::Viewports;
+
+// ============================================================================
+// Parameter panel: an egui overlay drawn onto the swapchain after tone mapping.
+// Every change goes through the simulation's setters, so tweaks made here
+// are recorded in the event log like any other input.
+// ============================================================================
+
+/// Slider range for each tunable parameter, logarithmic where values span decades
+const PARAM_RANGES: &[(&str, f32, f32, bool)] = &[
+    ("gravitational_constant", 0.0, 100.0, false),
+    ("separation_radius", 0.001, 2.0, true),
+    ("alignment_radius", 0.001, 5.0, true),
+    ("cohesion_radius", 0.001, 5.0, true),
+    ("separation_weight", 0.0, 10.0, false),
+    ("alignment_weight", 0.0, 10.0, false),
+    ("cohesion_weight", 0.0, 10.0, false),
+    ("max_speed", 0.01, 100.0, true),
+    ("max_force", 0.01, 100.0, true),
+    ("softening", 1e-6, 1.0, true),
+    ("damping", 0.9, 1.0, false),
+    ("swarm_gravity_weight", 0.0, 2.0, false),
+    ("despawn_radius", 1.0, 10_000.0, true),
+];
+
+/// What the panel changed that the renderer has to act on
+#[derive(Debug, Default)]
+pub struct PanelActions {
+    /// New trail length; the trails must be refilled from current positions
+    pub trail_length: Option<usize>,
+    /// Render and save a screenshot of this size
+    pub screenshot: Option<(u32, u32)>,
+    /// Switch the camera to this follow mode
+    pub follow: Option<FollowMode>,
+    /// Switch to this reference frame; the trails must be refilled
+    pub frame: Option<ReferenceFrame>,
+}
+
+/// One frame of tessellated UI, ready to paint
+pub struct PanelFrame {
+    paint_jobs: Vec<ClippedPrimitive>,
+    screen: egui_wgpu::ScreenDescriptor,
+    textures_free: Vec<TextureId>,
+    pub actions: PanelActions,
+}
+
+pub struct Panel {
+    ctx: egui::Context,
+    state: egui_winit::State,
+    renderer: egui_wgpu::Renderer,
+    pub visible: bool,
+    ring_particles: usize,
+    /// Pair whose Lagrange points are marked, primary first
+    pub lagrange_pair: Option<(usize, usize)>,
+    lagrange_particles: usize,
+    save_path: String,
+    status: String,
+    /// Size of high-resolution screenshots
+    pub screenshot_size: (u32, u32),
+    /// Outcome of the last screenshot
+    pub screenshot_status: String,
+    /// Keyframes being recorded or played back
+    pub camera_path: CameraPath,
+    /// Drive the camera along `camera_path` by sim time
+    pub path_playing: bool,
+    /// The inspector was shown last frame, so input goes to egui even
+    /// with the panel hidden
+    inspecting: bool,
+}
+
+impl Panel {
+    pub fn new(window: &Window, gpu: &GpuState) -> Self {
+        let ctx = egui::Context::default();
+        let state = egui_winit::State::new(
+            ctx.clone(),
+            egui::ViewportId::ROOT,
+            window,
+            Some(window.scale_factor() as f32),
+            Some(gpu.device.limits().max_texture_dimension_2d as usize),
+        );
+        let renderer = egui_wgpu::Renderer::new(
+            &gpu.device,
+            gpu.config.format,
+            None,
+            1,
+        );
+        Self {
+            ctx,
+            state,
+            renderer,
+            visible: true,
+            ring_particles: 2000,
+            lagrange_pair: None,
+            lagrange_particles: 300,
+            save_path: "scenarios/custom.toml".to_string(),
+            status: String::new(),
+            screenshot_size: (7680, 4320),
+            screenshot_status: String::new(),
+            camera_path: CameraPath::default(),
+            path_playing: false,
+            inspecting: false,
+        }
+    }
+
+    /// Feed a window event to egui. Returns true if egui consumed it.
+    pub fn on_window_event(&mut self, window: &Window, event: &WindowEvent) -> bool {
+        if !self.visible && !self.inspecting {
+            return false;
+        }
+        self.state.on_window_event(window, event).consumed
+    }
+
+    /// True while a text field has focus, so key shortcuts should be ignored
+    pub fn wants_keyboard(&self) -> bool {
+        self.visible && self.ctx.wants_keyboard_input()
+    }
+
+    /// Lay out the panel and the inspector of `selection`, apply their
+    /// changes, and upload the draw data
+    #[allow(clippy::too_many_arguments)]
+    pub fn run(
+        &mut self,
+        window: &Window,
+        gpu: &mut GpuState,
+        encoder: &mut wgpu::CommandEncoder,
+        sim: &mut Simulation,
+        camera: &mut Camera,
+        views: &mut Viewports,
+        scenario: &Scenario,
+        selection: &mut Option<Selection>,
+        follow: &FollowMode,
+    ) -> PanelFrame {
+        let mut actions = PanelActions::default();
+        let input = self.state.take_egui_input(window);
+        let ctx = self.ctx.clone();
+        let output = ctx.run(input, |ctx| {
+            if self.visible {
+                self.draw(ctx, sim, camera, views, &mut gpu.post.settings, scenario, follow, &mut actions);
+            }
+            if let Some(selected) = *selection {
+                draw_inspector(ctx, sim, selected, selection, &mut actions);
+            }
+        });
+        self.inspecting = selection.is_some();
+        self.state.handle_platform_output(window, output.platform_output);
+
+        let paint_jobs = self.ctx.tessellate(output.shapes, output.pixels_per_point);
+        let screen = egui_wgpu::ScreenDescriptor {
+            size_in_pixels: [gpu.config.width, gpu.config.height],
+            pixels_per_point: output.pixels_per_point,
+        };
+        for (id, delta) in &output.textures_delta.set {
+            self.renderer.update_texture(&gpu.device, &gpu.queue, *id, delta);
+        }
+        let commands = self
+            .renderer
+            .update_buffers(&gpu.device, &gpu.queue, encoder, &paint_jobs, &screen);
+        if !commands.is_empty() {
+            gpu.queue.submit(commands);
+        }
+
+        PanelFrame {
+            paint_jobs,
+            screen,
+            textures_free: output.textures_delta.free,
+            actions,
+        }
+    }
+
+    pub fn paint<'rp>(&'rp self, rp: &mut wgpu::RenderPass<'rp>, frame: &'rp PanelFrame) {
+        self.renderer.render(rp, &frame.paint_jobs, &frame.screen);
+    }
+
+    /// Release textures egui no longer needs, once the frame is submitted
+    pub fn finish(&mut self, frame: PanelFrame) {
+        for id in &frame.textures_free {
+            self.renderer.free_texture(id);
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn draw(
+        &mut self,
+        ctx: &egui::Context,
+        sim: &mut Simulation,
+        camera: &mut Camera,
+        views: &mut Viewports,
+        settings: &mut RenderSettings,
+        scenario: &Scenario,
+        follow: &FollowMode,
+        actions: &mut PanelActions,
+    ) {
+        let defaults = scenario.params();
+
+        egui::Window::new("Parameters")
+            .default_pos([12.0, 12.0])
+            .default_width(320.0)
+            .show(ctx, |ui| {
+                egui::CollapsingHeader::new("Simulation")
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        let mut time_scale = sim.time_scale;
+                        ui.horizontal(|ui| {
+                            let slider = Slider::new(&mut time_scale, 1e-4..=1000.0)
+                                .logarithmic(true)
+                                .text("time_scale");
+                            if ui.add(slider).changed() {
+                                sim.set_time_scale(time_scale);
+                            }
+                            if sim.time_scale != scenario.time_scale && ui.small_button("↺").clicked() {
+                                sim.set_time_scale(scenario.time_scale);
+                            }
+                        });
+
+                        let mut integrator = sim.integrator();
+                        egui::ComboBox::from_label("integrator")
+                            .selected_text(format!("{:?}", integrator))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut integrator, Integrator::Euler, "Euler");
+                                ui.selectable_value(&mut integrator, Integrator::Leapfrog, "Leapfrog");
+                            });
+                        if integrator != sim.integrator() {
+                            sim.set_integrator(integrator);
+                        }
+
+                        let mut precision = sim.precision();
+                        egui::ComboBox::from_label("body precision")
+                            .selected_text(format!("{:?}", precision))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut precision, Precision::F32, "F32");
+                                ui.selectable_value(&mut precision, Precision::Df64, "Df64");
+                            });
+                        if precision != sim.precision() {
+                            sim.set_precision(precision);
+                        }
+
+                        let mut trail_length = sim.params.trail_length as usize;
+                        ui.add(Slider::new(&mut trail_length, 2..=MAX_TRAIL_LENGTH).text("trail_length"));
+                        if trail_length != sim.params.trail_length as usize {
+                            actions.trail_length = Some(trail_length);
+                        }
+
+                        let name = |i: Option<usize>| {
+                            i.and_then(|i| sim.body_info.get(i)).map_or("off", |b| b.name.as_str()).to_string()
+                        };
+                        let mut focus = sim.floating_origin;
+                        egui::ComboBox::from_label("floating origin")
+                            .selected_text(name(focus))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut focus, None, "off");
+                                for i in 0..sim.bodies.len() {
+                                    ui.selectable_value(&mut focus, Some(i), name(Some(i)));
+                                }
+                            });
+                        sim.floating_origin = focus;
+                        if sim.origin != glam::DVec3::ZERO {
+                            let o = sim.origin;
+                            ui.label(format!("origin at ({:.3}, {:.3}, {:.3}) AU", o.x, o.y, o.z));
+                        }
+
+                        // Centred on any body, or turning with any body and its primary
+                        let mut frame = sim.frame();
+                        egui::ComboBox::from_label("reference frame")
+                            .selected_text(frame.describe(sim))
+                            .show_ui(ui, |ui| {
+                                let mut choices =
+                                    vec![ReferenceFrame::Scenario, ReferenceFrame::Heliocentric, ReferenceFrame::Barycentric];
+                                choices.extend((0..sim.bodies.len()).map(ReferenceFrame::Body));
+                                choices.extend((0..sim.bodies.len()).filter_map(|i| {
+                                    let primary = picking::primary(sim, Selection::Body(i))?;
+                                    (primary != i).then_some(ReferenceFrame::Rotating(primary, i))
+                                }));
+                                for choice in choices {
+                                    ui.selectable_value(&mut frame, choice, choice.describe(sim));
+                                }
+                            });
+                        if frame != sim.frame() {
+                            actions.frame = Some(frame);
+                        }
+
+                        ui.label(format!(
+                            "t = {:.4} yr   step {}   dt = {:.2e}",
+                            sim.time, sim.step, sim.params.dt
+                        ));
+                        ui.label(format!(
+                            "{} bodies   {} / {} particles",
+                            sim.params.num_bodies, sim.num_alive_particles, sim.params.num_particles
+                        ));
+                        match sim.target_pos {
+                            Some(t) => {
+                                ui.horizontal(|ui| {
+                                    match sim.target_site() {
+                                        Some(site) => ui.label(format!("target {}", site.describe(sim))),
+                                        None => ui.label(format!("target ({:.2}, {:.2}, {:.2})", t.x, t.y, t.z)),
+                                    };
+                                    if ui.small_button("clear").clicked() {
+                                        sim.clear_target();
+                                    }
+                                });
+                            }
+                            None => {
+                                ui.label("target: none (right-click to set)");
+                            }
+                        }
+                    });
+
+                egui::CollapsingHeader::new("Physics")
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        for &(name, min, max, log) in PARAM_RANGES {
+                            let current = sim.params.get(name).unwrap_or_default();
+                            let default = defaults.get(name).unwrap_or_default();
+                            let mut value = current;
+                            ui.horizontal(|ui| {
+                                let slider = Slider::new(&mut value, min..=max)
+                                    .logarithmic(log)
+                                    .clamp_to_range(false)
+                                    .text(name);
+                                if ui.add(slider).changed() {
+                                    sim.set_param(name, value);
+                                }
+                                if current != default && ui.small_button("↺").clicked() {
+                                    sim.set_param(name, default);
+                                }
+                            });
+                        }
+                        if ui.button("Reset all to scenario defaults").clicked() {
+                            for &name in SimParams::TUNABLE {
+                                let default = defaults.get(name).unwrap_or_default();
+                                if sim.params.get(name) != Some(default) {
+                                    sim.set_param(name, default);
+                                }
+                            }
+                            if sim.time_scale != scenario.time_scale {
+                                sim.set_time_scale(scenario.time_scale);
+                            }
+                        }
+                    });
+
+                egui::CollapsingHeader::new("Lagrange points")
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        let name = |i: usize| sim.body_info.get(i).map_or("?", |b| b.name.as_str());
+                        let pair_name = |pair: Option<(usize, usize)>| {
+                            pair.map_or("none".to_string(), |(a, b)| format!("{}-{}", name(a), name(b)))
+                        };
+                        let pairs: Vec<(usize, usize)> = (0..sim.bodies.len())
+                            .filter_map(|i| {
+                                let primary = picking::primary(sim, Selection::Body(i))?;
+                                (primary != i).then_some((primary, i))
+                            })
+                            .collect();
+                        egui::ComboBox::from_label("marked pair")
+                            .selected_text(pair_name(self.lagrange_pair))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut self.lagrange_pair, None, "none");
+                                for pair in pairs {
+                                    ui.selectable_value(&mut self.lagrange_pair, Some(pair), pair_name(Some(pair)));
+                                }
+                            });
+
+                        let Some((primary, secondary)) = self.lagrange_pair else {
+                            return;
+                        };
+                        if sim.bodies.get(primary).is_some_and(|b| b.data[2] > 0.5) {
+                            ui.label(format!("{} is pinned, so L4 and L5 do not hold particles", name(primary)));
+                        }
+                        ui.add(Slider::new(&mut self.lagrange_particles, 10..=5000).logarithmic(true).text("particles"));
+                        let frame = sim.frame_at(0.0);
+                        for point in LagrangePoint::ALL {
+                            let site = LagrangeSite { primary, secondary, point };
+                            let Some(at) = site.locate(&sim.bodies) else {
+                                continue;
+                            };
+                            let p = frame.point_in_frame(at.position);
+                            ui.horizontal(|ui| {
+                                ui.label(format!("{} ({:.3}, {:.3}, {:.3})", point.name(), p.x, p.y, p.z));
+                                if ui.small_button("spawn").clicked() {
+                                    sim.spawn_at_lagrange(site, self.lagrange_particles);
+                                }
+                                if ui.small_button("target").clicked() {
+                                    sim.set_lagrange_target(site);
+                                }
+                            });
+                        }
+                        ui.label(format!("Spawns use the current mode ({:?})", sim.spawn_mode));
+                    });
+
+                if !sim.rings.is_empty() {
+                    egui::CollapsingHeader::new("Rings")
+                        .default_open(false)
+                        .show(ui, |ui| {
+                            ui.add(Slider::new(&mut self.ring_particles, 100..=20_000).logarithmic(true).text("particles"));
+                            let ringed: Vec<usize> = sim.rings.iter().map(|r| r.body()).collect();
+                            for body in ringed {
+                                let name = &sim.body_info[body].name;
+                                if ui.button(format!("Seed {} ring", name)).clicked() {
+                                    sim.spawn_ring_particles(body, self.ring_particles);
+                                }
+                            }
+                            if sim.integrator() == Integrator::Euler {
+                                ui.label("Ring orbits hold together best with the leapfrog integrator");
+                            }
+                        });
+                }
+
+                egui::CollapsingHeader::new("Camera")
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        let mut distance = camera.distance;
+                        let mut azimuth = camera.theta.to_degrees().rem_euclid(360.0);
+                        let mut elevation = camera.phi.to_degrees();
+                        let mut fov = camera.fov.to_degrees();
+                        let mut target = camera.target.to_array();
+
+                        let mut changed = false;
+                        changed |= ui
+                            .add(Slider::new(&mut distance, camera.min_distance..=camera.max_distance)
+                                .logarithmic(true)
+                                .text("distance"))
+                            .changed();
+                        changed |= ui.add(Slider::new(&mut azimuth, 0.0..=360.0).text("azimuth°")).changed();
+                        changed |= ui.add(Slider::new(&mut elevation, -89.0..=89.0).text("elevation°")).changed();
+                        ui.horizontal(|ui| {
+                            ui.label("target");
+                            for axis in &mut target {
+                                changed |= ui.add(egui::DragValue::new(axis).speed(0.01)).changed();
+                            }
+                        });
+                        if ui.add(Slider::new(&mut fov, 10.0..=120.0).text("fov°")).changed() {
+                            camera.fov = fov.to_radians();
+                        }
+                        if changed {
+                            camera.set_orbit(
+                                glam::Vec3::from(target),
+                                distance,
+                                azimuth.to_radians(),
+                                elevation.to_radians(),
+                            );
+                        }
+                        if ui.button("Reset camera").clicked() {
+                            actions.follow = Some(FollowMode::Off);
+                            scenario.camera.apply(camera);
+                        }
+                        let mut flying = camera.mode == CameraMode::Fly;
+                        if ui.checkbox(&mut flying, "fly mode (V)").changed() {
+                            camera.set_mode(if flying { CameraMode::Fly } else { CameraMode::Orbit });
+                        }
+                        if flying {
+                            ui.add(Slider::new(&mut camera.fly_speed, 0.01..=100.0).logarithmic(true).text("fly speed"));
+                        }
+                        let mut orthographic = camera.projection == Projection::Orthographic;
+                        if ui.checkbox(&mut orthographic, "orthographic (U)").changed() {
+                            camera.projection = if orthographic { Projection::Orthographic } else { Projection::Perspective };
+                        }
+                        ui.checkbox(&mut views.split, "top and side views (M)");
+                        ui.separator();
+                        ui.label(format!("Camera {}", follow.describe(sim)));
+                        ui.horizontal(|ui| {
+                            if ui.button("Frame all bodies (B)").clicked() {
+                                actions.follow = Some(FollowMode::Frame((0..sim.bodies.len()).map(Selection::Body).collect()));
+                            }
+                            if ui.add_enabled(*follow != FollowMode::Off, egui::Button::new("Free")).clicked() {
+                                actions.follow = Some(FollowMode::Off);
+                            }
+                        });
+                    });
+
+                egui::CollapsingHeader::new("Camera path")
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        let path = &mut self.camera_path;
+                        egui::ComboBox::from_label("spline")
+                            .selected_text(format!("{:?}", path.spline))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut path.spline, Spline::Linear, "Linear");
+                                ui.selectable_value(&mut path.spline, Spline::CatmullRom, "Catmull-Rom");
+                                ui.selectable_value(&mut path.spline, Spline::Bezier, "Bezier");
+                            });
+                        let mut remove = None;
+                        egui::Grid::new("camera keys").num_columns(3).show(ui, |ui| {
+                            for (i, key) in path.keys.iter_mut().enumerate() {
+                                ui.label(format!("t = {:.4} yr", key.time));
+                                egui::ComboBox::from_id_source(("ease", i))
+                                    .selected_text(format!("{:?}", key.ease))
+                                    .show_ui(ui, |ui| {
+                                        ui.selectable_value(&mut key.ease, Easing::Linear, "Linear");
+                                        ui.selectable_value(&mut key.ease, Easing::In, "In");
+                                        ui.selectable_value(&mut key.ease, Easing::Out, "Out");
+                                        ui.selectable_value(&mut key.ease, Easing::InOut, "InOut");
+                                    });
+                                if ui.small_button("✕").clicked() {
+                                    remove = Some(i);
+                                }
+                                ui.end_row();
+                            }
+                        });
+                        if let Some(i) = remove {
+                            path.keys.remove(i);
+                        }
+                        ui.horizontal(|ui| {
+                            if ui.button("Add key (N)").clicked() {
+                                path.insert(CameraKey::from_camera(camera, sim.time));
+                            }
+                            if ui.add_enabled(!path.keys.is_empty(), egui::Button::new("Clear")).clicked() {
+                                path.keys.clear();
+                            }
+                        });
+                        self.path_playing &= !path.keys.is_empty();
+                        let play = egui::Checkbox::new(&mut self.path_playing, "play by sim time");
+                        if ui.add_enabled(!path.keys.is_empty(), play).changed() && self.path_playing {
+                            actions.follow = Some(FollowMode::Off);
+                        }
+                        ui.label("Saved with the scenario, and used by --export");
+                    });
+
+                egui::CollapsingHeader::new("Rendering")
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        ui.add(Slider::new(&mut settings.exposure, 0.05..=20.0).logarithmic(true).text("exposure"));
+                        ui.add(Slider::new(&mut settings.bloom_intensity, 0.0..=3.0).text("bloom intensity"));
+                        ui.add(Slider::new(&mut settings.bloom_threshold, 0.0..=10.0).text("bloom threshold"));
+                        ui.add(Slider::new(&mut settings.bloom_radius, 0.5..=4.0).text("bloom radius"));
+                        egui::ComboBox::from_label("tone mapping")
+                            .selected_text(format!("{:?}", settings.tonemap))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut settings.tonemap, ToneMap::Aces, "ACES");
+                                ui.selectable_value(&mut settings.tonemap, ToneMap::Reinhard, "Reinhard");
+                                ui.selectable_value(&mut settings.tonemap, ToneMap::Linear, "Linear");
+                            });
+                        ui.add(Slider::new(&mut settings.starfield, 0.0..=4.0).text("starfield"));
+                        ui.add(Slider::new(&mut settings.trail_width, 0.5..=8.0).text("trail width"));
+                        ui.checkbox(&mut settings.true_scale, "true-scale bodies");
+                        if ui.button("Reset rendering").clicked() {
+                            *settings = RenderSettings::default();
+                        }
+                    });
+
+                egui::CollapsingHeader::new("Screenshot")
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        let (width, height) = &mut self.screenshot_size;
+                        ui.horizontal(|ui| {
+                            ui.add(egui::DragValue::new(width).range(1..=MAX_CAPTURE_SIZE));
+                            ui.label("×");
+                            ui.add(egui::DragValue::new(height).range(1..=MAX_CAPTURE_SIZE));
+                            for (label, size) in [("1080p", (1920, 1080)), ("4K", (3840, 2160)), ("8K", (7680, 4320))] {
+                                if ui.small_button(label).clicked() {
+                                    (*width, *height) = size;
+                                }
+                            }
+                        });
+                        if ui.button("Save PNG (Shift+F12)").clicked() {
+                            actions.screenshot = Some(self.screenshot_size);
+                        }
+                        ui.label("F12 saves one at the window size");
+                        if !self.screenshot_status.is_empty() {
+                            ui.label(&self.screenshot_status);
+                        }
+                    });
+
+                egui::CollapsingHeader::new("Save scenario")
+                    .default_open(false)
+                    .show(ui, |ui| {
+                        ui.text_edit_singleline(&mut self.save_path);
+                        if ui.button("Save").clicked() {
+                            self.status = match save_scenario(scenario, sim, camera, &self.camera_path, Path::new(&self.save_path)) {
+                                Ok(()) => format!("Saved {}", self.save_path),
+                                Err(e) => e,
+                            };
+                        }
+                        if !self.status.is_empty() {
+                            ui.label(&self.status);
+                        }
+                    });
+            });
+    }
+}
+
+/// Live state of the selected body or particle
+fn draw_inspector(
+    ctx: &egui::Context,
+    sim: &Simulation,
+    selected: Selection,
+    selection: &mut Option<Selection>,
+    actions: &mut PanelActions,
+) {
+    let Some(info) = picking::inspect(sim, selected) else {
+        return;
+    };
+    let vector = |v: glam::Vec3| format!("({:.5}, {:.5}, {:.5})", v.x, v.y, v.z);
+    egui::Window::new("Inspector")
+        .default_pos([ctx.screen_rect().width() - 300.0, 12.0])
+        .default_width(280.0)
+        .show(ctx, |ui| {
+            ui.heading(&info.name);
+            egui::Grid::new("inspector").num_columns(2).show(ui, |ui| {
+                let mut row = |label: &str, value: String| {
+                    ui.label(label);
+                    ui.label(value);
+                    ui.end_row();
+                };
+                row("position", format!("{} AU", vector(info.position)));
+                row("velocity", format!("{} AU/yr", vector(info.velocity)));
+                row("speed", format!("{:.5} AU/yr", info.velocity.length()));
+                row("mass", format!("{:.4e} M☉", info.mass));
+                if let Some((star, distance)) = &info.star {
+                    row("nearest star", format!("{} at {:.5} AU", star, distance));
+                }
+                if let Some((primary, el, period)) = &info.orbit {
+                    row("orbiting", primary.clone());
+                    row("a", format!("{:.5} AU", el.semi_major_axis));
+                    row("e", format!("{:.5}", el.eccentricity));
+                    row("i", format!("{:.3}°", el.inclination.to_degrees()));
+                    row("Ω", format!("{:.3}°", el.ascending_node.to_degrees()));
+                    row("ω", format!("{:.3}°", el.argument_of_periapsis.to_degrees()));
+                    row("M", format!("{:.3}°", el.mean_anomaly.to_degrees()));
+                    row("period", period.map_or("unbound".to_string(), |p| format!("{:.5} yr", p)));
+                }
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Follow (F)").clicked() {
+                    actions.follow = Some(FollowMode::Centre(selected));
+                }
+                if ui.button("Lock to orbit (K)").clicked() {
+                    actions.follow = Some(FollowMode::OrbitLock(selected));
+                }
+                if let Some(primary) = picking::primary(sim, selected) {
+                    if ui.button("Frame with primary").clicked() {
+                        actions.follow = Some(FollowMode::Frame(vec![selected, Selection::Body(primary)]));
+                    }
+                }
+            });
+            if ui.button("Deselect").clicked() {
+                *selection = None;
+            }
+        });
+}
+
+/// The running scenario with the current parameters, time scale, trail
+/// length, camera, camera path and seed, as a scenario file
+fn save_scenario(
+    scenario: &Scenario,
+    sim: &Simulation,
+    camera: &Camera,
+    camera_path: &CameraPath,
+    path: &Path,
+) -> Result<(), String> {
+    let mut out = scenario.clone();
+    let defaults = SimParams::default();
+    out.params = SimParams::TUNABLE
+        .iter()
+        .filter_map(|&name| {
+            let value = sim.params.get(name)?;
+            (Some(value) != defaults.get(name)).then(|| (name.to_string(), value))
+        })
+        .collect();
+    out.seed = Some(sim.seed);
+    out.time_scale = sim.time_scale;
+    out.trail_length = sim.params.trail_length as usize;
+    out.camera = CameraSpec::from_camera(camera);
+    out.camera_path = (!camera_path.keys.is_empty()).then(|| camera_path.clone());
+    out.validate()?;
+    out.save(path)
+}
@@ -0,0 +1,183 @@
+use glam::Vec3;
+
+use crate::camera::{Camera, MouseButton, Projection};
+
+// ============================================================================
+// Viewports: the window can be split into the main 3D view and two
+// orthographic insets stacked on the right, a top-down map of the ecliptic
+// (the y = 0 plane, where swarm targets go) and a side elevation. Each has
+// its own camera and is drawn into its own rectangle of the scene target.
+// The insets stay centred on the main camera's target, but zoom and pan on
+// their own, and do not turn: both have x to the right, so a point sits in
+// the same column in each.
+// ============================================================================
+
+/// Share of the window width taken by the insets
+const INSET_WIDTH: f32 = 0.32;
+/// Gap between views, pixels
+const GAP: u32 = 2;
+/// Tilt of the map away from straight down, so its up stays -z
+const MAP_TILT: f32 = 1e-3;
+
+/// A rectangle of the target drawn through one camera: the main camera, or
+/// inset `inset`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    pub inset: Option<usize>,
+    /// x, y, width, height in pixels
+    pub rect: [u32; 4],
+}
+
+impl Viewport {
+    /// The main camera over a whole `width` x `height` target
+    pub fn full(width: u32, height: u32) -> Self {
+        Self { inset: None, rect: [0, 0, width, height] }
+    }
+
+    pub fn contains(&self, (x, y): (f32, f32)) -> bool {
+        let [left, top, width, height] = self.rect.map(|v| v as f32);
+        x >= left && x < left + width && y >= top && y < top + height
+    }
+
+    /// Window pixel `pos` relative to this view
+    pub fn local(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        (x - self.rect[0] as f32, y - self.rect[1] as f32)
+    }
+
+    /// World ray through window pixel `pos`, seen through `camera`
+    pub fn ray(&self, camera: &Camera, pos: (f32, f32)) -> (Vec3, Vec3) {
+        let (x, y) = self.local(pos);
+        camera.screen_to_world_ray(x, y, self.rect[2] as f32, self.rect[3] as f32)
+    }
+
+    /// Where the ray through window pixel `pos` meets the ecliptic
+    pub fn on_ecliptic(&self, camera: &Camera, pos: (f32, f32)) -> Option<Vec3> {
+        let (origin, dir) = self.ray(camera, pos);
+        camera.ray_plane_intersection(origin, dir, 0.0)
+    }
+}
+
+pub struct Inset {
+    pub title: &'static str,
+    pub camera: Camera,
+}
+
+impl Inset {
+    /// Title with the width of ground the view spans
+    pub fn caption(&self, viewport: &Viewport) -> String {
+        let [_, _, width, height] = viewport.rect;
+        let across = self.camera.pixel_size(self.camera.distance, height as f32) * width as f32;
+        format!("{}  {:.3} AU across", self.title, across)
+    }
+}
+
+pub struct Viewports {
+    /// Show the insets beside the main view
+    pub split: bool,
+    pub insets: Vec<Inset>,
+    /// Main camera target the insets were last centred on
+    anchor: Vec3,
+}
+
+impl Viewports {
+    pub fn new() -> Self {
+        let inset = |title, phi| {
+            let mut camera = Camera::new();
+            camera.projection = Projection::Orthographic;
+            camera.set_orbit(Vec3::ZERO, camera.distance, 0.0, phi);
+            Inset { title, camera }
+        };
+        Self {
+            split: false,
+            insets: vec![
+                inset("TOP  x-z", std::f32::consts::FRAC_PI_2 - MAP_TILT),
+                inset("SIDE  x-y", 0.0),
+            ],
+            anchor: Vec3::ZERO,
+        }
+    }
+
+    /// Centre the insets on the main camera's target, spanning about what
+    /// it shows there
+    pub fn reset(&mut self, main: &Camera) {
+        for inset in &mut self.insets {
+            let camera = &mut inset.camera;
+            camera.min_distance = main.min_distance;
+            camera.max_distance = main.max_distance;
+            camera.set_orbit(main.target, main.distance, camera.theta, camera.phi);
+        }
+        self.anchor = main.target;
+    }
+
+    /// The main view first, then each inset when split, for a `width` x
+    /// `height` window
+    pub fn viewports(&self, (width, height): (u32, u32)) -> Vec<Viewport> {
+        if !self.split {
+            return vec![Viewport::full(width, height)];
+        }
+        let column = ((width as f32 * INSET_WIDTH) as u32).clamp(1, width.saturating_sub(GAP + 1).max(1));
+        let main = width.saturating_sub(column + GAP).max(1);
+        let count = self.insets.len() as u32;
+        let row = (height.saturating_sub(GAP * (count - 1)) / count).max(1);
+        let mut views = vec![Viewport { inset: None, rect: [0, 0, main, height] }];
+        views.extend((0..count).map(|i| Viewport {
+            inset: Some(i as usize),
+            rect: [width - column, i * (row + GAP), column, row],
+        }));
+        views
+    }
+
+    /// The view under window pixel `pos`, the main one between views
+    pub fn viewport_at(&self, size: (u32, u32), pos: (f32, f32)) -> Viewport {
+        let views = self.viewports(size);
+        views.iter().copied().find(|v| v.contains(pos)).unwrap_or(views[0])
+    }
+
+    /// The camera drawing `viewport`
+    pub fn camera<'a>(&'a self, main: &'a Camera, viewport: &Viewport) -> &'a Camera {
+        viewport.inset.map_or(main, |i| &self.insets[i].camera)
+    }
+
+    pub fn camera_mut<'a>(&'a mut self, main: &'a mut Camera, viewport: &Viewport) -> &'a mut Camera {
+        match viewport.inset {
+            Some(i) => &mut self.insets[i].camera,
+            None => main,
+        }
+    }
+
+    /// Start a drag on the camera of `viewport`, or end it on every camera.
+    /// The insets pan but never orbit, so they keep looking along their axes.
+    pub fn mouse_button(&mut self, main: &mut Camera, viewport: &Viewport, button: MouseButton, pressed: bool) {
+        if !pressed {
+            main.handle_mouse_button(button, false);
+            for inset in &mut self.insets {
+                inset.camera.handle_mouse_button(button, false);
+            }
+        } else if viewport.inset.is_none() || button != MouseButton::Left {
+            self.camera_mut(main, viewport).handle_mouse_button(button, true);
+        }
+    }
+
+    pub fn mouse_move(&mut self, main: &mut Camera, x: f32, y: f32) {
+        main.handle_mouse_move(x, y);
+        for inset in &mut self.insets {
+            inset.camera.handle_mouse_move(x, y);
+        }
+    }
+
+    /// Carry the insets along with the main target, fit every camera to
+    /// its view and smooth the insets' own zooming and panning
+    pub fn update(&mut self, main: &mut Camera, size: (u32, u32), dt: f32) {
+        let views = self.viewports(size);
+        main.resize(views[0].rect[2], views[0].rect[3]);
+        let delta = main.target - self.anchor;
+        self.anchor = main.target;
+        for (i, inset) in self.insets.iter_mut().enumerate() {
+            inset.camera.translate(delta);
+            if let Some(view) = views.get(i + 1) {
+                inset.camera.resize(view.rect[2], view.rect[3]);
+            }
+            inset.camera.update(dt);
+        }
+    }
+}